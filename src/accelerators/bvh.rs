@@ -0,0 +1,530 @@
+use crate::geometry::{Point, Ray, AABB};
+use crate::shapes::{Intersection, ShapeOperations};
+
+/// Cantidad de buckets en los que se divide cada eje para evaluar la
+/// heurística de área superficial.
+const N_BUCKETS: usize = 12;
+
+/// Máxima cantidad de primitivas que puede tener una hoja.
+const MAX_PRIMITIVES_IN_NODE: usize = 4;
+
+/// Profundidad máxima del árbol. Los nodos a esa profundidad son hojas
+/// aunque tengan muchas primitivas, así la pila del recorrido nunca se
+/// llena.
+const MAX_DEPTH: usize = 64;
+
+/// Bounding volume hierarchy construida con la heurística de área superficial
+/// (SAH). El árbol se guarda aplanado en un vector en orden depth-first: el
+/// primer hijo de un nodo interior está justo después de él, y el nodo guarda
+/// el índice del segundo hijo.
+///
+/// La BVH no es dueña de las primitivas. Al construirla se reordena el vector
+/// de primitivas para que cada hoja apunte a un rango contiguo, así que hay
+/// que pasarle el mismo vector al momento de intersecar.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug, Default)]
+pub struct BVH {
+    nodes: Vec<LinearNode>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct LinearNode {
+    caja: AABB,
+    /// En una hoja es el índice de la primera primitiva, en un nodo interior
+    /// es el índice del segundo hijo.
+    offset: usize,
+    /// Cantidad de primitivas, 0 si es un nodo interior.
+    n_primitives: u32,
+    /// Eje en el que se partió el nodo interior.
+    axis: u8,
+}
+
+/// Datos de cada primitiva que se usan durante la construcción.
+#[derive(Clone, Copy, Debug)]
+struct PrimitiveInfo {
+    index: usize,
+    caja: AABB,
+    centroid: Point,
+}
+
+/// Nodo del árbol antes de aplanarlo.
+enum BuildNode {
+    Leaf {
+        caja: AABB,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        caja: AABB,
+        axis: usize,
+        children: Box<[BuildNode; 2]>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    count: usize,
+    caja: Option<AABB>,
+}
+
+impl BVH {
+    /// Construye la jerarquía y reordena `primitivas` según las hojas.
+    pub fn new<T: ShapeOperations>(primitivas: &mut Vec<T>) -> BVH {
+        if primitivas.is_empty() {
+            return BVH::default();
+        }
+
+        let mut info: Vec<PrimitiveInfo> = primitivas
+            .iter()
+            .enumerate()
+            .map(|(index, p)| {
+                let caja = *p.bounding_box();
+                PrimitiveInfo {
+                    index,
+                    caja,
+                    centroid: caja.centroid(),
+                }
+            })
+            .collect();
+
+        let mut ordered = Vec::with_capacity(primitivas.len());
+        let mut total_nodes = 0;
+        let root = Self::build(&mut info, &mut ordered, &mut total_nodes, 0);
+
+        // reordeno las primitivas sin necesidad de clonarlas
+        let mut viejas: Vec<Option<T>> =
+            std::mem::take(primitivas).into_iter().map(Some).collect();
+        *primitivas =
+            ordered.iter().map(|&i| viejas[i].take().unwrap()).collect();
+
+        let mut nodes = Vec::with_capacity(total_nodes);
+        Self::flatten(&root, &mut nodes);
+
+        BVH { nodes }
+    }
+
+    fn build(
+        info: &mut [PrimitiveInfo],
+        ordered: &mut Vec<usize>,
+        total_nodes: &mut usize,
+        depth: usize,
+    ) -> BuildNode {
+        *total_nodes += 1;
+
+        let caja = info
+            .iter()
+            .skip(1)
+            .fold(info[0].caja, |acc, p| acc.union(&p.caja));
+
+        let make_leaf = |info: &[PrimitiveInfo], ordered: &mut Vec<usize>| {
+            let first = ordered.len();
+            ordered.extend(info.iter().map(|p| p.index));
+            BuildNode::Leaf {
+                caja,
+                first,
+                count: info.len(),
+            }
+        };
+
+        if info.len() == 1 || depth + 1 >= MAX_DEPTH {
+            return make_leaf(info, ordered);
+        }
+
+        let centroid_box = info
+            .iter()
+            .skip(1)
+            .fold(AABB::from_point(&info[0].centroid), |acc, p| {
+                AABB::union_point(&acc, &p.centroid)
+            });
+        let axis = centroid_box.maximum_extent();
+
+        // si todos los centroides coinciden no hay forma de separarlos, pero
+        // si son muchos los parto a la mitad para que las hojas sigan siendo
+        // chicas
+        let coinciden = centroid_box.max()[axis] == centroid_box.min()[axis];
+        if coinciden && info.len() <= MAX_PRIMITIVES_IN_NODE {
+            return make_leaf(info, ordered);
+        }
+
+        let mid = if coinciden {
+            info.len() / 2
+        } else if info.len() <= 2 {
+            // con tan pocas primitivas no vale la pena calcular el SAH
+            info.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+            info.len() / 2
+        } else {
+            match Self::split_sah(info, &caja, &centroid_box, axis) {
+                Some(mid) => mid,
+                None => return make_leaf(info, ordered),
+            }
+        };
+
+        let (left, right) = info.split_at_mut(mid);
+        let children = Box::new([
+            Self::build(left, ordered, total_nodes, depth + 1),
+            Self::build(right, ordered, total_nodes, depth + 1),
+        ]);
+
+        BuildNode::Interior {
+            caja,
+            axis,
+            children,
+        }
+    }
+
+    /// Separa las primitivas usando la heurística de área superficial.
+    /// Devuelve el índice de la partición, o None si conviene hacer una hoja.
+    fn split_sah(
+        info: &mut [PrimitiveInfo],
+        caja: &AABB,
+        centroid_box: &AABB,
+        axis: usize,
+    ) -> Option<usize> {
+        let bucket_of = |p: &PrimitiveInfo| {
+            let b = (N_BUCKETS as f64 * centroid_box.offset(&p.centroid)[axis])
+                as usize;
+            b.min(N_BUCKETS - 1)
+        };
+
+        let mut buckets = [Bucket {
+            count: 0,
+            caja: None,
+        }; N_BUCKETS];
+
+        for p in info.iter() {
+            let b = &mut buckets[bucket_of(p)];
+            b.count += 1;
+            b.caja = Some(match b.caja {
+                Some(c) => c.union(&p.caja),
+                None => p.caja,
+            });
+        }
+
+        // costo de partir después de cada bucket, tomando el costo de
+        // intersecar una primitiva como 1 y el de recorrer un nodo como 1/8
+        let area = |cajas: &[Bucket]| -> (usize, f64) {
+            let mut count = 0;
+            let mut union: Option<AABB> = None;
+            for b in cajas {
+                count += b.count;
+                if let Some(c) = b.caja {
+                    union = Some(match union {
+                        Some(u) => u.union(&c),
+                        None => c,
+                    });
+                }
+            }
+            (count, union.map_or(0.0, |u| u.surface_area()))
+        };
+
+        let total_area = caja.surface_area();
+        let (mut min_cost, mut min_bucket) = (f64::INFINITY, 0);
+
+        for i in 0..(N_BUCKETS - 1) {
+            let (count_0, area_0) = area(&buckets[..=i]);
+            let (count_1, area_1) = area(&buckets[i + 1..]);
+
+            let cost = if total_area > 0.0 {
+                0.125
+                    + (count_0 as f64 * area_0 + count_1 as f64 * area_1)
+                        / total_area
+            } else {
+                // caja degenerada, cuento primitivas nomás
+                0.125 + count_0.max(count_1) as f64
+            };
+
+            if cost < min_cost {
+                min_cost = cost;
+                min_bucket = i;
+            }
+        }
+
+        let leaf_cost = info.len() as f64;
+        if info.len() <= MAX_PRIMITIVES_IN_NODE && min_cost >= leaf_cost {
+            return None;
+        }
+
+        // particiono: primero las que van a la izquierda
+        let mut mid = 0;
+        for i in 0..info.len() {
+            if bucket_of(&info[i]) <= min_bucket {
+                info.swap(i, mid);
+                mid += 1;
+            }
+        }
+
+        if mid == 0 || mid == info.len() {
+            // no debería pasar porque los centroides no coinciden, pero por
+            // las dudas parto a la mitad
+            info.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+            mid = info.len() / 2;
+        }
+
+        Some(mid)
+    }
+
+    /// Aplana el árbol en orden depth-first, devuelve el índice del nodo.
+    fn flatten(node: &BuildNode, nodes: &mut Vec<LinearNode>) -> usize {
+        let index = nodes.len();
+
+        match node {
+            BuildNode::Leaf { caja, first, count } => {
+                nodes.push(LinearNode {
+                    caja: *caja,
+                    offset: *first,
+                    n_primitives: u32::try_from(*count)
+                        .expect("Demasiadas primitivas en una hoja de la BVH"),
+                    axis: 0,
+                });
+            }
+            BuildNode::Interior {
+                caja,
+                axis,
+                children,
+            } => {
+                nodes.push(LinearNode {
+                    caja: *caja,
+                    offset: 0,
+                    n_primitives: 0,
+                    axis: *axis as u8,
+                });
+                Self::flatten(&children[0], nodes);
+                nodes[index].offset = Self::flatten(&children[1], nodes);
+            }
+        }
+
+        index
+    }
+
+    /// Devuelve la intersección más cercana entre el rayo y las primitivas,
+    /// junto con el índice de la primitiva chocada.
+    pub fn intersect<'a, T: ShapeOperations>(
+        &self,
+        primitivas: &'a [T],
+        rayo: &Ray,
+    ) -> Option<(usize, Intersection<'a>)> {
+        let mut menor: Option<(usize, Intersection<'a>)> = None;
+
+        // cada vez que encuentro un choque achico el max_t del rayo, así las
+        // cajas y primitivas que están más lejos se descartan enseguida
+        let mut rayo = *rayo;
+
+        self.traverse(&mut rayo, |rayo, first, count| {
+            for (i, p) in primitivas[first..first + count].iter().enumerate() {
+                if let Some(choque) = p.intersect(rayo) {
+                    if choque.t() < rayo.max_t() {
+                        *rayo = rayo.with_max_t(choque.t());
+                        menor = Some((first + i, choque));
+                    }
+                }
+            }
+            false
+        });
+
+        menor
+    }
+
+    /// Devuelve true si el rayo choca alguna primitiva antes de su max_t.
+    /// Termina apenas encuentra un choque, sin buscar el más cercano.
+    pub fn is_intersecting<T: ShapeOperations>(
+        &self,
+        primitivas: &[T],
+        rayo: &Ray,
+    ) -> bool {
+        let mut choca = false;
+        let mut rayo = *rayo;
+
+        self.traverse(&mut rayo, |rayo, first, count| {
+            choca = primitivas[first..first + count]
+                .iter()
+                .any(|p| p.is_intersecting(rayo));
+            choca
+        });
+
+        choca
+    }
+
+    /// Recorre los nodos que atraviesa el rayo, visitando primero el hijo más
+    /// cercano. Por cada hoja llama a `visit_leaf` con el rango de primitivas;
+    /// si devuelve true se corta el recorrido. `visit_leaf` puede achicar el
+    /// max_t del rayo para descartar los nodos más lejanos.
+    fn traverse<F>(&self, rayo: &mut Ray, mut visit_leaf: F)
+    where
+        F: FnMut(&mut Ray, usize, usize) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let dir_is_neg =
+            [rayo.dir().x < 0.0, rayo.dir().y < 0.0, rayo.dir().z < 0.0];
+
+        // como mucho hay un nodo pendiente por nivel del árbol
+        let mut stack = [0usize; MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node.caja.intersect_ray(rayo).is_some() {
+                if node.n_primitives > 0 {
+                    if visit_leaf(rayo, node.offset, node.n_primitives as usize)
+                    {
+                        return;
+                    }
+                } else if dir_is_neg[node.axis as usize] {
+                    // el segundo hijo está más cerca
+                    stack[stack_len] = current + 1;
+                    stack_len += 1;
+                    current = node.offset;
+                    continue;
+                } else {
+                    stack[stack_len] = node.offset;
+                    stack_len += 1;
+                    current += 1;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                return;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_float;
+    use crate::geometry::{Transform, Vector};
+    use crate::material::Material;
+    use crate::shapes::{Sphere, Triangle};
+
+    fn random_point(scale: f64) -> Point {
+        Point::new(
+            scale * (rand::random::<f64>() - 0.5),
+            scale * (rand::random::<f64>() - 0.5),
+            scale * (rand::random::<f64>() - 0.5),
+        )
+    }
+
+    #[test]
+    fn bvh_vacía_no_choca() {
+        let mut primitivas: Vec<Triangle> = Vec::new();
+        let bvh = BVH::new(&mut primitivas);
+
+        let rayo = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        assert!(bvh.intersect(&primitivas, &rayo).is_none());
+        assert!(!bvh.is_intersecting(&primitivas, &rayo));
+    }
+
+    #[test]
+    fn bvh_conserva_las_primitivas() {
+        let mut primitivas: Vec<Sphere> = (0..100)
+            .map(|i| {
+                let centro = Vector::new(i as f64, 0.0, 0.0);
+                Sphere::new(
+                    &crate::geometry::create_translation(&centro),
+                    0.25,
+                    &Material::default(),
+                )
+            })
+            .collect();
+
+        let bvh = BVH::new(&mut primitivas);
+        assert_eq!(primitivas.len(), 100);
+
+        // cada hoja apunta a un rango válido y todas las primitivas están en
+        // alguna hoja una sola vez
+        let mut vistas = vec![0; primitivas.len()];
+        for node in &bvh.nodes {
+            if node.n_primitives > 0 {
+                let n = node.n_primitives as usize;
+                assert!(n <= MAX_PRIMITIVES_IN_NODE);
+                for v in &mut vistas[node.offset..node.offset + n] {
+                    *v += 1;
+                }
+            }
+        }
+        assert!(vistas.iter().all(|v| *v == 1));
+    }
+
+    #[test]
+    fn centroides_coincidentes() {
+        // todas en el mismo lugar, no se pueden separar por el centroide
+        let mut primitivas: Vec<Sphere> = (0..100)
+            .map(|i| {
+                Sphere::new(
+                    &Transform::identity(),
+                    1.0 + i as f64 * 0.01,
+                    &Material::default(),
+                )
+            })
+            .collect();
+
+        let bvh = BVH::new(&mut primitivas);
+
+        assert!(bvh.nodes.iter().all(|node| {
+            node.n_primitives as usize <= MAX_PRIMITIVES_IN_NODE
+        }));
+
+        let rayo = Ray::new(
+            &Point::new(-10.0, 0.0, 0.0),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+        let (_, choque) = bvh.intersect(&primitivas, &rayo).unwrap();
+        assert_eq_float!(choque.t(), 10.0 - 1.99);
+    }
+
+    #[test]
+    fn bvh_coincide_con_fuerza_bruta() {
+        let mut primitivas: Vec<Triangle> = (0..500)
+            .map(|_| {
+                let centro = random_point(20.0);
+                Triangle::new(
+                    &(centro + random_point(1.0).coords),
+                    &(centro + random_point(1.0).coords),
+                    &(centro + random_point(1.0).coords),
+                    &Transform::identity(),
+                    &Material::default(),
+                )
+            })
+            .collect();
+
+        let bvh = BVH::new(&mut primitivas);
+
+        for _ in 0..1000 {
+            let rayo = Ray::new(
+                &random_point(30.0),
+                &random_point(1.0).coords,
+                f64::INFINITY,
+            );
+
+            let esperado = primitivas
+                .iter()
+                .filter_map(|p| p.intersect(&rayo))
+                .min_by(|a, b| a.t().total_cmp(&b.t()));
+            let obtenido = bvh.intersect(&primitivas, &rayo);
+
+            assert_eq!(esperado.is_some(), obtenido.is_some());
+            assert_eq!(
+                esperado.is_some(),
+                bvh.is_intersecting(&primitivas, &rayo)
+            );
+            if let (Some(esperado), Some((_, obtenido))) = (esperado, obtenido)
+            {
+                assert_eq_float!(esperado.t(), obtenido.t());
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::geometry::Point;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Árbol k-d balanceado sobre puntos con datos asociados, para buscar los
+/// puntos cercanos a uno dado (por ejemplo los fotones de un photon map).
+///
+/// El árbol está implícito en el vector: cada rango [lo, hi) tiene su nodo en
+/// el elemento del medio, los menores según el eje de corte a la izquierda y
+/// los mayores a la derecha.
+#[derive(Clone, Debug)]
+pub struct KdTree<T> {
+    items: Vec<(Point, T)>,
+    ejes: Vec<u8>, // eje de corte del nodo que está en cada índice
+}
+
+/// Candidato de la búsqueda de los más cercanos, ordenado por distancia.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Candidato {
+    distancia_2: f64,
+    índice: usize,
+}
+
+impl Eq for Candidato {}
+
+impl PartialOrd for Candidato {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidato {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distancia_2.total_cmp(&other.distancia_2)
+    }
+}
+
+impl<T> KdTree<T> {
+    pub fn new(mut items: Vec<(Point, T)>) -> KdTree<T> {
+        let mut ejes = vec![0; items.len()];
+        let n = items.len();
+        Self::build(&mut items, &mut ejes, 0, n);
+
+        KdTree { items, ejes }
+    }
+
+    fn build(items: &mut [(Point, T)], ejes: &mut [u8], lo: usize, hi: usize) {
+        if hi - lo <= 1 {
+            return;
+        }
+
+        // corto en el eje en el que los puntos están más dispersos
+        let (mut min, mut max) = (items[lo].0, items[lo].0);
+        for (p, _) in &items[lo..hi] {
+            min = min.inf(p);
+            max = max.sup(p);
+        }
+        let eje = (max - min).imax();
+
+        let mid = (lo + hi) / 2;
+        items[lo..hi].select_nth_unstable_by(mid - lo, |a, b| {
+            a.0[eje].total_cmp(&b.0[eje])
+        });
+        ejes[mid] = eje as u8;
+
+        Self::build(items, ejes, lo, mid);
+        Self::build(items, ejes, mid + 1, hi);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Llama a f con cada punto a distancia menor a radio de p, junto con
+    /// su dato y la distancia al cuadrado.
+    pub fn for_each_within<F>(&self, p: &Point, radio: f64, mut f: F)
+    where
+        F: FnMut(&Point, &T, f64),
+    {
+        self.within(p, radio * radio, 0, self.items.len(), &mut f);
+    }
+
+    fn within<F>(
+        &self,
+        p: &Point,
+        radio_2: f64,
+        lo: usize,
+        hi: usize,
+        f: &mut F,
+    ) where
+        F: FnMut(&Point, &T, f64),
+    {
+        if lo >= hi {
+            return;
+        }
+
+        let mid = (lo + hi) / 2;
+        let (punto, dato) = &self.items[mid];
+
+        let distancia_2 = (punto - p).norm_squared();
+        if distancia_2 < radio_2 {
+            f(punto, dato, distancia_2);
+        }
+
+        if hi - lo == 1 {
+            return;
+        }
+
+        let eje = self.ejes[mid] as usize;
+        let delta = p[eje] - punto[eje];
+
+        // del lado de p siempre, del otro solo si la esfera cruza el plano
+        if delta <= 0.0 || delta * delta < radio_2 {
+            self.within(p, radio_2, lo, mid, f);
+        }
+        if delta >= 0.0 || delta * delta < radio_2 {
+            self.within(p, radio_2, mid + 1, hi, f);
+        }
+    }
+
+    /// Los k puntos más cercanos a p a distancia menor a radio_max, con su
+    /// distancia al cuadrado, del más lejano al más cercano.
+    pub fn nearest(
+        &self,
+        p: &Point,
+        k: usize,
+        radio_max: f64,
+    ) -> Vec<(&Point, &T, f64)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.nearest_rec(
+                p,
+                k,
+                radio_max * radio_max,
+                0,
+                self.len(),
+                &mut heap,
+            );
+        }
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .rev()
+            .map(|c| {
+                let (punto, dato) = &self.items[c.índice];
+                (punto, dato, c.distancia_2)
+            })
+            .collect()
+    }
+
+    fn nearest_rec(
+        &self,
+        p: &Point,
+        k: usize,
+        radio_2: f64,
+        lo: usize,
+        hi: usize,
+        heap: &mut BinaryHeap<Candidato>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+
+        let mid = (lo + hi) / 2;
+        let punto = &self.items[mid].0;
+
+        // cuando ya tengo k candidatos solo busco más cerca que el peor
+        let radio_actual = |heap: &BinaryHeap<Candidato>| {
+            if heap.len() == k {
+                heap.peek().map_or(radio_2, |c| c.distancia_2)
+            } else {
+                radio_2
+            }
+        };
+
+        let distancia_2 = (punto - p).norm_squared();
+        if distancia_2 < radio_actual(heap) {
+            heap.push(Candidato {
+                distancia_2,
+                índice: mid,
+            });
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+
+        if hi - lo == 1 {
+            return;
+        }
+
+        let eje = self.ejes[mid] as usize;
+        let delta = p[eje] - punto[eje];
+
+        // primero el lado de p, así el radio se achica antes
+        let (cerca, lejos) = if delta <= 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+
+        self.nearest_rec(p, k, radio_2, cerca.0, cerca.1, heap);
+        if delta * delta < radio_actual(heap) {
+            self.nearest_rec(p, k, radio_2, lejos.0, lejos.1, heap);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_point() -> Point {
+        Point::new(rand::random(), rand::random(), rand::random())
+    }
+
+    #[test]
+    fn búsqueda_por_radio_coincide_con_fuerza_bruta() {
+        let puntos: Vec<(Point, usize)> =
+            (0..2000).map(|i| (random_point(), i)).collect();
+        let árbol = KdTree::new(puntos.clone());
+
+        for _ in 0..50 {
+            let p = random_point();
+            let radio = 0.1;
+
+            let mut encontrados = Vec::new();
+            árbol.for_each_within(&p, radio, |_, i, _| encontrados.push(*i));
+            encontrados.sort();
+
+            let esperados: Vec<usize> = puntos
+                .iter()
+                .filter(|(q, _)| (q - p).norm() < radio)
+                .map(|(_, i)| *i)
+                .collect();
+
+            assert_eq!(encontrados, esperados);
+        }
+    }
+
+    #[test]
+    fn más_cercanos_coinciden_con_fuerza_bruta() {
+        let puntos: Vec<(Point, usize)> =
+            (0..2000).map(|i| (random_point(), i)).collect();
+        let árbol = KdTree::new(puntos.clone());
+
+        for _ in 0..50 {
+            let p = random_point();
+
+            let encontrados: Vec<usize> = árbol
+                .nearest(&p, 10, f64::INFINITY)
+                .iter()
+                .map(|(_, i, _)| **i)
+                .collect();
+
+            let mut esperados = puntos.clone();
+            esperados.sort_by(|(a, _), (b, _)| {
+                (a - p).norm().total_cmp(&(b - p).norm())
+            });
+            let esperados: Vec<usize> =
+                esperados[..10].iter().rev().map(|(_, i)| *i).collect();
+
+            assert_eq!(encontrados, esperados);
+        }
+
+        // con el radio máximo se encuentran menos
+        let p = Point::new(0.5, 0.5, 0.5);
+        for (q, _, d) in árbol.nearest(&p, 100, 0.05) {
+            assert!(d < 0.05 * 0.05);
+            assert!((q - p).norm() < 0.05);
+        }
+    }
+
+    #[test]
+    fn árbol_vacío() {
+        let árbol: KdTree<()> = KdTree::new(Vec::new());
+
+        assert!(árbol.is_empty());
+        assert!(árbol.nearest(&Point::origin(), 5, 1.0).is_empty());
+        árbol.for_each_within(&Point::origin(), 1.0, |_, _, _| panic!());
+    }
+}
@@ -0,0 +1,5 @@
+mod bvh;
+mod kdtree;
+
+pub use bvh::BVH;
+pub use kdtree::KdTree;
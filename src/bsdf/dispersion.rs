@@ -0,0 +1,79 @@
+/// Longitud de onda, en nanómetros, de la línea d del helio. Es a la que se
+/// suele dar el índice de refracción de un vidrio.
+pub const D_LINE: f64 = 587.56;
+
+/// Índice de refracción que cambia con la longitud de onda, para que los
+/// prismas y los diamantes separen los colores. Las fórmulas usan la
+/// longitud de onda en micrómetros, como las tablas de los fabricantes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dispersion {
+    /// n = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    /// n² = 1 + Σ b_i λ² / (λ² - c_i), con c_i en micrómetros al cuadrado.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Vidrio óptico borosilicato de Schott.
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+        c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+    };
+
+    /// Sílice fundida, de Malitson (1965).
+    pub const FUSED_SILICA: Dispersion = Dispersion::Sellmeier {
+        b: [0.696_166_3, 0.407_942_6, 0.897_479_4],
+        c: [0.004_679_148, 0.013_512_06, 97.934_002_5],
+    };
+
+    /// Diamante, de Peter (1923). Dispersa bastante más que los vidrios.
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.0],
+        c: [0.030_625, 0.011_236, 0.0],
+    };
+
+    /// Busca uno de los materiales conocidos por su nombre.
+    pub fn from_name(name: &str) -> Option<Dispersion> {
+        match name {
+            "BK7" => Some(Dispersion::BK7),
+            "FusedSilica" => Some(Dispersion::FUSED_SILICA),
+            "Diamond" => Some(Dispersion::DIAMOND),
+            _ => None,
+        }
+    }
+
+    /// Índice de refracción para la longitud de onda dada en nanómetros.
+    pub fn eta(&self, lambda: f64) -> f64 {
+        let l2 = (lambda / 1000.0).powi(2);
+
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => (1.0
+                + b.iter().zip(c).map(|(b, c)| b * l2 / (l2 - c)).sum::<f64>())
+            .sqrt(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn índices_de_catálogo() {
+        // valores publicados para la línea d
+        assert!((Dispersion::BK7.eta(D_LINE) - 1.5168).abs() < 1e-4);
+        assert!((Dispersion::FUSED_SILICA.eta(D_LINE) - 1.4585).abs() < 1e-4);
+        assert!((Dispersion::DIAMOND.eta(D_LINE) - 2.417).abs() < 2e-3);
+
+        // el azul se refracta más que el rojo
+        for dispersion in [
+            Dispersion::BK7,
+            Dispersion::FUSED_SILICA,
+            Dispersion::DIAMOND,
+            Dispersion::Cauchy { a: 1.5, b: 0.005 },
+        ] {
+            assert!(dispersion.eta(450.0) > dispersion.eta(650.0));
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use super::{same_hemisphere, BxDF};
+use crate::geometry::Vector;
+use crate::spectrum::SampledSpectrum;
+use std::f64::consts::FRAC_1_PI;
+
+/// Reflexión difusa ideal: refleja la fracción r de la luz, igual para todas
+/// las direcciones del mismo lado de la superficie.
+#[derive(Clone, Copy, Debug)]
+pub struct LambertianReflection {
+    r: SampledSpectrum,
+}
+
+impl LambertianReflection {
+    pub fn new(r: SampledSpectrum) -> Self {
+        Self { r }
+    }
+}
+
+impl BxDF for LambertianReflection {
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        if !same_hemisphere(wo, wi) {
+            return SampledSpectrum::new(0.0);
+        }
+
+        self.r * FRAC_1_PI as f32
+    }
+}
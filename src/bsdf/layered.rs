@@ -0,0 +1,575 @@
+use super::{
+    same_hemisphere, BsdfSample, BxDF, Fresnel, LambertianReflection, Lobe,
+    MicrofacetDistribution, MicrofacetReflection, MicrofacetTransmission,
+    SpecularReflection, SpecularTransmission,
+};
+use crate::geometry::Vector;
+use crate::material::fresnel_dielectric;
+use crate::sampling::{hash_rng, power_heuristic};
+use crate::spectrum::SampledSpectrum;
+use enum_dispatch::enum_dispatch;
+use rand::Rng;
+use std::f64::consts::FRAC_1_PI;
+
+/// Cantidad máxima de veces que la luz cruza la capa antes de descartarla.
+const MAX_DEPTH: usize = 32;
+
+/// Qué parte de la luz puede elegir Coating::sample().
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Parte {
+    Todo,
+    Reflexión,
+    Transmisión,
+}
+
+/// Barniz transparente: la superficie, pulida o rugosa, entre el aire y un
+/// dieléctrico de índice eta. Refleja o refracta según Fresnel.
+#[derive(Clone, Copy, Debug)]
+pub struct Coating {
+    distribution: Option<MicrofacetDistribution>,
+    eta: f64,
+}
+
+impl Coating {
+    /// Sin distribución de microfacetas el barniz es pulido.
+    pub fn new(distribution: Option<MicrofacetDistribution>, eta: f64) -> Self {
+        Self { distribution, eta }
+    }
+
+    fn reflection(&self) -> Lobe {
+        let fresnel = Fresnel::Dielectric {
+            eta_i: 1.0,
+            eta_t: self.eta,
+        };
+        let r = SampledSpectrum::new(1.0);
+
+        match self.distribution {
+            Some(d) => MicrofacetReflection::new(r, d, fresnel).into(),
+            None => SpecularReflection::new(r, fresnel).into(),
+        }
+    }
+
+    fn transmission(&self) -> Lobe {
+        let t = SampledSpectrum::new(1.0);
+
+        match self.distribution {
+            Some(d) => MicrofacetTransmission::new(t, d, 1.0, self.eta).into(),
+            None => SpecularTransmission::new(t, 1.0, self.eta).into(),
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distribution.is_none()
+    }
+
+    /// Reflectancia de Fresnel de la superficie sin rugosidad.
+    fn prob_reflexión(&self, wo: &Vector) -> f64 {
+        fresnel_dielectric(wo.z, 1.0, self.eta)
+    }
+
+    /// Reflectancia de Fresnel de la microfaceta que lleva wi a wo, o None
+    /// si no hay ninguna.
+    fn fresnel_microfaceta(&self, wo: &Vector, wi: &Vector) -> Option<f64> {
+        let wh = if same_hemisphere(wo, wi) {
+            wo + wi
+        } else {
+            let eta = if wo.z > 0.0 { self.eta } else { 1.0 / self.eta };
+            wo + wi * eta
+        };
+        let wh = wh.try_normalize(0.0)?;
+        let wh = if wh.z < 0.0 { -wh } else { wh };
+
+        Some(fresnel_dielectric(wo.dot(&wh), 1.0, self.eta))
+    }
+
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        self.reflection().f(wo, wi) + self.transmission().f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector, wi: &Vector, parte: Parte) -> f64 {
+        match parte {
+            Parte::Todo => match self.fresnel_microfaceta(wo, wi) {
+                Some(r) if same_hemisphere(wo, wi) => {
+                    r * self.reflection().pdf(wo, wi)
+                }
+                Some(r) => (1.0 - r) * self.transmission().pdf(wo, wi),
+                None => 0.0,
+            },
+            Parte::Reflexión => self.reflection().pdf(wo, wi),
+            Parte::Transmisión => self.transmission().pdf(wo, wi),
+        }
+    }
+
+    /// Elige entre reflejar y refractar con u_c, o solo lo que pide parte,
+    /// y la dirección con u.
+    fn sample(
+        &self,
+        wo: &Vector,
+        u_c: f64,
+        u: (f64, f64),
+        parte: Parte,
+    ) -> Option<BsdfSample> {
+        // si es rugoso elige según la microfaceta, que es la misma que
+        // después eligen los lóbulos con u. Con la de la superficie casi
+        // nunca refractaría la luz que llega rasante desde adentro
+        let r = match self.distribution {
+            Some(d) => {
+                let wh = d.sample_wh(wo, u);
+                fresnel_dielectric(wo.dot(&wh) * wh.z.signum(), 1.0, self.eta)
+            }
+            None => self.prob_reflexión(wo),
+        };
+        let (refleja, prob) = match parte {
+            Parte::Reflexión => (true, 1.0),
+            Parte::Transmisión => (false, 1.0),
+            Parte::Todo if u_c < r => (true, r),
+            Parte::Todo => (false, 1.0 - r),
+        };
+
+        let mut muestra = if refleja {
+            self.reflection().sample_f(wo, u)?
+        } else {
+            self.transmission().sample_f(wo, u)?
+        };
+
+        // la rugosa puede elegir wi con cualquiera de las dos partes
+        if parte == Parte::Todo && !muestra.specular {
+            muestra.pdf = self.pdf(wo, &muestra.wi, parte);
+        } else {
+            muestra.pdf *= prob;
+        }
+
+        (muestra.pdf > 0.0 && !muestra.f.is_black()).then_some(muestra)
+    }
+}
+
+/// Lo que hay debajo del barniz. La luz llega desde el barniz, así que el
+/// índice de refracción de afuera es el suyo.
+#[allow(clippy::enum_variant_names)]
+#[enum_dispatch(BxDF)]
+#[derive(Clone, Copy, Debug)]
+pub enum Substrate {
+    LambertianReflection,
+    SpecularReflection,
+    MicrofacetReflection,
+}
+
+/// Material en capas, de Guo et al. (2018) como en pbrt-v4: un barniz
+/// dieléctrico sobre una base opaca, con un medio que absorbe entre los dos.
+/// La luz rebota adentro de la capa hasta que sale, así que en lugar de
+/// calcular f() y pdf() se los estima siguiendo caminos al azar.
+///
+/// Es de dos caras: visto desde abajo de la normal es igual que desde
+/// arriba.
+#[derive(Clone, Copy, Debug)]
+pub struct Layered {
+    top: Coating,
+    bottom: Substrate,
+    thickness: f64,
+    absorption: SampledSpectrum,
+}
+
+impl Layered {
+    /// absorption es el coeficiente de absorción del medio por unidad de
+    /// espesor.
+    pub fn new<S: Into<Substrate>>(
+        top: Coating,
+        bottom: S,
+        thickness: f64,
+        absorption: SampledSpectrum,
+    ) -> Self {
+        Self {
+            top,
+            bottom: bottom.into(),
+            thickness,
+            absorption,
+        }
+    }
+
+    /// Fracción de la luz que cruza el medio en la dirección w.
+    fn tr(&self, w: &Vector) -> SampledSpectrum {
+        if self.thickness == 0.0 || self.absorption.is_black() {
+            return SampledSpectrum::new(1.0);
+        }
+
+        (self.absorption * (-self.thickness / w.z.abs()) as f32).exp()
+    }
+
+    /// Da vuelta las direcciones si wo está debajo de la superficie.
+    fn de_frente(wo: &Vector, wi: &Vector) -> (Vector, Vector) {
+        if wo.z < 0.0 {
+            (-wo, -wi)
+        } else {
+            (*wo, *wi)
+        }
+    }
+}
+
+impl BxDF for Layered {
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        // los caminos salen de un generador con semilla en las direcciones,
+        // así f() da siempre lo mismo para el mismo par
+        let mut rng = hash_rng(&[wo.x, wo.y, wo.z, wi.x, wi.y, wi.z]);
+        let (wo, wi) = Layered::de_frente(wo, wi);
+        if !same_hemisphere(&wo, &wi) {
+            return SampledSpectrum::new(0.0);
+        }
+
+        // lo que refleja el barniz, más un camino al azar por adentro de la
+        // capa que conecta en cada rebote con la entrada de wi
+        let mut f = self.top.f(&wo, &wi);
+
+        let entra = self.top.sample(&wo, 0.0, rng.gen(), Parte::Transmisión);
+        let sale = self.top.sample(&wi, 0.0, rng.gen(), Parte::Transmisión);
+        let (entra, sale) = match (entra, sale) {
+            (Some(entra), Some(sale)) => (entra, sale),
+            _ => return f,
+        };
+
+        // los lóbulos no escalan la radiancia por el cociente de los índices
+        // al cuadrado, pero acá hace falta: lo que sale hacia wo se divide
+        // por eta², y wi se muestreó desde afuera, así que su delta ya está
+        // en el ángulo sólido de afuera
+        let eta2 = self.top.eta * self.top.eta;
+        let mut beta = entra.f * (entra.wi.z.abs() / (entra.pdf * eta2)) as f32;
+        let mut w = entra.wi;
+        let mut en_la_base = false;
+
+        for depth in 0..MAX_DEPTH {
+            // ruleta rusa cuando ya queda poca luz
+            let máximo = beta.max_value() as f64;
+            if depth > 3 && máximo < 0.25 {
+                let q = (1.0 - máximo).max(0.0);
+                if rng.gen::<f64>() < q {
+                    break;
+                }
+                beta = beta / (1.0 - q) as f32;
+            }
+
+            // cruza el medio hasta la otra superficie
+            en_la_base = !en_la_base;
+            beta = beta * self.tr(&w);
+
+            if !en_la_base {
+                // se refleja hacia adentro en el barniz
+                let muestra = match self.top.sample(
+                    &-w,
+                    0.0,
+                    rng.gen(),
+                    Parte::Reflexión,
+                ) {
+                    Some(muestra) => muestra,
+                    None => break,
+                };
+                beta = beta
+                    * muestra.f
+                    * (muestra.wi.z.abs() / muestra.pdf) as f32;
+                w = muestra.wi;
+                continue;
+            }
+
+            // en la base conecta con la dirección por la que sale wi
+            if !self.bottom.is_specular() {
+                let peso = if self.top.is_specular() {
+                    1.0
+                } else {
+                    power_heuristic(
+                        1,
+                        sale.pdf,
+                        1,
+                        self.bottom.pdf(&-w, &-sale.wi),
+                    )
+                };
+
+                f += beta
+                    * self.bottom.f(&-w, &-sale.wi)
+                    * self.tr(&sale.wi)
+                    * sale.f
+                    * (sale.wi.z.abs() * peso / sale.pdf) as f32;
+            }
+
+            let muestra = match self.bottom.sample_f(&-w, rng.gen()) {
+                Some(muestra) if muestra.pdf > 0.0 && muestra.wi.z != 0.0 => {
+                    muestra
+                }
+                _ => break,
+            };
+            beta = beta * muestra.f * (muestra.wi.z.abs() / muestra.pdf) as f32;
+            w = muestra.wi;
+
+            // y si el barniz es rugoso, con la dirección que eligió la base
+            if !self.top.is_specular() {
+                // la luz que entra desde wi se multiplica por eta²
+                let f_sale = self.top.f(&-w, &wi) * eta2 as f32;
+                if !f_sale.is_black() {
+                    let peso = if self.bottom.is_specular() {
+                        1.0
+                    } else {
+                        power_heuristic(
+                            1,
+                            muestra.pdf,
+                            1,
+                            self.top.pdf(&-w, &wi, Parte::Transmisión),
+                        )
+                    };
+
+                    f += beta * self.tr(&w) * f_sale * peso as f32;
+                }
+            }
+        }
+
+        f
+    }
+
+    fn sample_f(&self, wo: &Vector, u: (f64, f64)) -> Option<BsdfSample> {
+        let mut rng = hash_rng(&[wo.x, wo.y, wo.z, u.0, u.1]);
+        let signo = if wo.z < 0.0 { -1.0 } else { 1.0 };
+        let wo = wo * signo;
+
+        // primero el barniz, y si la luz entra rebota hasta que sale
+        let mut muestra = self.top.sample(&wo, u.0, rng.gen(), Parte::Todo)?;
+        if same_hemisphere(&wo, &muestra.wi) {
+            muestra.wi *= signo;
+            return Some(muestra);
+        }
+
+        let mut f = muestra.f * muestra.wi.z.abs() as f32;
+        let mut pdf = muestra.pdf;
+        let mut specular = muestra.specular;
+        let mut w = muestra.wi;
+        let mut en_la_base = false;
+
+        for depth in 0..MAX_DEPTH {
+            let máximo = f.max_value() as f64 / pdf;
+            if depth > 3 && máximo < 0.25 {
+                let q = (1.0 - máximo).max(0.0);
+                if rng.gen::<f64>() < q {
+                    return None;
+                }
+                pdf *= 1.0 - q;
+            }
+
+            en_la_base = !en_la_base;
+            f = f * self.tr(&w);
+
+            let muestra = if en_la_base {
+                self.bottom.sample_f(&-w, rng.gen())
+            } else {
+                self.top.sample(&-w, rng.gen(), rng.gen(), Parte::Todo)
+            }?;
+            if muestra.pdf <= 0.0 || muestra.wi.z == 0.0 {
+                return None;
+            }
+
+            f = f * muestra.f;
+            pdf *= muestra.pdf;
+            specular &= muestra.specular;
+            w = muestra.wi;
+
+            // solo sale cruzando el barniz, porque la base es opaca
+            if !en_la_base && w.z > 0.0 {
+                if specular {
+                    return Some(BsdfSample {
+                        wi: w * signo,
+                        f,
+                        pdf,
+                        specular,
+                    });
+                }
+
+                // el camino da el cociente f / pdf, pero la densidad de wi
+                // es la aproximación de pdf(), para que coincida al pesar
+                // con MIS
+                let pdf_wi = self.pdf(&wo, &w);
+                return Some(BsdfSample {
+                    wi: w * signo,
+                    f: f * (pdf_wi / pdf) as f32,
+                    pdf: pdf_wi,
+                    specular,
+                });
+            }
+
+            f = f * w.z.abs() as f32;
+        }
+
+        None
+    }
+
+    /// Una aproximación de la densidad de sample_f(): el reflejo del barniz,
+    /// más la luz que entra a la capa, que sale más o menos como la de un
+    /// difuso.
+    fn pdf(&self, wo: &Vector, wi: &Vector) -> f64 {
+        let (wo, wi) = Layered::de_frente(wo, wi);
+        if !same_hemisphere(&wo, &wi) {
+            return 0.0;
+        }
+
+        let r = self.top.prob_reflexión(&wo);
+        let reflejo = self.top.pdf(&wo, &wi, Parte::Todo);
+
+        reflejo + (1.0 - r) * wi.z.abs() * FRAC_1_PI
+    }
+
+    fn is_specular(&self) -> bool {
+        self.top.is_specular() && self.bottom.is_specular()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reflectancia hacia wo estimada como el promedio de f.cos/pdf.
+    fn reflectancia(lobe: &Layered, wo: &Vector) -> f64 {
+        let n = 10_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            if let Some(s) = lobe.sample_f(wo, rand::random()) {
+                total += s.f.max_value() as f64 * s.wi.z.abs() / s.pdf;
+            }
+        }
+
+        total / n as f64
+    }
+
+    /// Reflectancia de un barniz sobre una base blanca sin medio, siguiendo
+    /// la luz solo con Coating::sample(), sin las estimaciones ni la ruleta
+    /// de Layered.
+    fn referencia(top: &Coating, wo: &Vector, difusa: bool) -> f64 {
+        let n = 10_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            // de dos caras, como Layered
+            let mut w = if wo.z < 0.0 { -wo } else { *wo };
+            let mut beta = 1.0;
+            for _ in 0..MAX_DEPTH {
+                let muestra = match top.sample(
+                    &w,
+                    rand::random(),
+                    rand::random(),
+                    Parte::Todo,
+                ) {
+                    Some(muestra) => muestra,
+                    None => break,
+                };
+                beta *= muestra.f.max_value() as f64 * muestra.wi.z.abs()
+                    / muestra.pdf;
+                if muestra.wi.z > 0.0 {
+                    total += beta;
+                    break;
+                }
+
+                // la base blanca no pierde nada, solo cambia la dirección
+                let sube = if difusa {
+                    crate::geometry::sample_versor_cos_density(
+                        &Vector::z(),
+                        rand::random(),
+                    )
+                } else {
+                    Vector::new(muestra.wi.x, muestra.wi.y, -muestra.wi.z)
+                };
+                w = -sube;
+            }
+        }
+
+        total / n as f64
+    }
+
+    fn capas(
+        distribution: Option<MicrofacetDistribution>,
+        absorption: f32,
+    ) -> [Layered; 2] {
+        let blanco = SampledSpectrum::new(1.0);
+        let barniz = Coating::new(distribution, 1.5);
+        let absorption = SampledSpectrum::new(absorption);
+
+        [
+            Layered::new(
+                barniz,
+                LambertianReflection::new(blanco),
+                0.1,
+                absorption,
+            ),
+            Layered::new(
+                barniz,
+                SpecularReflection::new(blanco, Fresnel::NoOp),
+                0.1,
+                absorption,
+            ),
+        ]
+    }
+
+    #[test]
+    fn horno_blanco() {
+        // con una base blanca y un barniz pulido que no absorbe, sale toda
+        // la luz que entra
+        let rugoso = MicrofacetDistribution::GGX {
+            alpha_x: 0.3,
+            alpha_y: 0.3,
+        };
+        for wo in [
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(1.0, 0.0, 1.0).normalize(),
+            Vector::new(0.9, 0.3, -0.2).normalize(),
+        ] {
+            for capa in capas(None, 0.0) {
+                assert!((reflectancia(&capa, &wo) - 1.0).abs() < 0.02);
+            }
+
+            // el rugoso pierde la luz que rebota entre microfacetas cada vez
+            // que llega al barniz, sobre todo la rasante desde adentro, así
+            // que se compara con seguir la luz rebote por rebote
+            let barniz = Coating::new(Some(rugoso), 1.5);
+            let [difusa, espejo] = capas(Some(rugoso), 0.0);
+            let r = reflectancia(&difusa, &wo);
+            assert!((r - referencia(&barniz, &wo, true)).abs() < 0.03);
+            let r = reflectancia(&espejo, &wo);
+            assert!((r - referencia(&barniz, &wo, false)).abs() < 0.03);
+
+            // y el medio que absorbe oscurece
+            for capa in capas(None, 5.0) {
+                assert!(reflectancia(&capa, &wo) < 0.7);
+            }
+        }
+    }
+
+    #[test]
+    fn f_coincide_con_las_muestras() {
+        // la reflectancia integrando f() con direcciones del coseno es la
+        // misma que la de sample_f()
+        let [difuso, _] = capas(None, 1.0);
+        let wo = Vector::new(0.5, 0.0, 1.0).normalize();
+
+        let n = 10_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let wi = crate::geometry::sample_versor_cos_density(
+                &Vector::z(),
+                rand::random(),
+            );
+            total += difuso.f(&wo, &wi).max_value() as f64 / FRAC_1_PI;
+        }
+
+        // el reflejo pulido del barniz no lo ve f()
+        let r = fresnel_dielectric(wo.z, 1.0, 1.5);
+        let esperado = reflectancia(&difuso, &wo) - r;
+        assert!((total / n as f64 - esperado).abs() < 0.03);
+    }
+
+    #[test]
+    fn se_repite_con_los_mismos_argumentos() {
+        // los caminos dentro de la capa no dependen de rand::random(), así
+        // que con las mismas direcciones y el mismo u da lo mismo
+        let [difuso, _] = capas(None, 1.0);
+        let wo = Vector::new(0.5, 0.0, 1.0).normalize();
+        let wi = Vector::new(-0.3, 0.2, 1.0).normalize();
+
+        assert_eq!(difuso.f(&wo, &wi), difuso.f(&wo, &wi));
+        for _ in 0..100 {
+            let u = rand::random();
+            let a = difuso.sample_f(&wo, u).map(|s| (s.wi, s.pdf));
+            let b = difuso.sample_f(&wo, u).map(|s| (s.wi, s.pdf));
+            assert_eq!(a, b);
+        }
+    }
+}
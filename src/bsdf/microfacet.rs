@@ -0,0 +1,650 @@
+use super::{same_hemisphere, BsdfSample, BxDF, Fresnel};
+use crate::geometry::{refract, Vector};
+use crate::material::fresnel_dielectric;
+use crate::spectrum::SampledSpectrum;
+use std::f64::consts::PI;
+
+/// Distribución de las normales de las microfacetas de una superficie
+/// rugosa. alpha_x y alpha_y son el ancho de la distribución en cada eje
+/// del sistema local; si son distintos la superficie es anisotrópica.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MicrofacetDistribution {
+    /// Pendientes gaussianas.
+    Beckmann { alpha_x: f64, alpha_y: f64 },
+    /// Trowbridge-Reitz, con colas más largas que Beckmann: los brillos se
+    /// desvanecen más suave.
+    GGX { alpha_x: f64, alpha_y: f64 },
+}
+
+impl MicrofacetDistribution {
+    /// Pasa una rugosidad en [0, 1], que se ve más o menos lineal, al ancho
+    /// de la distribución, como en el modelo de Disney.
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        (roughness * roughness).max(1e-4)
+    }
+
+    fn alphas(&self) -> (f64, f64) {
+        match *self {
+            Self::Beckmann { alpha_x, alpha_y } => (alpha_x, alpha_y),
+            Self::GGX { alpha_x, alpha_y } => (alpha_x, alpha_y),
+        }
+    }
+
+    /// Densidad de microfacetas con normal wh, por unidad de área
+    /// proyectada de la superficie.
+    pub fn d(&self, wh: &Vector) -> f64 {
+        let tan2 = tan2_theta(wh);
+        if tan2.is_infinite() {
+            return 0.0;
+        }
+        let (ax, ay) = self.alphas();
+        let cos4 = cos2_theta(wh) * cos2_theta(wh);
+        let e = (cos2_phi(wh) / (ax * ax) + sin2_phi(wh) / (ay * ay)) * tan2;
+
+        match self {
+            Self::Beckmann { .. } => (-e).exp() / (PI * ax * ay * cos4),
+            Self::GGX { .. } => {
+                1.0 / (PI * ax * ay * cos4 * (1.0 + e) * (1.0 + e))
+            }
+        }
+    }
+
+    /// Área de microfacetas tapadas por cada unidad visible desde w, según
+    /// el modelo de Smith.
+    fn lambda(&self, w: &Vector) -> f64 {
+        let tan = tan2_theta(w).sqrt();
+        if tan.is_infinite() {
+            return 0.0;
+        }
+        let (ax, ay) = self.alphas();
+        let alpha = (cos2_phi(w) * ax * ax + sin2_phi(w) * ay * ay).sqrt();
+
+        match self {
+            Self::Beckmann { .. } => {
+                // aproximación racional de la fórmula con erf
+                let a = 1.0 / (alpha * tan);
+                if a >= 1.6 {
+                    return 0.0;
+                }
+                (1.0 - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+            }
+            Self::GGX { .. } => {
+                let a2_tan2 = (alpha * tan) * (alpha * tan);
+                ((1.0 + a2_tan2).sqrt() - 1.0) / 2.0
+            }
+        }
+    }
+
+    /// Fracción de las microfacetas visibles desde w.
+    pub fn g1(&self, w: &Vector) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fracción de las microfacetas visibles desde wo y desde wi a la vez.
+    pub fn g(&self, wo: &Vector, wi: &Vector) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Elige la normal de una microfaceta entre las visibles desde wo, con
+    /// densidad pdf().
+    pub fn sample_wh(&self, wo: &Vector, u: (f64, f64)) -> Vector {
+        // las fórmulas son para wo del lado de la normal
+        let (wo, flip) = if wo.z < 0.0 {
+            (-wo, true)
+        } else {
+            (*wo, false)
+        };
+
+        let wh = match self {
+            Self::Beckmann { alpha_x, alpha_y } => {
+                beckmann_sample(&wo, *alpha_x, *alpha_y, u)
+            }
+            Self::GGX { alpha_x, alpha_y } => {
+                ggx_sample(&wo, *alpha_x, *alpha_y, u)
+            }
+        };
+
+        if flip {
+            -wh
+        } else {
+            wh
+        }
+    }
+
+    /// Densidad con la que sample_wh() elige wh.
+    pub fn pdf(&self, wo: &Vector, wh: &Vector) -> f64 {
+        if wo.z == 0.0 {
+            return 0.0;
+        }
+
+        self.d(wh) * self.g1(wo) * wo.dot(wh).abs() / wo.z.abs()
+    }
+}
+
+fn cos2_theta(w: &Vector) -> f64 {
+    w.z * w.z
+}
+
+fn sin2_theta(w: &Vector) -> f64 {
+    (1.0 - cos2_theta(w)).max(0.0)
+}
+
+fn tan2_theta(w: &Vector) -> f64 {
+    sin2_theta(w) / cos2_theta(w)
+}
+
+fn cos2_phi(w: &Vector) -> f64 {
+    let sen2 = sin2_theta(w);
+    if sen2 == 0.0 {
+        1.0
+    } else {
+        (w.x * w.x / sen2).min(1.0)
+    }
+}
+
+fn sin2_phi(w: &Vector) -> f64 {
+    let sen2 = sin2_theta(w);
+    if sen2 == 0.0 {
+        0.0
+    } else {
+        (w.y * w.y / sen2).min(1.0)
+    }
+}
+
+/// Muestreo de normales visibles de GGX, de Heitz (2018): se estira la
+/// superficie para que sea isotrópica con alpha 1, donde las normales
+/// visibles se distribuyen como los puntos de un disco visto desde wo.
+fn ggx_sample(wo: &Vector, ax: f64, ay: f64, u: (f64, f64)) -> Vector {
+    let vh = Vector::new(ax * wo.x, ay * wo.y, wo.z).normalize();
+
+    // base ortonormal alrededor de vh
+    let largo2 = vh.x * vh.x + vh.y * vh.y;
+    let t_1 = if largo2 > 0.0 {
+        Vector::new(-vh.y, vh.x, 0.0) / largo2.sqrt()
+    } else {
+        Vector::x()
+    };
+    let t_2 = vh.cross(&t_1);
+
+    // punto en el disco, comprimido en la mitad que no se ve
+    let r = u.0.sqrt();
+    let phi = 2.0 * PI * u.1;
+    let p_1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p_2 = (1.0 - s) * (1.0 - p_1 * p_1).sqrt() + s * r * phi.sin();
+    let p_3 = (1.0 - p_1 * p_1 - p_2 * p_2).max(0.0).sqrt();
+
+    let nh = t_1 * p_1 + t_2 * p_2 + vh * p_3;
+
+    Vector::new(ax * nh.x, ay * nh.y, nh.z.max(1e-6)).normalize()
+}
+
+/// Muestreo de normales visibles de Beckmann, de Jakob (2014): se estira la
+/// superficie, se eligen las pendientes para wo y se deshace el estiramiento.
+fn beckmann_sample(wo: &Vector, ax: f64, ay: f64, u: (f64, f64)) -> Vector {
+    let estirado = Vector::new(ax * wo.x, ay * wo.y, wo.z).normalize();
+
+    let (x, y) = beckmann_sample_slopes(estirado.z, u);
+
+    // roto según el ángulo de wo
+    let sen_theta = sin2_theta(&estirado).sqrt();
+    let (cos_phi, sen_phi) = if sen_theta == 0.0 {
+        (1.0, 0.0)
+    } else {
+        (
+            (estirado.x / sen_theta).clamp(-1.0, 1.0),
+            (estirado.y / sen_theta).clamp(-1.0, 1.0),
+        )
+    };
+    let (x, y) = (cos_phi * x - sen_phi * y, sen_phi * x + cos_phi * y);
+
+    Vector::new(-x * ax, -y * ay, 1.0).normalize()
+}
+
+/// Pendientes de una microfaceta visible desde una dirección con coseno
+/// cos_theta, para Beckmann isotrópico con alpha 1.
+fn beckmann_sample_slopes(cos_theta: f64, u: (f64, f64)) -> (f64, f64) {
+    // a incidencia normal todas las microfacetas se ven igual
+    if cos_theta > 0.9999 {
+        let r = (-(1.0 - u.0).ln()).sqrt();
+        let phi = 2.0 * PI * u.1;
+        return (r * phi.cos(), r * phi.sin());
+    }
+
+    let sen_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let tan_theta = sen_theta / cos_theta;
+    let cot_theta = 1.0 / tan_theta;
+    let raiz_pi_inv = 1.0 / PI.sqrt();
+
+    // invierto la distribución acumulada de la pendiente en x con Newton,
+    // sin salir del intervalo [a, c]
+    let mut a = -1.0;
+    let mut c = erf(cot_theta);
+    let muestra = u.0.max(1e-6);
+
+    // primera aproximación ajustada a mano en el paper
+    let theta = cos_theta.acos();
+    let ajuste = 1.0 + theta * (-0.876 + theta * (0.4265 - 0.0594 * theta));
+    let mut b = c - (1.0 + c) * (1.0 - muestra).powf(ajuste);
+
+    let normalizacion = 1.0
+        / (1.0 + c + raiz_pi_inv * tan_theta * (-cot_theta * cot_theta).exp());
+
+    for _ in 0..9 {
+        if !(a..=c).contains(&b) {
+            b = 0.5 * (a + c);
+        }
+
+        let inv_erf = erf_inv(b);
+        let valor = normalizacion
+            * (1.0 + b + raiz_pi_inv * tan_theta * (-inv_erf * inv_erf).exp())
+            - muestra;
+        if valor.abs() < 1e-5 {
+            break;
+        }
+
+        if valor > 0.0 {
+            c = b;
+        } else {
+            a = b;
+        }
+
+        let derivada = normalizacion * (1.0 - inv_erf * tan_theta);
+        b -= valor / derivada;
+    }
+
+    (erf_inv(b), erf_inv(2.0 * u.1.max(1e-6) - 1.0))
+}
+
+/// Función error, con la aproximación de Abramowitz y Stegun (7.1.26).
+fn erf(x: f64) -> f64 {
+    let signo = x.signum();
+    let x = x.abs();
+
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let polinomio = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741
+                    + t * (-1.453_152_027 + t * 1.061_405_429))));
+
+    signo * (1.0 - polinomio * (-x * x).exp())
+}
+
+/// Inversa de la función error, con la aproximación de Giles (2010).
+fn erf_inv(x: f64) -> f64 {
+    let x = x.clamp(-0.99999, 0.99999);
+    let w = -((1.0 - x) * (1.0 + x)).ln();
+
+    let p = if w < 5.0 {
+        let w = w - 2.5;
+        [
+            3.432_739_39e-7, -3.523_387_7e-6, -4.391_506_54e-6,
+            0.000_218_580_87, -0.001_253_725_03, -0.004_177_681_64,
+            0.246_640_727, 1.501_409_41,
+        ]
+        .iter()
+        .fold(2.810_226_36e-8, |p, coef| coef + p * w)
+    } else {
+        let w = w.sqrt() - 3.0;
+        [
+            0.000_100_950_558, 0.001_349_343_22, -0.003_673_428_44,
+            0.005_739_507_73, -0.007_622_461_3, 0.009_438_870_47, 1.001_674_06,
+            2.832_976_82,
+        ]
+        .iter()
+        .fold(-0.000_200_214_257, |p, coef| coef + p * w)
+    };
+
+    p * x
+}
+
+/// Reflexión en una superficie rugosa, de Torrance y Sparrow: cada
+/// microfaceta es un espejo con la reflectancia de Fresnel.
+#[derive(Clone, Copy, Debug)]
+pub struct MicrofacetReflection {
+    r: SampledSpectrum,
+    distribution: MicrofacetDistribution,
+    fresnel: Fresnel,
+}
+
+impl MicrofacetReflection {
+    pub fn new(
+        r: SampledSpectrum,
+        distribution: MicrofacetDistribution,
+        fresnel: Fresnel,
+    ) -> Self {
+        Self {
+            r,
+            distribution,
+            fresnel,
+        }
+    }
+}
+
+impl BxDF for MicrofacetReflection {
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        let (cos_o, cos_i) = (wo.z.abs(), wi.z.abs());
+        let wh = wo + wi;
+        if !same_hemisphere(wo, wi)
+            || cos_o == 0.0
+            || cos_i == 0.0
+            || wh == Vector::zeros()
+        {
+            return SampledSpectrum::new(0.0);
+        }
+        let wh = wh.normalize();
+
+        // el coseno con la microfaceta, del lado de la normal
+        let fresnel = self.fresnel.evaluate(wi.dot(&wh) * wh.z.signum());
+        let d_g = self.distribution.d(&wh) * self.distribution.g(wo, wi);
+
+        self.r * fresnel * (d_g / (4.0 * cos_i * cos_o)) as f32
+    }
+
+    fn sample_f(&self, wo: &Vector, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+
+        let wh = self.distribution.sample_wh(wo, u);
+        let cos_h = wo.dot(&wh);
+        if cos_h <= 0.0 {
+            return None;
+        }
+
+        let wi = -wo + wh * (2.0 * cos_h);
+        if !same_hemisphere(wo, &wi) {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.f(wo, &wi),
+            pdf: self.distribution.pdf(wo, &wh) / (4.0 * cos_h),
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: &Vector, wi: &Vector) -> f64 {
+        let wh = wo + wi;
+        if !same_hemisphere(wo, wi) || wh == Vector::zeros() {
+            return 0.0;
+        }
+        let wh = wh.normalize();
+
+        self.distribution.pdf(wo, &wh) / (4.0 * wo.dot(&wh).abs())
+    }
+}
+
+/// Refracción a través de una superficie rugosa entre un medio de índice
+/// eta_a, del lado de la normal, y uno de índice eta_b. Como en
+/// SpecularTransmission, la radiancia no se escala por el cociente de los
+/// índices al cuadrado.
+#[derive(Clone, Copy, Debug)]
+pub struct MicrofacetTransmission {
+    t: SampledSpectrum,
+    distribution: MicrofacetDistribution,
+    eta_a: f64,
+    eta_b: f64,
+}
+
+impl MicrofacetTransmission {
+    pub fn new(
+        t: SampledSpectrum,
+        distribution: MicrofacetDistribution,
+        eta_a: f64,
+        eta_b: f64,
+    ) -> Self {
+        Self {
+            t,
+            distribution,
+            eta_a,
+            eta_b,
+        }
+    }
+
+    /// Cociente eta_t / eta_i de los índices para la luz que sale hacia wo.
+    fn eta(&self, wo: &Vector) -> f64 {
+        if wo.z > 0.0 {
+            self.eta_b / self.eta_a
+        } else {
+            self.eta_a / self.eta_b
+        }
+    }
+
+    /// Normal de la microfaceta que refracta wi en wo, del lado de la
+    /// normal de la superficie, y la derivada de wh respecto a wi.
+    fn half_vector(&self, wo: &Vector, wi: &Vector) -> Option<(Vector, f64)> {
+        if same_hemisphere(wo, wi) || wo.z == 0.0 || wi.z == 0.0 {
+            return None;
+        }
+
+        let eta = self.eta(wo);
+        let wh = (wo + wi * eta).try_normalize(0.0)?;
+        let wh = if wh.z < 0.0 { -wh } else { wh };
+
+        // wo y wi tienen que estar de lados distintos de la microfaceta
+        let (cos_o, cos_i) = (wo.dot(&wh), wi.dot(&wh));
+        if cos_o * cos_i > 0.0 {
+            return None;
+        }
+
+        let denominador = cos_o + eta * cos_i;
+        let dwh_dwi = (eta * eta * cos_i / (denominador * denominador)).abs();
+
+        Some((wh, dwh_dwi))
+    }
+}
+
+impl BxDF for MicrofacetTransmission {
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        let (wh, dwh_dwi) = match self.half_vector(wo, wi) {
+            Some(h) => h,
+            None => return SampledSpectrum::new(0.0),
+        };
+
+        let fresnel = fresnel_dielectric(wo.dot(&wh), self.eta_a, self.eta_b);
+        let d_g = self.distribution.d(&wh) * self.distribution.g(wo, wi);
+        let valor = (1.0 - fresnel) * d_g * dwh_dwi * wo.dot(&wh).abs()
+            / (wo.z * wi.z).abs();
+
+        self.t * valor as f32
+    }
+
+    fn sample_f(&self, wo: &Vector, u: (f64, f64)) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+
+        let wh = self.distribution.sample_wh(wo, u);
+        if wo.dot(&wh) <= 0.0 {
+            return None;
+        }
+
+        // None si hay reflexión total interna en la microfaceta
+        let wi = refract(wo, &wh, self.eta(wo))?;
+
+        Some(BsdfSample {
+            wi,
+            f: self.f(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: &Vector, wi: &Vector) -> f64 {
+        match self.half_vector(wo, wi) {
+            Some((wh, dwh_dwi)) => self.distribution.pdf(wo, &wh) * dwh_dwi,
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::uniform_sample_sphere;
+
+    /// Direcciones del lado de la normal repartidas en una grilla de
+    /// lado x lado celdas de igual ángulo sólido, 2π / lado². Sirven para
+    /// integrar sin el ruido que tienen las direcciones al azar cuando la
+    /// densidad es muy concentrada.
+    fn hemisphere_grid(lado: usize) -> impl Iterator<Item = Vector> {
+        let centro = move |i: usize| (i as f64 + 0.5) / lado as f64;
+
+        (0..lado).flat_map(move |i| {
+            // con u_1 < 0.5 queda z > 0
+            (0..lado).map(move |j| {
+                uniform_sample_sphere((centro(i) / 2.0, centro(j)))
+            })
+        })
+    }
+
+    fn distribuciones() -> [MicrofacetDistribution; 4] {
+        [
+            MicrofacetDistribution::Beckmann {
+                alpha_x: 0.3,
+                alpha_y: 0.3,
+            },
+            MicrofacetDistribution::Beckmann {
+                alpha_x: 0.2,
+                alpha_y: 0.5,
+            },
+            MicrofacetDistribution::GGX {
+                alpha_x: 0.3,
+                alpha_y: 0.3,
+            },
+            MicrofacetDistribution::GGX {
+                alpha_x: 0.5,
+                alpha_y: 0.2,
+            },
+        ]
+    }
+
+    #[test]
+    fn distribución_normalizada() {
+        // el área proyectada de las microfacetas es la de la superficie
+        let lado = 400;
+        let n = lado * lado;
+        for distribución in distribuciones() {
+            let total: f64 = hemisphere_grid(lado)
+                .map(|wh| distribución.d(&wh) * wh.z * 2.0 * PI)
+                .sum();
+
+            assert!((total / n as f64 - 1.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn muestreo_de_normales_visibles() {
+        // la densidad que da pdf() integra 1, y la de las muestras coincide
+        // con pdf(): el promedio de g(wh) / pdf(wh) estima la integral de g
+        let wo = Vector::new(0.5, -0.3, 0.6).normalize();
+        let g = |wh: &Vector| wh.z * wh.z;
+        let lado = 400;
+        let n = lado * lado;
+
+        for distribución in distribuciones() {
+            let (mut con_pdf, mut uniforme, mut muestreada) = (0.0, 0.0, 0.0);
+            for wh in hemisphere_grid(lado) {
+                let pdf = distribución.pdf(&wo, &wh);
+                con_pdf += pdf * 2.0 * PI;
+                uniforme += g(&wh) * pdf * 2.0 * PI;
+            }
+            for _ in 0..n {
+                let wh = distribución.sample_wh(&wo, rand::random());
+                assert!(wh.z > 0.0);
+                muestreada += g(&wh);
+            }
+
+            assert!((con_pdf / n as f64 - 1.0).abs() < 0.05);
+            assert!(((uniforme - muestreada) / n as f64).abs() < 0.01);
+        }
+    }
+
+    /// Reflectancia hacia wo estimada como el promedio de f.cos/pdf, y la
+    /// misma integrada en una grilla de direcciones.
+    fn reflectancia(lobe: &impl BxDF, wo: &Vector) -> (f64, f64) {
+        let n = 100_000;
+        let mut muestreada = 0.0;
+        for _ in 0..n {
+            if let Some(s) = lobe.sample_f(wo, rand::random()) {
+                if s.pdf > 0.0 {
+                    muestreada += s.f.max_value() as f64 * s.wi.z.abs() / s.pdf;
+                }
+            }
+        }
+
+        // en la esfera, para contar también la refracción
+        let lado = 300;
+        let uniforme: f64 = hemisphere_grid(lado)
+            .flat_map(|w| [w, Vector::new(w.x, w.y, -w.z)])
+            .map(|wi| lobe.f(wo, &wi).max_value() as f64 * wi.z.abs())
+            .sum();
+
+        (
+            muestreada / n as f64,
+            uniforme * 2.0 * PI / (lado * lado) as f64,
+        )
+    }
+
+    #[test]
+    fn reflexión_rugosa_conserva_energía() {
+        let wo = Vector::new(0.3, 0.4, 0.8).normalize();
+
+        for distribución in distribuciones() {
+            let lobe = MicrofacetReflection::new(
+                SampledSpectrum::new(1.0),
+                distribución,
+                Fresnel::NoOp,
+            );
+            let (muestreada, uniforme) = reflectancia(&lobe, &wo);
+
+            // pierde algo por la luz que rebota entre microfacetas, más con
+            // las colas largas de GGX
+            assert!(muestreada <= 1.0 && muestreada > 0.75);
+            assert!((muestreada - uniforme).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn refracción_rugosa() {
+        let fresnel = Fresnel::Dielectric {
+            eta_i: 1.0,
+            eta_t: 1.5,
+        };
+
+        for distribución in distribuciones() {
+            let reflexión = MicrofacetReflection::new(
+                SampledSpectrum::new(1.0),
+                distribución,
+                fresnel,
+            );
+            let refracción = MicrofacetTransmission::new(
+                SampledSpectrum::new(1.0),
+                distribución,
+                1.0,
+                1.5,
+            );
+
+            // desde afuera y desde adentro
+            for wo in [Vector::new(0.3, 0.0, 0.9), Vector::new(0.2, 0.1, -0.9)]
+            {
+                let wo = wo.normalize();
+                let (r, r_uniforme) = reflectancia(&reflexión, &wo);
+                let (t, t_uniforme) = reflectancia(&refracción, &wo);
+
+                let s = refracción.sample_f(&wo, (0.3, 0.6)).unwrap();
+                assert!(s.wi.z * wo.z < 0.0);
+
+                assert!((r - r_uniforme).abs() < 0.05);
+                // la refracción se concentra en un ángulo sólido más chico y
+                // las direcciones uniformes la estiman peor
+                assert!((t - t_uniforme).abs() < 0.1);
+                assert!(r + t <= 1.0 && r + t > 0.75);
+            }
+        }
+    }
+}
@@ -0,0 +1,324 @@
+mod dispersion;
+mod lambertian;
+mod layered;
+mod microfacet;
+mod oren_nayar;
+mod principled;
+mod specular;
+
+pub use dispersion::{Dispersion, D_LINE};
+pub use lambertian::LambertianReflection;
+pub use layered::{Coating, Layered, Substrate};
+pub use microfacet::{
+    MicrofacetDistribution, MicrofacetReflection, MicrofacetTransmission,
+};
+pub use oren_nayar::OrenNayar;
+pub use principled::{schlick_weight, PrincipledDiffuse, Sheen};
+pub use specular::{
+    Fresnel, SpecularReflection, SpecularTransmission, ThinDielectric,
+};
+
+use crate::geometry::{
+    create_base_using_normal, sample_versor_cos_density, Normal, Vector,
+};
+use crate::spectrum::SampledSpectrum;
+use enum_dispatch::enum_dispatch;
+use nalgebra::Matrix3;
+use std::f64::consts::FRAC_1_PI;
+
+/// Cantidad máxima de lóbulos de un BSDF.
+const MAX_LOBES: usize = 8;
+
+/// Dirección incidente elegida al muestrear un material, junto con el valor
+/// de la función de reflectancia y la densidad con la que se eligió. Si es
+/// especular, pdf no es una densidad sino la probabilidad de la dirección.
+#[derive(Clone, Copy, Debug)]
+pub struct BsdfSample {
+    pub wi: Vector,
+    pub f: SampledSpectrum,
+    pub pdf: f64,
+    pub specular: bool,
+}
+
+/// Una parte de la reflectancia de un material. Trabaja en coordenadas
+/// locales: la normal saliente de la superficie es el eje z, así que el
+/// coseno de una dirección con la normal es su componente z. Las direcciones
+/// wo y wi apuntan hacia afuera del punto.
+#[enum_dispatch]
+pub trait BxDF {
+    /// Evalúa la función para la luz que llega desde wi y sale hacia wo.
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum;
+
+    /// Elige wi usando los números aleatorios u en [0, 1). Por defecto lo
+    /// hace con densidad coseno del mismo lado que wo.
+    fn sample_f(&self, wo: &Vector, u: (f64, f64)) -> Option<BsdfSample> {
+        let mut wi = sample_versor_cos_density(&Vector::z(), u);
+        if wo.z < 0.0 {
+            wi.z = -wi.z;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.f(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            specular: false,
+        })
+    }
+
+    /// Densidad con la que sample_f() elige wi.
+    fn pdf(&self, wo: &Vector, wi: &Vector) -> f64 {
+        if same_hemisphere(wo, wi) {
+            wi.z.abs() * FRAC_1_PI
+        } else {
+            0.0
+        }
+    }
+
+    /// Si es true, solo devuelve direcciones puntuales, y f() y pdf() valen
+    /// cero en cualquier otra.
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+// los lóbulos van por valor en el arreglo del Bsdf, para no allocar en
+// cada choque, aunque algunos ocupen bastante más que otros
+#[allow(clippy::large_enum_variant)]
+#[enum_dispatch(BxDF)]
+#[derive(Clone, Copy, Debug)]
+pub enum Lobe {
+    LambertianReflection,
+    SpecularReflection,
+    SpecularTransmission,
+    MicrofacetReflection,
+    MicrofacetTransmission,
+    OrenNayar,
+    PrincipledDiffuse,
+    Sheen,
+    Layered,
+    ThinDielectric,
+}
+
+/// Si las dos direcciones, en coordenadas locales, están del mismo lado de
+/// la superficie.
+pub fn same_hemisphere(w: &Vector, wp: &Vector) -> bool {
+    w.z * wp.z > 0.0
+}
+
+/// Función de distribución de reflectancia y transmitancia en un punto, como
+/// suma de lóbulos. Recibe y devuelve direcciones en coordenadas globales.
+#[derive(Clone, Copy, Debug)]
+pub struct Bsdf {
+    base: Matrix3<f64>, // de coordenadas locales a globales
+    lobes: [Option<Lobe>; MAX_LOBES],
+    n_lobes: usize,
+}
+
+impl Bsdf {
+    /// BSDF sin lóbulos, que no refleja nada. La normal es la saliente de la
+    /// superficie, no la del lado de donde llega la luz.
+    pub fn new(normal: &Normal) -> Bsdf {
+        Bsdf {
+            base: create_base_using_normal(normal),
+            lobes: [None; MAX_LOBES],
+            n_lobes: 0,
+        }
+    }
+
+    pub fn add<L: Into<Lobe>>(&mut self, lobe: L) {
+        assert!(self.n_lobes < MAX_LOBES, "Demasiados lóbulos en el BSDF");
+
+        self.lobes[self.n_lobes] = Some(lobe.into());
+        self.n_lobes += 1;
+    }
+
+    fn lobes(&self) -> impl Iterator<Item = &Lobe> {
+        self.lobes.iter().flatten()
+    }
+
+    fn world_to_local(&self, v: &Vector) -> Vector {
+        self.base.tr_mul(v)
+    }
+
+    fn local_to_world(&self, v: &Vector) -> Vector {
+        self.base * v
+    }
+
+    /// Si todos los lóbulos son especulares. Un BSDF sin lóbulos no lo es.
+    pub fn is_specular(&self) -> bool {
+        self.n_lobes > 0 && self.lobes().all(|lobe| lobe.is_specular())
+    }
+
+    /// Evalúa la función para la luz que llega desde wi y sale hacia wo.
+    pub fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        let (wo, wi) = (self.world_to_local(wo), self.world_to_local(wi));
+
+        self.lobes()
+            .filter(|lobe| !lobe.is_specular())
+            .fold(SampledSpectrum::new(0.0), |acc, lobe| {
+                acc + lobe.f(&wo, &wi)
+            })
+    }
+
+    /// Densidad con la que sample_f() elige wi.
+    pub fn pdf(&self, wo: &Vector, wi: &Vector) -> f64 {
+        if self.n_lobes == 0 {
+            return 0.0;
+        }
+        let (wo, wi) = (self.world_to_local(wo), self.world_to_local(wi));
+
+        self.lobes().map(|lobe| lobe.pdf(&wo, &wi)).sum::<f64>()
+            / self.n_lobes as f64
+    }
+
+    /// Elige uno de los lóbulos al azar y una dirección incidente wi con él,
+    /// usando los números aleatorios u en [0, 1). Devuelve None si el
+    /// material no refleja luz hacia wo.
+    pub fn sample_f(&self, wo: &Vector, u: (f64, f64)) -> Option<BsdfSample> {
+        if self.n_lobes == 0 {
+            return None;
+        }
+
+        // uso u.0 para elegir el lóbulo y lo reescalo a [0, 1) para muestrear
+        let n = self.n_lobes as f64;
+        let elegido = ((u.0 * n) as usize).min(self.n_lobes - 1);
+        let u = ((u.0 * n - elegido as f64).min(1.0 - f64::EPSILON), u.1);
+        let lobe = self.lobes().nth(elegido)?;
+
+        let wo = self.world_to_local(wo);
+        let mut muestra = lobe.sample_f(&wo, u)?;
+        if muestra.pdf <= 0.0 {
+            return None;
+        }
+
+        // si no es especular, la dirección la pudo elegir cualquier lóbulo.
+        // Con uno solo ya está bien, y los que estiman f() al azar, como
+        // Layered, dan un cociente f / pdf con menos ruido al muestrear
+        if !muestra.specular && self.n_lobes > 1 {
+            muestra.f = self
+                .lobes()
+                .filter(|lobe| !lobe.is_specular())
+                .fold(SampledSpectrum::new(0.0), |acc, lobe| {
+                    acc + lobe.f(&wo, &muestra.wi)
+                });
+            muestra.pdf = self
+                .lobes()
+                .map(|lobe| lobe.pdf(&wo, &muestra.wi))
+                .sum::<f64>();
+        }
+        muestra.pdf /= n;
+        muestra.wi = self.local_to_world(&muestra.wi);
+
+        Some(muestra)
+    }
+
+    /// La dirección de cada lóbulo especular, con su f y su probabilidad
+    /// como si fuera el único lóbulo. Sirve para seguir todos los caminos
+    /// especulares sin elegir al azar entre ellos; u solo lo usan los
+    /// lóbulos que eligen una longitud de onda.
+    pub fn specular_samples(
+        &self,
+        wo: &Vector,
+        u: (f64, f64),
+    ) -> impl Iterator<Item = BsdfSample> + '_ {
+        let wo_local = self.world_to_local(wo);
+
+        self.lobes()
+            .filter(|lobe| lobe.is_specular())
+            .filter_map(move |lobe| lobe.sample_f(&wo_local, u))
+            .filter(|muestra| muestra.pdf > 0.0)
+            .map(|muestra| BsdfSample {
+                wi: self.local_to_world(&muestra.wi),
+                ..muestra
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_float;
+
+    /// Reflectancia hacia wo estimada como el promedio de f.cos/pdf.
+    fn reflectancia(bsdf: &Bsdf, normal: &Normal, wo: &Vector) -> f64 {
+        let n = 10_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            if let Some(s) = bsdf.sample_f(wo, rand::random()) {
+                let cos = s.wi.dot(normal).abs();
+                total += s.f.max_value() as f64 * cos / s.pdf;
+            }
+        }
+
+        total / n as f64
+    }
+
+    #[test]
+    fn coordenadas_locales() {
+        let normal = Vector::new(1.0, 2.0, -2.0).normalize();
+        let bsdf = Bsdf::new(&normal);
+
+        let local = bsdf.world_to_local(&normal);
+        assert_eq_float!(local.z, 1.0);
+
+        let v = Vector::new(0.3, -0.1, 0.7);
+        let ida_y_vuelta = bsdf.local_to_world(&bsdf.world_to_local(&v));
+        assert_eq_float!((ida_y_vuelta - v).norm(), 0.0);
+    }
+
+    #[test]
+    fn lóbulos_se_suman() {
+        // dos lóbulos difusos de 0.25 reflejan lo mismo que uno de 0.5, y la
+        // densidad sigue siendo la del coseno
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let wo = Vector::new(0.0, 1.0, 1.0).normalize();
+
+        let mut bsdf = Bsdf::new(&normal);
+        bsdf.add(LambertianReflection::new(SampledSpectrum::new(0.25)));
+        bsdf.add(LambertianReflection::new(SampledSpectrum::new(0.25)));
+
+        let wi = Vector::new(1.0, 1.0, 0.0).normalize();
+        let f = bsdf.f(&wo, &wi).max_value() as f64;
+        assert!((f - 0.5 * FRAC_1_PI).abs() < 1e-6);
+        assert_eq_float!(bsdf.pdf(&wo, &wi), wi.y * FRAC_1_PI);
+        assert!((reflectancia(&bsdf, &normal, &wo) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn difuso_y_especular() {
+        // la mitad de las muestras siguen el espejo, con probabilidad 1/2
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let wo = Vector::new(1.0, 0.0, 1.0).normalize();
+
+        let mut bsdf = Bsdf::new(&normal);
+        bsdf.add(LambertianReflection::new(SampledSpectrum::new(0.5)));
+        bsdf.add(SpecularReflection::new(
+            SampledSpectrum::new(0.5),
+            Fresnel::NoOp,
+        ));
+        assert!(!bsdf.is_specular());
+
+        let espejo = bsdf.sample_f(&wo, (0.75, 0.3)).unwrap();
+        assert!(espejo.specular);
+        assert_eq_float!(espejo.pdf, 0.5);
+        assert_eq_float!(espejo.wi.x, -wo.x);
+
+        let especulares: Vec<_> =
+            bsdf.specular_samples(&wo, (0.5, 0.5)).collect();
+        assert_eq!(especulares.len(), 1);
+        assert_eq_float!(especulares[0].pdf, 1.0);
+
+        assert!((reflectancia(&bsdf, &normal, &wo) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn sin_lóbulos_no_refleja() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let bsdf = Bsdf::new(&normal);
+
+        assert!(!bsdf.is_specular());
+        assert!(bsdf.sample_f(&normal, rand::random()).is_none());
+        assert!(bsdf.f(&normal, &normal).is_black());
+        assert_eq_float!(bsdf.pdf(&normal, &normal), 0.0);
+    }
+}
@@ -0,0 +1,96 @@
+use super::{same_hemisphere, BxDF};
+use crate::geometry::Vector;
+use crate::spectrum::SampledSpectrum;
+use std::f64::consts::FRAC_1_PI;
+
+/// Reflexión difusa de una superficie rugosa, de Oren y Nayar: la superficie
+/// está hecha de microfacetas lambertianas con pendientes gaussianas de
+/// desvío sigma. A diferencia de Lambertian devuelve más luz hacia el lado de
+/// donde llega, así que no se ve tan plana en los bordes.
+#[derive(Clone, Copy, Debug)]
+pub struct OrenNayar {
+    r: SampledSpectrum,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    /// sigma es el desvío de las pendientes en grados. Con sigma 0 es
+    /// Lambertian.
+    pub fn new(r: SampledSpectrum, sigma: f64) -> Self {
+        let sigma2 = sigma.to_radians().powi(2);
+
+        Self {
+            r,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl BxDF for OrenNayar {
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        if !same_hemisphere(wo, wi) {
+            return SampledSpectrum::new(0.0);
+        }
+
+        let sen_i = (1.0 - wi.z * wi.z).max(0.0).sqrt();
+        let sen_o = (1.0 - wo.z * wo.z).max(0.0).sqrt();
+
+        // coseno de la diferencia de los ángulos azimutales
+        let cos_phi = if sen_i > 1e-4 && sen_o > 1e-4 {
+            ((wi.x * wo.x + wi.y * wo.y) / (sen_i * sen_o)).max(0.0)
+        } else {
+            0.0
+        };
+
+        // seno del ángulo más grande y tangente del más chico
+        let (sen_alfa, tan_beta) = if wi.z.abs() > wo.z.abs() {
+            (sen_o, sen_i / wi.z.abs())
+        } else {
+            (sen_i, sen_o / wo.z.abs())
+        };
+
+        let valor = self.a + self.b * cos_phi * sen_alfa * tan_beta;
+
+        self.r * (valor * FRAC_1_PI) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::LambertianReflection;
+
+    #[test]
+    fn oren_nayar() {
+        let r = SampledSpectrum::new(0.5);
+        let liso = OrenNayar::new(r, 0.0);
+        let lambertian = LambertianReflection::new(r);
+        let rugoso = OrenNayar::new(r, 30.0);
+
+        let wo = Vector::new(0.9, 0.0, 0.2).normalize();
+        let wi = Vector::new(-0.3, 0.4, 0.8).normalize();
+
+        // sin rugosidad es Lambertian
+        let f = |lobe: &dyn BxDF, wo, wi| lobe.f(wo, wi).max_value();
+        assert_eq!(f(&liso, &wo, &wi), f(&lambertian, &wo, &wi));
+
+        // rasante, devuelve más luz hacia donde llega que hacia el otro lado
+        let opuesto = Vector::new(-wo.x, -wo.y, wo.z);
+        assert!(f(&rugoso, &wo, &wo) > f(&lambertian, &wo, &wo));
+        assert!(f(&rugoso, &wo, &opuesto) < f(&lambertian, &wo, &opuesto));
+
+        // no refleja más que el lambertiano del mismo color
+        let n = 10_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let s = rugoso.sample_f(&wo, rand::random()).unwrap();
+            total += s.f.max_value() as f64 * s.wi.z / s.pdf;
+        }
+        assert!(total / n as f64 <= 0.5);
+
+        // no transmite
+        assert!(rugoso.f(&wo, &-wi).is_black());
+    }
+}
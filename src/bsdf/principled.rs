@@ -0,0 +1,95 @@
+use super::{same_hemisphere, BxDF};
+use crate::geometry::Vector;
+use crate::spectrum::SampledSpectrum;
+use std::f64::consts::FRAC_1_PI;
+
+/// Peso (1 - cos)^5 de la aproximación de Schlick.
+pub fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+/// Difuso del modelo de Disney, de Burley (2012): en los bordes se oscurece
+/// si la superficie es lisa y se aclara si es rugosa.
+#[derive(Clone, Copy, Debug)]
+pub struct PrincipledDiffuse {
+    r: SampledSpectrum,
+    roughness: f64,
+}
+
+impl PrincipledDiffuse {
+    pub fn new(r: SampledSpectrum, roughness: f64) -> Self {
+        Self { r, roughness }
+    }
+}
+
+impl BxDF for PrincipledDiffuse {
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        let wh = wo + wi;
+        if !same_hemisphere(wo, wi) || wh == Vector::zeros() {
+            return SampledSpectrum::new(0.0);
+        }
+        let cos_d = wi.dot(&wh.normalize());
+
+        // reflectancia a incidencia rasante
+        let f_d90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let f_l = 1.0 + (f_d90 - 1.0) * schlick_weight(wi.z.abs());
+        let f_v = 1.0 + (f_d90 - 1.0) * schlick_weight(wo.z.abs());
+
+        self.r * (f_l * f_v * FRAC_1_PI) as f32
+    }
+}
+
+/// Brillo suave en los bordes de las telas, por las fibras que se ven de
+/// costado.
+#[derive(Clone, Copy, Debug)]
+pub struct Sheen {
+    r: SampledSpectrum,
+}
+
+impl Sheen {
+    pub fn new(r: SampledSpectrum) -> Self {
+        Self { r }
+    }
+}
+
+impl BxDF for Sheen {
+    fn f(&self, wo: &Vector, wi: &Vector) -> SampledSpectrum {
+        let wh = wo + wi;
+        if !same_hemisphere(wo, wi) || wh == Vector::zeros() {
+            return SampledSpectrum::new(0.0);
+        }
+        let cos_d = wi.dot(&wh.normalize());
+
+        self.r * schlick_weight(cos_d) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difuso_de_disney() {
+        let r = SampledSpectrum::new(0.5);
+        let f = |lobe: &dyn BxDF, wo: &Vector, wi: &Vector| {
+            lobe.f(wo, wi).max_value() as f64
+        };
+
+        // a incidencia normal vale lo mismo que Lambertian
+        let z = Vector::z();
+        let liso = PrincipledDiffuse::new(r, 0.0);
+        let rugoso = PrincipledDiffuse::new(r, 1.0);
+        assert!((f(&liso, &z, &z) - 0.5 * FRAC_1_PI).abs() < 1e-6);
+
+        // rasante, el liso se oscurece y el rugoso se aclara
+        let rasante = Vector::new(0.95, 0.0, 0.1).normalize();
+        assert!(f(&liso, &rasante, &rasante) < f(&liso, &z, &z));
+        assert!(f(&rugoso, &rasante, &rasante) > f(&rugoso, &z, &z));
+
+        // el brillo de las telas está en los bordes
+        let sheen = Sheen::new(SampledSpectrum::new(1.0));
+        let opuesto = Vector::new(-rasante.x, 0.0, rasante.z);
+        assert!(f(&sheen, &rasante, &opuesto) > f(&sheen, &z, &z));
+        assert!(sheen.f(&z, &-z).is_black());
+    }
+}
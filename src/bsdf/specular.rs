@@ -0,0 +1,345 @@
+use super::{schlick_weight, BsdfSample, BxDF, Dispersion, D_LINE};
+use crate::geometry::{refract, Vector};
+use crate::material::{fresnel_conductor, fresnel_dielectric};
+use crate::spectrum::{sample_wavelength, SampledSpectrum, N_SAMPLES};
+
+/// Qué fracción de la luz se refleja en una superficie especular.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Copy, Debug)]
+pub enum Fresnel {
+    /// Se refleja toda.
+    NoOp,
+    /// Superficie entre dos dieléctricos, con índices de refracción eta_i
+    /// del lado de la normal y eta_t del otro.
+    Dielectric { eta_i: f64, eta_t: f64 },
+    /// Superficie de un metal con índice de refracción complejo eta + ik,
+    /// del lado de la normal hay un dieléctrico de índice eta_i.
+    Conductor {
+        eta_i: f64,
+        eta: SampledSpectrum,
+        k: SampledSpectrum,
+    },
+    /// Aproximación de Schlick, con reflectancia r0 a incidencia normal.
+    /// Sirve para mezclar dieléctricos y metales con el color de la base.
+    Schlick { r0: SampledSpectrum },
+}
+
+impl Fresnel {
+    /// Reflectancia para la luz que forma un ángulo de coseno cos_i con la
+    /// normal. Si es negativo la luz llega del lado opuesto a la normal.
+    pub fn evaluate(&self, cos_i: f64) -> SampledSpectrum {
+        match self {
+            Fresnel::NoOp => SampledSpectrum::new(1.0),
+            Fresnel::Dielectric { eta_i, eta_t } => SampledSpectrum::new(
+                fresnel_dielectric(cos_i, *eta_i, *eta_t) as f32,
+            ),
+            Fresnel::Conductor { eta_i, eta, k } => {
+                fresnel_conductor(cos_i.abs(), *eta_i, eta, k)
+            }
+            Fresnel::Schlick { r0 } => {
+                let peso = schlick_weight(cos_i.abs()) as f32;
+                *r0 + (SampledSpectrum::new(1.0) - *r0) * peso
+            }
+        }
+    }
+}
+
+/// Reflexión perfecta respecto a la normal, multiplicada por r y por la
+/// reflectancia de Fresnel.
+#[derive(Clone, Copy, Debug)]
+pub struct SpecularReflection {
+    r: SampledSpectrum,
+    fresnel: Fresnel,
+}
+
+impl SpecularReflection {
+    pub fn new(r: SampledSpectrum, fresnel: Fresnel) -> Self {
+        Self { r, fresnel }
+    }
+}
+
+impl BxDF for SpecularReflection {
+    fn f(&self, _wo: &Vector, _wi: &Vector) -> SampledSpectrum {
+        SampledSpectrum::new(0.0)
+    }
+
+    fn sample_f(&self, wo: &Vector, _u: (f64, f64)) -> Option<BsdfSample> {
+        let wi = Vector::new(-wo.x, -wo.y, wo.z);
+        let cos = wi.z.abs();
+        if cos == 0.0 {
+            return None;
+        }
+
+        // divido por el coseno para que se cancele con el de la ecuación de
+        // rendering
+        Some(BsdfSample {
+            wi,
+            f: self.r * self.fresnel.evaluate(wi.z) / cos as f32,
+            pdf: 1.0,
+            specular: true,
+        })
+    }
+
+    fn pdf(&self, _wo: &Vector, _wi: &Vector) -> f64 {
+        0.0
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+/// Refracción perfecta entre un medio de índice eta_a, del lado de la
+/// normal, y uno de índice eta_b del otro lado, multiplicada por t y por la
+/// fracción que no se refleja. No se escala la radiancia por el cociente de
+/// los índices al cuadrado: en un objeto cerrado se cancela al entrar y
+/// salir.
+///
+/// Si el medio eta_b dispersa, cada longitud de onda se refracta en una
+/// dirección distinta: se elige una de las muestras del espectro al azar y
+/// el camino sigue solo con ella.
+#[derive(Clone, Copy, Debug)]
+pub struct SpecularTransmission {
+    t: SampledSpectrum,
+    eta_a: f64,
+    eta_b: f64,
+    dispersion: Option<Dispersion>,
+}
+
+impl SpecularTransmission {
+    pub fn new(t: SampledSpectrum, eta_a: f64, eta_b: f64) -> Self {
+        Self {
+            t,
+            eta_a,
+            eta_b,
+            dispersion: None,
+        }
+    }
+
+    /// Refracción hacia un medio cuyo índice depende de la longitud de onda.
+    pub fn dispersive(
+        t: SampledSpectrum,
+        eta_a: f64,
+        dispersion: Dispersion,
+    ) -> Self {
+        Self {
+            t,
+            eta_a,
+            eta_b: dispersion.eta(D_LINE),
+            dispersion: Some(dispersion),
+        }
+    }
+}
+
+impl BxDF for SpecularTransmission {
+    fn f(&self, _wo: &Vector, _wi: &Vector) -> SampledSpectrum {
+        SampledSpectrum::new(0.0)
+    }
+
+    fn sample_f(&self, wo: &Vector, u: (f64, f64)) -> Option<BsdfSample> {
+        // con dispersión, u.0 elige la longitud de onda que sigue
+        let (eta_b, t, pdf) = match self.dispersion {
+            Some(dispersion) => {
+                let i = ((u.0 * N_SAMPLES as f64) as usize).min(N_SAMPLES - 1);
+                let eta_b = dispersion.eta(sample_wavelength(i) as f64);
+
+                (eta_b, self.t.only_sample(i), 1.0 / N_SAMPLES as f64)
+            }
+            None => (self.eta_b, self.t, 1.0),
+        };
+
+        // si wo está del lado de la normal la luz entra al medio eta_b
+        let (eta_i, eta_t, normal) = if wo.z > 0.0 {
+            (self.eta_a, eta_b, Vector::z())
+        } else {
+            (eta_b, self.eta_a, -Vector::z())
+        };
+
+        // None si hay reflexión total interna
+        let wi = refract(wo, &normal, eta_t / eta_i)?;
+        let cos = wi.z.abs();
+        if cos == 0.0 {
+            return None;
+        }
+
+        let fresnel = fresnel_dielectric(wi.z, self.eta_a, eta_b);
+
+        Some(BsdfSample {
+            wi,
+            f: t * ((1.0 - fresnel) / cos) as f32,
+            pdf,
+            specular: true,
+        })
+    }
+
+    fn pdf(&self, _wo: &Vector, _wi: &Vector) -> f64 {
+        0.0
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+/// Lámina de dieléctrico de índice eta, tan fina que la luz que la cruza
+/// sigue derecho, como el vidrio de una ventana o una burbuja. Suma todos
+/// los rebotes entre las dos caras en la reflectancia y la transmitancia.
+#[derive(Clone, Copy, Debug)]
+pub struct ThinDielectric {
+    t: SampledSpectrum,
+    eta: f64,
+}
+
+impl ThinDielectric {
+    pub fn new(t: SampledSpectrum, eta: f64) -> Self {
+        Self { t, eta }
+    }
+}
+
+impl BxDF for ThinDielectric {
+    fn f(&self, _wo: &Vector, _wi: &Vector) -> SampledSpectrum {
+        SampledSpectrum::new(0.0)
+    }
+
+    fn sample_f(&self, wo: &Vector, u: (f64, f64)) -> Option<BsdfSample> {
+        let cos = wo.z.abs();
+        if cos == 0.0 {
+            return None;
+        }
+
+        // la luz que refleja la segunda cara sale en parte por la primera
+        let mut r = fresnel_dielectric(cos, 1.0, self.eta);
+        if r < 1.0 {
+            r += (1.0 - r) * (1.0 - r) * r / (1.0 - r * r);
+        }
+        let t = 1.0 - r;
+
+        // u.0 elige entre reflejar y cruzar según cuánto hace cada uno
+        let (wi, f, pdf) = if u.0 < r {
+            (
+                Vector::new(-wo.x, -wo.y, wo.z),
+                SampledSpectrum::new(r as f32),
+                r,
+            )
+        } else {
+            (-wo, self.t * t as f32, t)
+        };
+
+        Some(BsdfSample {
+            wi,
+            f: f / cos as f32,
+            pdf,
+            specular: true,
+        })
+    }
+
+    fn pdf(&self, _wo: &Vector, _wi: &Vector) -> f64 {
+        0.0
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_float;
+
+    #[test]
+    fn reflexión_y_refracción_suman_uno() {
+        let reflexión = SpecularReflection::new(
+            SampledSpectrum::new(1.0),
+            Fresnel::Dielectric {
+                eta_i: 1.0,
+                eta_t: 1.5,
+            },
+        );
+        let refracción =
+            SpecularTransmission::new(SampledSpectrum::new(1.0), 1.0, 1.5);
+
+        // desde afuera y desde adentro, sin pasar el ángulo crítico
+        for wo in [Vector::new(0.3, 0.2, 0.9), Vector::new(0.1, 0.0, -0.9)] {
+            let wo = wo.normalize();
+            let r = reflexión.sample_f(&wo, (0.5, 0.5)).unwrap();
+            let t = refracción.sample_f(&wo, (0.5, 0.5)).unwrap();
+
+            assert!(t.wi.z * wo.z < 0.0);
+            let total = r.f.max_value() as f64 * r.wi.z.abs()
+                + t.f.max_value() as f64 * t.wi.z.abs();
+            assert!((total - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn reflexión_total_interna() {
+        let refracción =
+            SpecularTransmission::new(SampledSpectrum::new(1.0), 1.0, 1.5);
+
+        // desde adentro con un ángulo rasante no sale nada
+        let wo = Vector::new(0.9, 0.0, -0.1).normalize();
+        assert!(refracción.sample_f(&wo, (0.5, 0.5)).is_none());
+
+        // a incidencia normal sigue derecho
+        let wo = Vector::new(0.0, 0.0, 1.0);
+        let t = refracción.sample_f(&wo, (0.5, 0.5)).unwrap();
+        assert_eq_float!(t.wi.z, -1.0);
+    }
+
+    #[test]
+    fn rojo_y_azul_se_separan() {
+        let prisma = SpecularTransmission::dispersive(
+            SampledSpectrum::new(1.0),
+            1.0,
+            Dispersion::BK7,
+        );
+        let wo = Vector::new(0.6, 0.0, 0.8);
+
+        // u.0 elige la muestra del espectro: 0 es azul y casi 1 es rojo
+        let azul = prisma.sample_f(&wo, (0.0, 0.5)).unwrap();
+        let rojo = prisma.sample_f(&wo, (0.999, 0.5)).unwrap();
+
+        // el azul se desvía más hacia la normal
+        let ángulo = |w: &Vector| w.z.abs().acos().to_degrees();
+        let (ángulo_azul, ángulo_rojo) = (ángulo(&azul.wi), ángulo(&rojo.wi));
+        assert!(ángulo_rojo - ángulo_azul > 0.1);
+
+        // cada una lleva solo su longitud de onda, y sumando sobre todas se
+        // conserva la energía que pasa
+        assert!(azul.f.only_sample(0) == azul.f);
+        assert!(rojo.f.only_sample(N_SAMPLES - 1) == rojo.f);
+        assert_eq_float!(azul.pdf, 1.0 / N_SAMPLES as f64);
+
+        // sin dispersión todas van juntas
+        let vidrio =
+            SpecularTransmission::new(SampledSpectrum::new(1.0), 1.0, 1.5);
+        let a = vidrio.sample_f(&wo, (0.0, 0.5)).unwrap();
+        let b = vidrio.sample_f(&wo, (0.999, 0.5)).unwrap();
+        assert_eq_float!((a.wi - b.wi).norm(), 0.0);
+    }
+
+    #[test]
+    fn lámina_fina() {
+        let lámina = ThinDielectric::new(SampledSpectrum::new(1.0), 1.5);
+        let wo = Vector::new(0.3, 0.1, 0.9).normalize();
+
+        // refleja más que una sola cara, cruza sin desviarse, y no se
+        // pierde luz
+        let mut total = 0.0;
+        let n = 1000;
+        for i in 0..n {
+            let u = ((i as f64 + 0.5) / n as f64, 0.5);
+            let s = lámina.sample_f(&wo, u).unwrap();
+            total += s.f.max_value() as f64 * s.wi.z.abs() / s.pdf;
+
+            if s.wi.z < 0.0 {
+                assert_eq_float!((s.wi + wo).norm(), 0.0);
+            }
+        }
+        assert!((total / n as f64 - 1.0).abs() < 1e-4);
+
+        let reflejo = lámina.sample_f(&wo, (0.0, 0.5)).unwrap();
+        assert!(reflejo.pdf > fresnel_dielectric(wo.z, 1.0, 1.5));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::geometry::{Point, Ray, Rectangle};
+use crate::geometry::{Point, Ray, Rectangle, Vector};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Camera {
@@ -50,15 +50,82 @@ impl Camera {
         }
     }
 
-    /*
-    pub fn focus(&self) -> Point {
-        self.focus
+    pub fn focus(&self) -> &Point {
+        &self.focus
     }
 
-    pub fn pantalla(&self) -> &Rectángulo {
-        &self.pantalla
+    /// Versor en la dirección en la que mira la cámara, perpendicular a la
+    /// pantalla.
+    pub fn forward(&self) -> Vector {
+        let centro = self.screen.0 + (self.screen.3 - self.screen.0) / 2.0;
+
+        (centro - self.focus).normalize()
+    }
+
+    /// Distancia del foco al punto, medida en la dirección en la que mira la
+    /// cámara.
+    pub fn depth(&self, punto: &Point) -> f64 {
+        (punto - self.focus).dot(&self.forward())
+    }
+
+    /// Área de la pantalla si estuviera a distancia 1 del foco.
+    fn unit_screen_area(&self) -> f64 {
+        let ancho = self.screen.1 - self.screen.0;
+        let alto = self.screen.2 - self.screen.0;
+        let distancia = (self.screen.0 - self.focus).dot(&self.forward());
+
+        ancho.norm() * alto.norm() / (distancia * distancia)
+    }
+
+    /// Inversa de get_ray: las coordenadas (i, j) del punto de la pantalla
+    /// por el que pasa la recta entre el foco y el punto. Devuelve None si el
+    /// punto no se ve desde la cámara.
+    pub fn raster_position(&self, punto: &Point) -> Option<(f64, f64)> {
+        let dir = punto - self.focus;
+        let adelante = self.forward();
+        let cos = dir.dot(&adelante);
+        if cos <= 0.0 {
+            return None;
+        }
+
+        // el punto de la pantalla sobre la recta
+        let distancia = (self.screen.0 - self.focus).dot(&adelante);
+        let en_pantalla = self.focus + dir * (distancia / cos) - self.screen.0;
+
+        let vec_right = self.screen.1 - self.screen.0;
+        let vec_down = self.screen.2 - self.screen.0;
+        let i = en_pantalla.dot(&vec_right) / vec_right.norm_squared()
+            * self.width as f64;
+        let j = en_pantalla.dot(&vec_down) / vec_down.norm_squared()
+            * self.height as f64;
+
+        // get_ray centra el pixel x en x, así que abarca [x - 0.5, x + 0.5)
+        let dentro = |v: f64, max: u32| (-0.5..max as f64 - 0.5).contains(&v);
+        if !dentro(i, self.width) || !dentro(j, self.height) {
+            return None;
+        }
+
+        Some((i, j))
+    }
+
+    /// Importancia que emite la cámara en la dirección dir, y la densidad
+    /// respecto al ángulo sólido con la que get_ray genera esa dirección si
+    /// se elige un punto uniforme en la pantalla. Las dos son 0 si la
+    /// dirección no pasa por la pantalla.
+    pub fn importance(&self, dir: &Vector) -> (f64, f64) {
+        let dir = dir.normalize();
+        if self.raster_position(&(self.focus + dir)).is_none() {
+            return (0.0, 0.0);
+        }
+
+        // un ángulo sólido dω ve un área dA = dω / cos³ en la pantalla a
+        // distancia 1
+        let cos = dir.dot(&self.forward());
+        let area = self.unit_screen_area();
+        let cos_3 = cos * cos * cos;
+
+        (1.0 / (area * cos_3 * cos), 1.0 / (area * cos_3))
     }
-    */
 
     pub fn width(&self) -> u32 {
         self.width
@@ -81,7 +148,12 @@ impl Camera {
         let vec_down = (self.screen.2 - self.screen.0) * j / self.height as f64;
         let point = self.screen.0 + vec_right + vec_down;
 
+        // el rayo abarca más o menos un píxel
+        let distancia = (self.screen.0 - self.focus).dot(&self.forward());
+        let píxel = (self.screen.1 - self.screen.0).norm() / self.width as f64;
+
         Ray::new(&self.focus, &(point - self.focus), std::f64::INFINITY)
+            .with_spread(píxel / distancia)
     }
 }
 
@@ -161,4 +233,55 @@ mod tests {
         let aux = Point::new(1.0, 0.0, 0.0);
         assert_eq_vec!(rayo.dir(), aux);
     }
+
+    #[test]
+    fn posición_en_pantalla_invierte_get_ray() {
+        let cámara = Camera::new(
+            &Point::new(1.0, 2.0, 3.0),
+            0.5,
+            70.0,
+            (0.3, 0.2, -0.4),
+            (40, 30),
+        );
+
+        for (i, j) in [(0.0, 0.0), (12.3, 4.5), (39.4, 29.4)] {
+            let rayo = cámara.get_ray(i, j);
+            let punto = rayo.at(7.0).unwrap();
+
+            let (x, y) = cámara.raster_position(&punto).unwrap();
+            assert_eq_float!(x, i);
+            assert_eq_float!(y, j);
+        }
+
+        // detrás de la cámara no se ve
+        let rayo = cámara.get_ray(10.0, 10.0);
+        let atrás = cámara.focus() - rayo.dir();
+        assert!(cámara.raster_position(&atrás).is_none());
+    }
+
+    #[test]
+    fn importancia_integra_uno() {
+        // la densidad de las direcciones integrada en la ventana que usa
+        // get_ray da 1
+        let cámara =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (20, 10));
+
+        // sumo pdf.dω con dω = dA.cos³, dA el área de cada celda de la
+        // pantalla a distancia 1 (la pantalla mide 2 x 1)
+        let n = 200;
+        let da = 2.0 / (n * n) as f64;
+        let mut total = 0.0;
+        for a in 0..n {
+            for b in 0..n {
+                let i = -0.5 + 20.0 * (a as f64 + 0.5) / n as f64;
+                let j = -0.5 + 10.0 * (b as f64 + 0.5) / n as f64;
+                let dir = *cámara.get_ray(i, j).dir();
+                let cos = dir.dot(&cámara.forward());
+
+                total += cámara.importance(&dir).1 * da * cos * cos * cos;
+            }
+        }
+
+        assert!((total - 1.0).abs() < 1e-3);
+    }
 }
@@ -78,47 +78,43 @@ impl AABB {
         *self = AABB::union(self, otra);
     }
 
-    pub fn intersect(&self, other: &AABB) -> AABB {
-        let x_min = self.min.x.max(other.min.x);
-        let y_min = self.min.y.max(other.min.y);
-        let z_min = self.min.z.max(other.min.z);
-        let x_max = self.max.x.min(other.max.x);
-        let y_max = self.max.y.min(other.max.y);
-        let z_max = self.max.z.min(other.max.z);
-
-        AABB {
-            min: Point::new(x_min, y_min, z_min),
-            max: Point::new(x_max, y_max, z_max),
-        }
+    #[inline]
+    pub fn diagonal(&self) -> Vector {
+        self.max - self.min
     }
 
-    pub fn overlaps(&self, other: &AABB) -> bool {
-        let x = self.max.x >= other.min.x && self.min.x <= other.max.x;
-        let y = self.max.y >= other.min.y && self.min.y <= other.max.y;
-        let z = self.max.z >= other.min.z && self.min.z <= other.max.z;
-
-        x && y && z
+    #[inline]
+    pub fn centroid(&self) -> Point {
+        self.min + self.diagonal() * 0.5
     }
 
-    pub fn point_inside(&self, p: &Point) -> bool {
-        p.x >= self.min.x
-            && p.x <= self.max.x
-            && p.y >= self.min.y
-            && p.y <= self.max.y
-            && p.z >= self.min.z
-            && p.z <= self.max.z
+    /// Devuelve el índice del eje (0, 1 o 2) en el que la caja es más larga.
+    pub fn maximum_extent(&self) -> usize {
+        let d = self.diagonal();
+
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
     }
 
-    pub fn expand(&mut self, delta: f64) {
-        let v = Vector::new(delta, delta, delta);
+    /// Posición relativa del punto dentro de la caja, (0, 0, 0) en min y
+    /// (1, 1, 1) en max. Si la caja es plana en algún eje, devuelve 0 en ese
+    /// eje.
+    pub fn offset(&self, p: &Point) -> Vector {
+        let mut o = p - self.min;
+        let d = self.diagonal();
 
-        self.min -= v;
-        self.max += v;
-    }
+        for i in 0..3 {
+            if d[i] > 0.0 {
+                o[i] /= d[i];
+            }
+        }
 
-    #[inline]
-    pub fn diagonal(&self) -> Vector {
-        self.max - self.min
+        o
     }
 
     pub fn surface_area(&self) -> f64 {
@@ -127,15 +123,11 @@ impl AABB {
         2.0 * (d.x * d.y + d.x * d.z + d.y * d.z)
     }
 
-    pub fn volume(&self) -> f64 {
-        let d = self.diagonal();
-
-        d.x * d.y * d.z
-    }
-
+    /// Devuelve el t en el que el rayo entra a la caja, si la atraviesa antes
+    /// de su max_t.
     pub fn intersect_ray(&self, rayo: &Ray) -> Option<f64> {
         let mut mínimo_intervalo = 0.0;
-        let mut máximo_intervalo = f64::INFINITY;
+        let mut máximo_intervalo = rayo.max_t();
 
         // Busco t_min y t_max respecto a X
         // La idea es que si alguno de estos es menor a 0, o si t_min es mayor
@@ -291,6 +283,19 @@ mod tests {
         assert!(caja.intersect_ray(&rayo).is_some());
     }
 
+    #[test]
+    fn caja_más_lejos_que_max_t() {
+        let caja =
+            AABB::new(&Point::new(1.0, 1.0, 1.0), &Point::new(2.0, 2.0, 2.0));
+        let dir = Vector::new(1.0, 1.0, 1.0);
+
+        let rayo = Ray::new(&Point::new(0.0, 0.0, 0.0), &dir, 1.0);
+        assert!(caja.intersect_ray(&rayo).is_none());
+
+        let rayo = Ray::new(&Point::new(0.0, 0.0, 0.0), &dir, 2.0);
+        assert!(caja.intersect_ray(&rayo).is_some());
+    }
+
     #[test]
     fn unir_cajas() {
         let c_1 =
@@ -304,6 +309,19 @@ mod tests {
         assert_eq_vec!(caja.max, Point::new(2.0, 2.0, 2.0));
     }
 
+    #[test]
+    fn eje_más_largo_de_caja() {
+        let caja =
+            AABB::new(&Point::new(0.0, 0.0, 0.0), &Point::new(1.0, 3.0, 2.0));
+
+        assert_eq!(caja.maximum_extent(), 1);
+        assert_eq_vec!(caja.centroid(), Point::new(0.5, 1.5, 1.0));
+        assert_eq_vec!(
+            caja.offset(&Point::new(0.5, 3.0, 0.0)),
+            Vector::new(0.5, 1.0, 0.0)
+        );
+    }
+
     #[test]
     fn ampliar_caja() {
         let mut caja =
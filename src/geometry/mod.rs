@@ -83,12 +83,21 @@ pub fn intersect_ray_and_triangle(
 /// t el ángulo entre el versor generado y la normal pasada como parámetro. O
 /// sea es más probable que el versor esté cerca de la normal
 pub fn random_versor_cos_density(normal: &Vector) -> Vector {
+    sample_versor_cos_density(normal, rand::random())
+}
+
+/// Igual que random_versor_cos_density, pero usando los números aleatorios
+/// (R_1, R_2) en [0, 1) que se le pasan.
+pub fn sample_versor_cos_density(
+    normal: &Vector,
+    (r_1, r_2): (f64, f64),
+) -> Vector {
     // sen(θ) = sqrt(R_1)
-    let sen_tita = rand::random::<f64>().sqrt();
+    let sen_tita = r_1.sqrt();
     // cos(θ) = sqrt(1 - sen(θ)²)
     let cos_tita = (1.0 - sen_tita * sen_tita).sqrt();
     // φ = 2.π.R_2
-    let phi: f64 = 2.0 * std::f64::consts::PI * rand::random::<f64>();
+    let phi: f64 = 2.0 * std::f64::consts::PI * r_2;
 
     let v: Vector =
         Vector::new(phi.cos() * sen_tita, phi.sin() * sen_tita, cos_tita);
@@ -96,10 +105,29 @@ pub fn random_versor_cos_density(normal: &Vector) -> Vector {
     create_base_using_normal(normal) * v
 }
 
+/// Refracta la dirección saliente wo respecto a la normal, que tiene que
+/// estar del mismo lado que wo. eta es el cociente entre el índice de
+/// refracción del otro lado y el del lado de wo. Devuelve None si hay
+/// reflexión total interna.
+pub fn refract(wo: &Vector, normal: &Normal, eta: f64) -> Option<Vector> {
+    // ley de Snell: sen(θ_t) = sen(θ_i) / eta
+    let cos_i = normal.dot(wo);
+    let sen2_i = (1.0 - cos_i * cos_i).max(0.0);
+    let sen2_t = sen2_i / (eta * eta);
+
+    if sen2_t >= 1.0 {
+        return None;
+    }
+
+    let cos_t = (1.0 - sen2_t).sqrt();
+
+    Some(-wo / eta + normal * (cos_i / eta - cos_t))
+}
+
 /// Devuelve una matriz de cambio de base a la canónica, siendo la base original
 /// una creada tomando el versor k, y dos versores cualquiera que sean
 /// ortogonales a k
-fn create_base_using_normal(normal: &Vector) -> Matrix3<f64> {
+pub fn create_base_using_normal(normal: &Vector) -> Matrix3<f64> {
     // si la normal está cerca del eje X uso el eje Y, si no uso el X
     let mut b_1 = if normal.x.abs() > 0.9 {
         Vector::new(0.0, 1.0, 0.0)
@@ -150,4 +178,20 @@ mod tests {
 
         assert!(intersect_ray_and_triangle(&vértices, &rayo).is_none());
     }
+
+    #[test]
+    fn refracción_cumple_snell() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let wo = Vector::new(1.0, 0.0, 1.0).normalize();
+
+        let wt = refract(&wo, &normal, 1.5).unwrap();
+
+        assert!((wt.norm() - 1.0).abs() < 1e-9);
+        assert!(wt.z < 0.0);
+        // sen(θ_i) = 1.5.sen(θ_t), y la componente tangencial se invierte
+        assert!((wo.x - 1.5 * -wt.x).abs() < 1e-9);
+
+        // saliendo del medio denso en un ángulo rasante no hay refracción
+        assert!(refract(&wo, &normal, 1.0 / 1.5).is_none());
+    }
 }
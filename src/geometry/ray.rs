@@ -7,6 +7,7 @@ pub struct Ray {
     dir: Vector, // debe ser normalizado en coordenadas globales. en coordenadas locales sirve para el escalado
     max_t: f64,
     // también debería guardar el medio de transmisión de la luz (humo, etc.)?
+    spread: f64, // ángulo que abarca el rayo, para filtrar las texturas
 }
 
 impl Ray {
@@ -15,6 +16,7 @@ impl Ray {
             origin: *origin,
             dir: dir.clone().normalize(),
             max_t,
+            spread: 0.0,
         }
     }
 
@@ -26,6 +28,41 @@ impl Ray {
         &self.dir
     }
 
+    pub fn max_t(&self) -> f64 {
+        self.max_t
+    }
+
+    /// A distancia t, el rayo abarca un ancho de spread * t. Los de la
+    /// cámara abarcan un píxel y los demás son finos, con spread 0.
+    pub fn spread(&self) -> f64 {
+        self.spread
+    }
+
+    pub fn with_spread(&self, spread: f64) -> Ray {
+        Ray { spread, ..*self }
+    }
+
+    /// Devuelve el mismo rayo pero cortado en max_t. Sirve para achicar el
+    /// intervalo de búsqueda a medida que se encuentran choques más cercanos.
+    #[inline]
+    pub fn with_max_t(&self, max_t: f64) -> Ray {
+        Ray { max_t, ..*self }
+    }
+
+    /// Crea un rayo que va de `origen` a `destino`, sin llegar a tocarlo. Sirve
+    /// para los shadow rays.
+    pub fn between(origen: &Point, destino: &Point) -> Ray {
+        let dir = destino - origen;
+        let distancia = dir.norm();
+
+        Ray {
+            origin: *origen,
+            dir: dir / distancia,
+            max_t: distancia * (1.0 - 1e-6),
+            spread: 0.0,
+        }
+    }
+
     #[inline]
     pub fn at(&self, t: f64) -> Option<Point> {
         if t < 0.0 || t > self.max_t {
@@ -40,10 +77,16 @@ impl Mul<&Ray> for Transform {
     type Output = Ray;
 
     fn mul(self, rhs: &Ray) -> Self::Output {
+        // la dirección se normaliza, así que hay que escalar max_t para que
+        // siga marcando el mismo punto del rayo
+        let dir = self * rhs.dir;
+        let escala = dir.norm();
+
         Ray {
             origin: self * rhs.origin,
-            dir: (self * rhs.dir).normalize(),
-            max_t: rhs.max_t, // creo que no hace falta tocar esto
+            dir: dir / escala,
+            max_t: rhs.max_t * escala,
+            spread: rhs.spread,
         }
     }
 }
@@ -126,6 +169,37 @@ mod tests {
         assert_eq_vec!(result.dir, Vector::new(0.0, 0.5, 3.0).normalize());
     }
 
+    #[test]
+    fn max_t_escalado() {
+        let rayo = Ray::new(
+            &Point::new(1.0, 0.0, 0.0),
+            &Vector::new(0.0, 0.0, 1.0),
+            2.0,
+        );
+
+        let scaling = create_scaling();
+        let local = scaling * rayo;
+
+        // el punto final del rayo es el mismo en ambos sistemas
+        let fin = rayo.at(rayo.max_t()).unwrap();
+        let fin_local = local.at(local.max_t()).unwrap();
+        assert_eq_float!(local.max_t(), 6.0);
+        assert_eq_vec!(scaling * fin, fin_local);
+        assert!(local.at(local.max_t() + 1e-6).is_none());
+    }
+
+    #[test]
+    fn rayo_entre_dos_puntos() {
+        let p = Point::new(1.0, 1.0, 1.0);
+        let q = Point::new(1.0, 4.0, 5.0);
+
+        let rayo = Ray::between(&p, &q);
+
+        assert_eq_vec!(rayo.dir(), Vector::new(0.0, 0.6, 0.8));
+        assert!(rayo.max_t() < 5.0);
+        assert!(rayo.max_t() > 5.0 - 1e-4);
+    }
+
     // traslada en (1, 0, 0)
     fn create_translation() -> Transform {
         geometry::create_translation(&Vector::new(1.0, 0.0, 0.0))
@@ -2,7 +2,7 @@ use super::integrator::SamplerIntegrator;
 use crate::camera::Camera;
 use crate::geometry::Ray;
 use crate::scene::Scene;
-use crate::shapes::ShapeOperations;
+use crate::shapes::Intersection;
 use crate::spectrum::SampledSpectrum;
 
 #[derive(Clone, Debug)]
@@ -43,30 +43,17 @@ impl SamplerIntegrator for AlbedoIntegrator {
         self.iterations
     }
 
-    fn incident_light(&self, ray: &Ray, _depth: usize) -> SampledSpectrum {
-        let black = SampledSpectrum::new(0.0);
-        // busco el objeto más cercano.
-        let intersection = match self.scene.intersect_ray(ray) {
-            Some(isect) => isect,
-            None => return black,
-        };
-
-        if let Some(ambient) = intersection.model().material().ambient_color {
-            return ambient;
-        } else if let Some(emitted) =
-            intersection.model().material().emitted_color
-        {
-            return emitted;
-        } else if let Some(diffuse) =
-            intersection.model().material().diffused_color
-        {
-            return diffuse;
-        } else if let Some(specular) =
-            intersection.model().material().specular_color
-        {
-            return specular;
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        _depth: usize,
+    ) -> SampledSpectrum {
+        match choque {
+            Some(intersection) => {
+                intersection.material().albedo(intersection.tex_coord())
+            }
+            None => SampledSpectrum::new(0.0),
         }
-
-        black
     }
 }
@@ -0,0 +1,147 @@
+use super::integrator::SamplerIntegrator;
+use crate::camera::Camera;
+use crate::geometry::{random_versor_cos_density, Ray};
+use crate::scene::Scene;
+use crate::shapes::Intersection;
+use crate::spectrum::{SampledSpectrum, SpectrumType};
+
+/// Integrador de oclusión ambiental: en el primer choque tira rayos con
+/// densidad coseno alrededor de la normal y devuelve la fracción que no
+/// choca con nada antes de max_distance. Vale 1 en un lugar abierto y 0 en
+/// uno completamente tapado.
+#[derive(Clone, Debug)]
+pub struct AmbientOcclusionIntegrator {
+    camera: Camera,
+    scene: Scene,
+    samples: usize,
+    max_distance: f64,
+    iterations: usize,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        samples: usize,
+        max_distance: f64,
+        iterations: usize,
+    ) -> AmbientOcclusionIntegrator {
+        AmbientOcclusionIntegrator {
+            camera: *camera,
+            scene: scene.clone(),
+            samples,
+            max_distance,
+            iterations,
+        }
+    }
+
+    /// Fracción de los rayos que salen del choque sin chocar con nada. Con
+    /// densidad coseno el coseno del integrando se cancela con la densidad,
+    /// así que alcanza con contarlos.
+    fn unoccluded_fraction(&self, choque: &Intersection) -> f64 {
+        let normal = choque.facing_normal();
+
+        let libres = (0..self.samples)
+            .filter(|_| {
+                let dir = random_versor_cos_density(&normal);
+                let rayo = Ray::new(
+                    &choque.spawn_point(&dir),
+                    &dir,
+                    self.max_distance,
+                );
+
+                !self.scene.is_intersecting(&rayo)
+            })
+            .count();
+
+        libres as f64 / self.samples.max(1) as f64
+    }
+}
+
+impl SamplerIntegrator for AmbientOcclusionIntegrator {
+    fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn max_depth(&self) -> usize {
+        0
+    }
+
+    fn total_samples(&self) -> usize {
+        self.iterations
+    }
+
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        _depth: usize,
+    ) -> SampledSpectrum {
+        match choque {
+            Some(choque) => {
+                let ao = self.unoccluded_fraction(&choque) as f32;
+
+                SampledSpectrum::from_RGB(
+                    (ao, ao, ao),
+                    SpectrumType::Reflectance,
+                )
+            }
+            None => SampledSpectrum::new(0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{create_translation, Point, Vector};
+    use crate::material::{Material, Type};
+    use crate::shapes::Sphere;
+
+    #[test]
+    fn oclusión_adentro_de_una_esfera() {
+        // Adentro de una esfera todos los rayos chocan con la pared, salvo
+        // que la distancia máxima sea muy chica
+        let mut scene = Scene::new();
+
+        let difuso = Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.5)),
+            ..Default::default()
+        };
+        let esfera = Sphere::new(
+            &create_translation(&Vector::new(0.0, 0.0, 0.0)),
+            5.0,
+            &difuso,
+        );
+        scene.add_shape(&esfera.into()).unwrap();
+        scene.build_bvh();
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let ray = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        let choque = scene.intersect_ray(&ray).unwrap();
+
+        let tapado = AmbientOcclusionIntegrator::new(
+            &camera,
+            &scene,
+            64,
+            f64::INFINITY,
+            1,
+        );
+        assert_eq!(tapado.unoccluded_fraction(&choque), 0.0);
+
+        let cerca =
+            AmbientOcclusionIntegrator::new(&camera, &scene, 64, 0.01, 1);
+        assert_eq!(cerca.unoccluded_fraction(&choque), 1.0);
+    }
+}
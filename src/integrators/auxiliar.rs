@@ -1,7 +1,88 @@
-use image::{ImageBuffer, Pixel, Rgb};
+use image::{ImageBuffer, Pixel, Rgb, RgbImage};
 use indicatif::{ProgressBar, ProgressStyle};
+use std::fs::File;
+use std::io::{BufWriter, Write};
 
-pub type Image = ImageBuffer<Rgb<u8>, Vec<<Rgb<u8> as Pixel>::Subpixel>>;
+/// Imagen con el color RGB lineal de cada píxel, sin recortar.
+pub type Image = ImageBuffer<Rgb<f32>, Vec<<Rgb<f32> as Pixel>::Subpixel>>;
+
+/// Guarda la imagen en el archivo. Los .pfm guardan los valores tal cual,
+/// en punto flotante; los demás formatos se guardan con 8 bits por canal.
+pub fn save_image(imagen: &Image, archivo: &str) -> Result<(), anyhow::Error> {
+    if archivo.ends_with(".pfm") {
+        write_pfm(imagen, archivo)
+    } else {
+        Ok(to_8_bits(imagen).save(archivo)?)
+    }
+}
+
+/// Pasa la imagen a 8 bits por canal, recortando cada color a [0, 1] y con
+/// corrección gamma.
+pub fn to_8_bits(imagen: &Image) -> RgbImage {
+    let mut buffer_img = RgbImage::new(imagen.width(), imagen.height());
+
+    for (pixel, color) in buffer_img.pixels_mut().zip(imagen.pixels()) {
+        // corrección gamma
+        *pixel = Rgb(color
+            .0
+            .map(|c| (256.0 * c.clamp(0.0, 1.0).powf(1.0 / 2.2)) as u8));
+    }
+
+    buffer_img
+}
+
+/// Escribe la imagen como Portable Float Map: un encabezado de texto y
+/// después los tres canales de cada píxel como f32 little endian, por filas
+/// de abajo hacia arriba.
+fn write_pfm(imagen: &Image, archivo: &str) -> Result<(), anyhow::Error> {
+    let mut salida = BufWriter::new(File::create(archivo)?);
+
+    // la escala negativa indica little endian
+    write!(salida, "PF\n{} {}\n-1.0\n", imagen.width(), imagen.height())?;
+    for fila in imagen.rows().rev() {
+        for valor in fila.flat_map(|pixel| pixel.0) {
+            salida.write_all(&valor.to_le_bytes())?;
+        }
+    }
+
+    Ok(salida.flush()?)
+}
+
+/// Lee una imagen que escribió write_pfm.
+#[cfg(test)]
+pub fn read_pfm(archivo: &str) -> Result<Image, anyhow::Error> {
+    let datos = std::fs::read(archivo)?;
+    let error = || anyhow::anyhow!("PFM inválido: {archivo}");
+
+    // el encabezado son tres líneas de texto
+    let mut líneas = datos.splitn(4, |b| *b == b'\n');
+    let mut encabezado = || {
+        líneas
+            .next()
+            .and_then(|l| std::str::from_utf8(l).ok())
+            .ok_or_else(error)
+    };
+    if encabezado()? != "PF" {
+        return Err(error());
+    }
+    let (ancho, alto) = encabezado()?.split_once(' ').ok_or_else(error)?;
+    let (ancho, alto): (u32, u32) = (ancho.parse()?, alto.parse()?);
+    if encabezado()?.parse::<f32>()? >= 0.0 {
+        return Err(error());
+    }
+    let valores: Vec<f32> = líneas
+        .next()
+        .ok_or_else(error)?
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    // las filas están de abajo hacia arriba
+    let fila = 3 * ancho as usize;
+    let valores = valores.chunks(fila).rev().flatten().copied().collect();
+
+    Image::from_raw(ancho, alto, valores).ok_or_else(error)
+}
 
 pub fn initialize_progress_bar(
     size: u64,
@@ -0,0 +1,820 @@
+use super::film::{Film, LightComponents};
+use super::integrator::SamplerIntegrator;
+use crate::bsdf::Bsdf;
+use crate::camera::Camera;
+use crate::geometry::{Normal, Point, Ray, Vector};
+use crate::material::Material;
+use crate::sampling::Distribution1D;
+use crate::scene::Scene;
+use crate::shapes::{Intersection, ShapeOperations};
+use crate::spectrum::SampledSpectrum;
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VertexType {
+    Camera,
+    Light,
+    Surface,
+}
+
+/// Vértice de un subcamino. beta es el throughput desde el comienzo del
+/// subcamino hasta el vértice. pdf_fwd es la densidad, respecto al área, con
+/// la que se eligió el vértice desde el anterior, y pdf_rev con la que se lo
+/// habría elegido si el camino se recorriera al revés.
+#[derive(Clone, Debug)]
+struct Vertex<'a> {
+    tipo: VertexType,
+    punto: Point,
+    normal: Normal, // en la cámara es la dirección en la que mira
+    wo: Vector,     // hacia el vértice anterior del subcamino
+    material: Option<&'a Material>,
+    bsdf: Option<Bsdf>, // solo en los vértices de superficie
+    luz: Option<usize>, // número de luz, si el vértice está sobre una
+    beta: SampledSpectrum,
+    delta: bool,
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(camera: &Camera, beta: SampledSpectrum) -> Vertex<'a> {
+        Vertex {
+            tipo: VertexType::Camera,
+            punto: *camera.focus(),
+            normal: camera.forward(),
+            wo: Vector::zeros(),
+            material: None,
+            bsdf: None,
+            luz: None,
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn light(
+        punto: &Point,
+        normal: &Normal,
+        luz: usize,
+        material: &'a Material,
+        beta: SampledSpectrum,
+        pdf_fwd: f64,
+    ) -> Vertex<'a> {
+        Vertex {
+            tipo: VertexType::Light,
+            punto: *punto,
+            normal: *normal,
+            wo: Vector::zeros(),
+            material: Some(material),
+            bsdf: None,
+            luz: Some(luz),
+            beta,
+            delta: false,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn surface(
+        choque: &Intersection<'a>,
+        luz: Option<usize>,
+        beta: SampledSpectrum,
+    ) -> Vertex<'a> {
+        Vertex {
+            tipo: VertexType::Surface,
+            punto: *choque.point(),
+            normal: *choque.normal(),
+            wo: *choque.direction_out(),
+            material: Some(choque.material()),
+            bsdf: Some(choque.bsdf()),
+            luz,
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    /// La cámara es puntual, así que no está sobre una superficie.
+    fn is_on_surface(&self) -> bool {
+        self.tipo != VertexType::Camera
+    }
+
+    /// Si se puede unir con otro vértice, o sea si no es especular.
+    fn is_connectible(&self) -> bool {
+        match self.tipo {
+            VertexType::Surface => !self.bsdf.is_some_and(|b| b.is_specular()),
+            VertexType::Camera | VertexType::Light => true,
+        }
+    }
+
+    /// La normal del lado de dir.
+    fn normal_towards(&self, dir: &Vector) -> Normal {
+        if self.normal.dot(dir) < 0.0 {
+            -self.normal
+        } else {
+            self.normal
+        }
+    }
+
+    /// El punto corrido un poco hacia el otro vértice, para que el rayo de
+    /// sombra no choque con la superficie del propio vértice.
+    fn spawn_point(&self, hacia: &Vertex) -> Point {
+        if !self.is_on_surface() {
+            return self.punto;
+        }
+
+        let dir = hacia.punto - self.punto;
+        self.punto + self.normal_towards(&dir) * 1e-9
+    }
+
+    /// Función de reflectancia para la luz que llega desde el vértice next y
+    /// sale hacia wo.
+    fn f(&self, next: &Vertex) -> SampledSpectrum {
+        let bsdf = match (self.tipo, &self.bsdf) {
+            (VertexType::Surface, Some(b)) => b,
+            _ => return SampledSpectrum::new(0.0),
+        };
+
+        let wi = (next.punto - self.punto).normalize();
+        bsdf.f(&self.wo, &wi)
+    }
+
+    /// Radiancia que emite el vértice hacia otro, si está sobre una luz. Las
+    /// luces emiten para los dos lados.
+    fn le(&self) -> SampledSpectrum {
+        match (self.luz, self.material) {
+            (Some(_), Some(m)) => {
+                m.emitted().unwrap_or(SampledSpectrum::new(0.0))
+            }
+            _ => SampledSpectrum::new(0.0),
+        }
+    }
+
+    /// Pasa una densidad respecto al ángulo sólido en este vértice a una
+    /// densidad respecto al área en el vértice next.
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.punto - self.punto;
+        let distancia_2 = w.norm_squared();
+        if distancia_2 == 0.0 {
+            return 0.0;
+        }
+
+        let mut pdf = pdf / distancia_2;
+        if next.is_on_surface() {
+            pdf *= next.normal.dot(&w).abs() / distancia_2.sqrt();
+        }
+
+        pdf
+    }
+
+    /// Densidad respecto al área con la que se elige next desde este vértice,
+    /// si al vértice se llegó desde prev.
+    fn pdf(
+        &self,
+        integrator: &BDPTIntegrator,
+        prev: Option<&Vertex>,
+        next: &Vertex,
+    ) -> f64 {
+        let wn = (next.punto - self.punto).normalize();
+
+        let pdf = match self.tipo {
+            VertexType::Light => return self.pdf_light(next),
+            VertexType::Camera => integrator.camera.importance(&wn).1,
+            VertexType::Surface => {
+                let (bsdf, prev) = match (&self.bsdf, prev) {
+                    (Some(b), Some(p)) => (b, p),
+                    _ => return 0.0,
+                };
+                let wp = (prev.punto - self.punto).normalize();
+
+                bsdf.pdf(&wp, &wn)
+            }
+        };
+
+        self.convert_density(pdf, next)
+    }
+
+    /// Densidad respecto al área con la que una luz en este vértice emite
+    /// hacia next. Las luces emiten con densidad coseno hacia los dos lados.
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let w = next.punto - self.punto;
+        let distancia_2 = w.norm_squared();
+        if distancia_2 == 0.0 {
+            return 0.0;
+        }
+        let w = w / distancia_2.sqrt();
+
+        let mut pdf = self.normal.dot(&w).abs() / (2.0 * PI) / distancia_2;
+        if next.is_on_surface() {
+            pdf *= next.normal.dot(&w).abs();
+        }
+
+        pdf
+    }
+
+    /// Densidad respecto al área con la que se habría elegido este vértice
+    /// como comienzo de un subcamino de luz.
+    fn pdf_light_origin(&self, integrator: &BDPTIntegrator) -> f64 {
+        match self.luz {
+            Some(luz) => {
+                integrator.distribución.discrete_pdf(luz)
+                    * integrator.scene.light(luz).pdf(&self.punto)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Path tracer bidireccional: por cada muestra arma un subcamino desde la
+/// cámara y otro desde una luz, y une cada par de vértices de los dos
+/// subcaminos. Cada forma de armar el camino se pesa con multiple importance
+/// sampling. Las uniones de los vértices de luz con la cámara (t = 1) caen en
+/// cualquier píxel, y se suman a la película como splats.
+#[derive(Clone, Debug)]
+pub struct BDPTIntegrator {
+    camera: Camera,
+    scene: Scene,
+    distribución: Distribution1D, // para elegir la luz, según su potencia
+    max_depth: usize,
+    iterations: usize,
+}
+
+impl BDPTIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        max_depth: usize,
+        iterations: usize,
+    ) -> Self {
+        Self {
+            camera: *camera,
+            scene: scene.clone(),
+            distribución: scene.light_power_distribution(),
+            max_depth,
+            iterations,
+        }
+    }
+
+    /// Estima la luz que llega por el rayo, que choca primero en choque,
+    /// combinando todas las estrategias. Si no hay película, no se usan las
+    /// estrategias t = 1 y los pesos de MIS se calculan sin ellas.
+    fn li<'s>(
+        &'s self,
+        ray: &Ray,
+        choque: Option<Intersection<'s>>,
+        depth: usize,
+        film: Option<&Film>,
+    ) -> SampledSpectrum {
+        let camera_path = self.generate_camera_subpath(ray, choque, depth + 2);
+        let light_path = self.generate_light_subpath(depth + 1);
+
+        let mut light = SampledSpectrum::new(0.0);
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                // la cantidad de rebotes del camino
+                let bounces = (s + t) as isize - 2;
+                if (s == 1 && t == 1) || bounces < 0 || bounces > depth as isize
+                {
+                    continue;
+                }
+
+                if t == 1 && film.is_none() {
+                    continue;
+                }
+
+                let (l, raster) = self.connect(
+                    &light_path,
+                    &camera_path,
+                    s,
+                    t,
+                    film.is_some(),
+                );
+
+                match (raster, film) {
+                    (Some(raster), Some(film)) => film.add_splat(raster, l),
+                    _ => light += l,
+                }
+            }
+        }
+
+        light
+    }
+
+    fn generate_camera_subpath<'s>(
+        &'s self,
+        ray: &Ray,
+        choque: Option<Intersection<'s>>,
+        max_vertices: usize,
+    ) -> Vec<Vertex<'s>> {
+        let mut path = Vec::with_capacity(max_vertices);
+        if max_vertices == 0 {
+            return path;
+        }
+
+        // con la cámara puntual, We.cos / pdf = 1
+        let beta = SampledSpectrum::new(1.0);
+        let pdf_dir = self.camera.importance(ray.dir()).1;
+
+        path.push(Vertex::camera(&self.camera, beta));
+        self.random_walk(&mut path, choque, beta, pdf_dir, max_vertices);
+
+        path
+    }
+
+    fn generate_light_subpath(&self, max_vertices: usize) -> Vec<Vertex<'_>> {
+        let mut path = Vec::with_capacity(max_vertices);
+        if max_vertices == 0 || self.scene.light_count() == 0 {
+            return path;
+        }
+
+        let (luz, pdf_luz, _) =
+            self.distribución.sample_discrete(rand::random());
+        let muestra = match self.scene.sample_emission(
+            luz,
+            rand::random(),
+            rand::random(),
+            rand::random(),
+        ) {
+            Some(m) => m,
+            None => return path,
+        };
+        let pdf_pos = muestra.pdf_pos * pdf_luz;
+
+        path.push(Vertex::light(
+            &muestra.punto,
+            &muestra.normal,
+            luz,
+            self.scene.light(luz).material(),
+            muestra.emitted,
+            pdf_pos,
+        ));
+
+        let cos = muestra.rayo.dir().dot(&muestra.normal).abs();
+        let beta = muestra.emitted * (cos / (pdf_pos * muestra.pdf_dir)) as f32;
+        let pdf_dir = muestra.pdf_dir;
+        let choque = self.scene.intersect_ray(&muestra.rayo);
+        self.random_walk(&mut path, choque, beta, pdf_dir, max_vertices);
+
+        path
+    }
+
+    /// Sigue el rayo rebotando en los materiales y agrega los vértices al
+    /// camino, hasta tener max_vertices. choque es el primer choque del
+    /// rayo que sale del último vértice.
+    fn random_walk<'s>(
+        &'s self,
+        path: &mut Vec<Vertex<'s>>,
+        choque: Option<Intersection<'s>>,
+        mut beta: SampledSpectrum,
+        pdf: f64,
+        max_vertices: usize,
+    ) {
+        let mut siguiente = choque;
+        let mut pdf_fwd = pdf;
+
+        while path.len() < max_vertices {
+            let choque = match siguiente.take() {
+                Some(isect) => isect,
+                None => break,
+            };
+
+            let anterior = path.len() - 1;
+            let mut vertex =
+                Vertex::surface(&choque, self.scene.light_index(&choque), beta);
+            vertex.pdf_fwd = path[anterior].convert_density(pdf_fwd, &vertex);
+            path.push(vertex);
+
+            if path.len() >= max_vertices {
+                break;
+            }
+
+            let wo = *choque.direction_out();
+            let normal = choque.facing_normal();
+            let bsdf = choque.bsdf();
+
+            let muestra = match bsdf.sample_f(&wo, rand::random()) {
+                Some(m) if m.pdf > 0.0 && !m.f.is_black() => m,
+                _ => break,
+            };
+
+            let cos = muestra.wi.dot(&normal).abs();
+            beta = beta * muestra.f * (cos / muestra.pdf) as f32;
+            pdf_fwd = muestra.pdf;
+            let mut pdf_rev = bsdf.pdf(&muestra.wi, &wo);
+
+            let actual = path.len() - 1;
+            if muestra.specular {
+                path[actual].delta = true;
+                pdf_fwd = 0.0;
+                pdf_rev = 0.0;
+            }
+
+            path[anterior].pdf_rev =
+                path[actual].convert_density(pdf_rev, &path[anterior]);
+
+            siguiente =
+                self.scene.intersect_ray(&choque.spawn_ray(&muestra.wi));
+        }
+    }
+
+    /// Factor geométrico entre dos vértices, incluyendo la visibilidad.
+    fn g(&self, a: &Vertex, b: &Vertex) -> f64 {
+        if !self.scene.unoccluded(&a.spawn_point(b), &b.spawn_point(a)) {
+            return 0.0;
+        }
+
+        let w = b.punto - a.punto;
+        let distancia_2 = w.norm_squared();
+        let w = w / distancia_2.sqrt();
+
+        let mut g = 1.0 / distancia_2;
+        if a.is_on_surface() {
+            g *= a.normal.dot(&w).abs();
+        }
+        if b.is_on_surface() {
+            g *= b.normal.dot(&w).abs();
+        }
+
+        g
+    }
+
+    /// Une los primeros s vértices del subcamino de luz con los primeros t
+    /// del de la cámara. Si t = 1, devuelve también la posición en la
+    /// película donde cae el aporte.
+    fn connect(
+        &self,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        light_tracing: bool,
+    ) -> (SampledSpectrum, Option<(f64, f64)>) {
+        let black = (SampledSpectrum::new(0.0), None);
+        let pt = &camera_path[t - 1];
+
+        let mut sampled = None;
+        let mut raster = None;
+
+        let light = if s == 0 {
+            // el subcamino de la cámara chocó una luz
+            pt.le() * pt.beta
+        } else if t == 1 {
+            // uno el vértice de luz con la cámara
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() {
+                return black;
+            }
+
+            raster = match self.camera.raster_position(&qs.punto) {
+                Some(r) => Some(r),
+                None => return black,
+            };
+
+            let wi = self.camera.focus() - qs.punto;
+            let distancia_2 = wi.norm_squared();
+            let we = self.camera.importance(&-wi).0;
+            if we == 0.0 {
+                return black;
+            }
+
+            // densidad de elegir el foco visto desde qs, respecto al ángulo
+            // sólido. La cámara es puntual, con área 1
+            let cos = self.camera.forward().dot(&-wi.normalize());
+            let pdf = distancia_2 / cos;
+
+            let v = Vertex::camera(
+                &self.camera,
+                SampledSpectrum::new((we / pdf) as f32),
+            );
+
+            let mut l = qs.beta * qs.f(&v) * v.beta;
+            if qs.is_on_surface() {
+                l = l * qs.normal.dot(&wi.normalize()).abs() as f32;
+            }
+
+            if l.is_black()
+                || !self.scene.unoccluded(&qs.spawn_point(&v), &v.punto)
+            {
+                return black;
+            }
+
+            sampled = Some(v);
+            l
+        } else if s == 1 {
+            // uno el vértice de la cámara con un punto elegido en una luz
+            if !pt.is_connectible() || self.scene.light_count() == 0 {
+                return black;
+            }
+
+            let (luz, pdf_luz, _) =
+                self.distribución.sample_discrete(rand::random());
+            let muestra = match self.scene.sample_light_at(
+                luz,
+                &pt.punto,
+                rand::random(),
+            ) {
+                Some(m) if m.pdf > 0.0 && pdf_luz > 0.0 => m,
+                _ => return black,
+            };
+
+            let mut v = Vertex::light(
+                &muestra.punto,
+                &muestra.normal,
+                luz,
+                self.scene.light(luz).material(),
+                muestra.emitted / (muestra.pdf * pdf_luz) as f32,
+                0.0,
+            );
+            v.pdf_fwd = v.pdf_light_origin(self);
+
+            let l = pt.beta
+                * pt.f(&v)
+                * v.beta
+                * pt.normal.dot(&muestra.wi).abs() as f32;
+
+            if l.is_black()
+                || !self.scene.unoccluded(&pt.spawn_point(&v), &v.punto)
+            {
+                return black;
+            }
+
+            sampled = Some(v);
+            l
+        } else {
+            // uno dos vértices intermedios
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() || !pt.is_connectible() {
+                return black;
+            }
+
+            let l = qs.beta * qs.f(pt) * pt.f(qs) * pt.beta;
+            if l.is_black() {
+                return black;
+            }
+
+            l * self.g(qs, pt) as f32
+        };
+
+        if light.is_black() {
+            return black;
+        }
+
+        let peso = self.mis_weight(
+            light_path,
+            camera_path,
+            sampled.as_ref(),
+            s,
+            t,
+            light_tracing,
+        );
+
+        (light * peso as f32, raster)
+    }
+
+    /// Peso de MIS para el camino armado con la estrategia (s, t), con la
+    /// heurística de la potencia. Compara las densidades con las que las
+    /// demás estrategias habrían armado el mismo camino.
+    fn mis_weight(
+        &self,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+        light_tracing: bool,
+    ) -> f64 {
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        // copio los vértices para cambiarles las densidades
+        let mut light_path = light_path[..s].to_vec();
+        let mut camera_path = camera_path[..t].to_vec();
+        if let Some(v) = sampled {
+            if s == 1 {
+                light_path[0] = v.clone();
+            } else if t == 1 {
+                camera_path[0] = v.clone();
+            }
+        }
+
+        // densidades en sentido contrario en los vértices de la unión
+        {
+            let pt = &camera_path[t - 1];
+            let pt_minus = t.checked_sub(2).map(|i| &camera_path[i]);
+            let qs = s.checked_sub(1).map(|i| &light_path[i]);
+            let qs_minus = s.checked_sub(2).map(|i| &light_path[i]);
+
+            let pt_rev = match qs {
+                Some(qs) => qs.pdf(self, qs_minus, pt),
+                None => pt.pdf_light_origin(self),
+            };
+            let pt_minus_rev = pt_minus.map(|pt_minus| match qs {
+                Some(qs) => pt.pdf(self, Some(qs), pt_minus),
+                None => pt.pdf_light(pt_minus),
+            });
+            let qs_rev = qs.map(|qs| pt.pdf(self, pt_minus, qs));
+            let qs_minus_rev = qs_minus
+                .map(|qs_minus| qs.unwrap().pdf(self, Some(pt), qs_minus));
+
+            camera_path[t - 1].pdf_rev = pt_rev;
+            camera_path[t - 1].delta = false;
+            if let Some(pdf) = pt_minus_rev {
+                camera_path[t - 2].pdf_rev = pdf;
+            }
+            if let Some(pdf) = qs_rev {
+                light_path[s - 1].pdf_rev = pdf;
+                light_path[s - 1].delta = false;
+            }
+            if let Some(pdf) = qs_minus_rev {
+                light_path[s - 2].pdf_rev = pdf;
+            }
+        }
+
+        // las densidades de los vértices especulares valen 0
+        let remap = |f: f64| if f != 0.0 { f } else { 1.0 };
+
+        let mut suma = 0.0;
+
+        // estrategias con menos vértices de la cámara
+        let mut ri = 1.0;
+        for i in (1..t).rev() {
+            ri *= remap(camera_path[i].pdf_rev) / remap(camera_path[i].pdf_fwd);
+            if !camera_path[i].delta
+                && !camera_path[i - 1].delta
+                && (i > 1 || light_tracing)
+            {
+                suma += ri * ri;
+            }
+        }
+
+        // estrategias con menos vértices de la luz
+        let mut ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap(light_path[i].pdf_rev) / remap(light_path[i].pdf_fwd);
+            let delta_anterior = i > 0 && light_path[i - 1].delta;
+            if !light_path[i].delta && !delta_anterior {
+                suma += ri * ri;
+            }
+        }
+
+        1.0 / (1.0 + suma)
+    }
+}
+
+impl SamplerIntegrator for BDPTIntegrator {
+    fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn total_samples(&self) -> usize {
+        self.iterations
+    }
+
+    fn ray_light<'a>(
+        &'a self,
+        ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        film: &Film,
+    ) -> LightComponents {
+        LightComponents::from_total(self.li(
+            ray,
+            choque,
+            self.max_depth,
+            Some(film),
+        ))
+    }
+
+    /// Sin película no se pueden sumar los caminos que llegan a la cámara
+    /// desde la luz, así que no se usa esa estrategia.
+    fn hit_light<'a>(
+        &'a self,
+        ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum {
+        self.li(ray, choque, depth, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::create_translation;
+    use crate::integrators::path::tests::{difusa, esfera_dentro_de_una_luz};
+    use crate::integrators::{DirectLightingIntegrator, LightStrategy};
+    use crate::material::Type;
+    use crate::shapes::Sphere;
+
+    #[test]
+    fn esfera_difusa_dentro_de_una_luz() {
+        // igual que en el path tracer: la esfera difusa refleja 0.5
+        let scene = esfera_dentro_de_una_luz(&difusa());
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let integrator = BDPTIntegrator::new(&camera, &scene, 5, 1);
+
+        let ray = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        // con 5 rebotes se pierde 0.5⁶ de la luz
+        let n = 3000;
+        let total = (0..n)
+            .map(|_| integrator.incident_light(&ray, 5).max_value() as f64)
+            .sum::<f64>();
+
+        assert!((total / n as f64 - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn imagen_coincide_con_luz_directa() {
+        // Una luz chica sobre un piso difuso, detrás de la cámara. Con los
+        // splats de t = 1 el promedio de la imagen tiene que dar lo mismo que
+        // con los otros integradores
+        let mut scene = Scene::new();
+
+        let luz = Material {
+            tipo: Type::Emitter,
+            emitted_color: Some(SampledSpectrum::new(5.0)),
+            ..Default::default()
+        };
+        let difuso = Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.5)),
+            ..Default::default()
+        };
+        let esfera_luz = Sphere::new(
+            &create_translation(&Vector::new(-1.0, 0.0, 1.5)),
+            0.3,
+            &luz,
+        );
+        let piso = Sphere::new(
+            &create_translation(&Vector::new(0.0, 0.0, -100.0)),
+            100.0,
+            &difuso,
+        );
+        scene.add_shape(&esfera_luz.into()).unwrap();
+        scene.add_shape(&piso.into()).unwrap();
+        scene.build_bvh();
+
+        let camera = Camera::new(
+            &Point::new(0.0, 0.0, 1.0),
+            1.0,
+            90.0,
+            (0.0, 0.6, 0.0),
+            (4, 4),
+        );
+
+        let samples = 1000;
+        // con un rebote la luz directa sirve de referencia, y tiene poco ruido
+        let bdpt = BDPTIntegrator::new(&camera, &scene, 1, samples);
+        let directa = DirectLightingIntegrator::new(
+            &camera,
+            &scene,
+            LightStrategy::SampleAll,
+            1,
+            samples,
+        );
+
+        let film = Film::new(4, 4);
+        let mut promedio_bdpt = 0.0;
+        let mut promedio_directa = 0.0;
+        for (x, y) in (0..4).flat_map(|x| (0..4).map(move |y| (x, y))) {
+            for i in 0..samples {
+                promedio_bdpt += bdpt
+                    .evaluate_pixel_sample((x, y), i, &film, true)
+                    .light
+                    .total
+                    .max_value() as f64;
+                promedio_directa += directa
+                    .evaluate_pixel_sample((x, y), i, &film, true)
+                    .light
+                    .total
+                    .max_value() as f64;
+            }
+        }
+        let splats = film.total_splats().max_value() as f64;
+        let promedio_bdpt = (promedio_bdpt + splats) / (16 * samples) as f64;
+        let promedio_directa = promedio_directa / (16 * samples) as f64;
+
+        assert!(promedio_directa > 0.0);
+        assert!(
+            (promedio_bdpt - promedio_directa).abs() / promedio_directa < 0.1
+        );
+    }
+}
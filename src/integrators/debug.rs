@@ -0,0 +1,374 @@
+use super::auxiliar::Image;
+use super::film::{Aov, Film};
+use super::integrator::SamplerIntegrator;
+use crate::camera::Camera;
+use crate::geometry::Ray;
+use crate::parallel::ThreadPool;
+use crate::scene::Scene;
+use crate::shapes::Intersection;
+use crate::spectrum::{SampledSpectrum, SpectrumType};
+
+/// Qué dato del primer choque muestra el DebugIntegrator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugChannel {
+    /// Distancia al foco medida en la dirección en la que mira la cámara.
+    Depth,
+    /// Posición del choque en coordenadas globales.
+    Position,
+    /// Coordenadas paramétricas de la figura (baricéntricas en triángulos).
+    UV,
+    /// Índice del objeto chocado en la escena.
+    ShapeIndex,
+    /// Índice del material del objeto chocado en la escena.
+    MaterialIndex,
+}
+
+/// Integrador para revisar escenas: en vez de luz devuelve un dato del
+/// primer choque. Los valores se multiplican por scale y se muestran tal
+/// cual, o con colores falsos para que se distingan mejor. Tal cual no se
+/// recortan ni se redondean, así que guardados en un .pfm se pueden leer
+/// exactos. Los rayos que no chocan con nada quedan negros.
+#[derive(Clone, Debug)]
+pub struct DebugIntegrator {
+    camera: Camera,
+    scene: Scene,
+    channel: DebugChannel,
+    false_color: bool,
+    scale: f64,
+    iterations: usize,
+}
+
+impl DebugIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        channel: DebugChannel,
+        false_color: bool,
+        scale: f64,
+        iterations: usize,
+    ) -> DebugIntegrator {
+        DebugIntegrator {
+            camera: *camera,
+            scene: scene.clone(),
+            channel,
+            false_color,
+            scale,
+            iterations,
+        }
+    }
+
+    /// Valor del canal en el choque, sin escalar. Los canales escalares
+    /// repiten el valor en las tres componentes.
+    fn value(&self, choque: &Intersection) -> (f64, f64, f64) {
+        let escalar = |x: f64| (x, x, x);
+
+        match self.channel {
+            DebugChannel::Depth => escalar(self.camera.depth(choque.point())),
+            DebugChannel::Position => {
+                let p = choque.point();
+                (p.x, p.y, p.z)
+            }
+            DebugChannel::UV => {
+                let (u, v) = choque.uv();
+                (u, v, 0.0)
+            }
+            DebugChannel::ShapeIndex => {
+                escalar(choque.shape_index().map_or(-1.0, |i| i as f64))
+            }
+            DebugChannel::MaterialIndex => escalar(
+                self.scene
+                    .material_index(choque.material())
+                    .map_or(-1.0, |i| i as f64),
+            ),
+        }
+    }
+
+    /// Valor del canal en el choque, multiplicado por scale.
+    fn scaled_value(&self, choque: &Intersection) -> (f64, f64, f64) {
+        let (x, y, z) = self.value(choque);
+
+        (x * self.scale, y * self.scale, z * self.scale)
+    }
+
+    /// Imagen con el promedio del valor escalado en cada píxel, sin pasar
+    /// por espectros. Calcula cada fila en paralelo.
+    fn raw_image(&self) -> Image {
+        let width = self.camera.width();
+        let height = self.camera.height();
+        let n = self.iterations;
+
+        let mut imagen = Image::new(width, height);
+        let mut thread_pool = ThreadPool::new();
+
+        for (y, fila) in imagen.chunks_mut(3 * width as usize).enumerate() {
+            thread_pool.add_task(move || {
+                for (x, pixel) in fila.chunks_mut(3).enumerate() {
+                    let mut suma = (0.0, 0.0, 0.0);
+                    for i in 0..n {
+                        let ray = self.pixel_ray((x, y), i);
+                        if let Some(choque) = self.scene.intersect_ray(&ray) {
+                            let (r, g, b) = self.scaled_value(&choque);
+                            suma = (suma.0 + r, suma.1 + g, suma.2 + b);
+                        }
+                    }
+
+                    let n = n as f64;
+                    pixel.copy_from_slice(&[
+                        (suma.0 / n) as f32,
+                        (suma.1 / n) as f32,
+                        (suma.2 / n) as f32,
+                    ]);
+                }
+            });
+        }
+        thread_pool.run(|| {});
+        drop(thread_pool);
+
+        imagen
+    }
+
+    /// Color falso para el valor escalado del canal.
+    fn false_color(&self, (x, y, z): (f64, f64, f64)) -> (f64, f64, f64) {
+        match self.channel {
+            // de azul (0) a rojo (1)
+            DebugChannel::Depth => {
+                hsv_to_rgb((1.0 - x.clamp(0.0, 1.0)) * 2.0 / 3.0, 1.0, 1.0)
+            }
+            // como las normales, el origen queda gris
+            DebugChannel::Position => (
+                x.mul_add(0.5, 0.5),
+                y.mul_add(0.5, 0.5),
+                z.mul_add(0.5, 0.5),
+            ),
+            // se repite cada 1 / scale, para ver cómo se estira
+            DebugChannel::UV => (x.rem_euclid(1.0), y.rem_euclid(1.0), 0.0),
+            // un color distinto para cada índice
+            DebugChannel::ShapeIndex | DebugChannel::MaterialIndex => {
+                hsv_to_rgb(
+                    (x * 0.618_033_988_749_895).rem_euclid(1.0),
+                    0.7,
+                    1.0,
+                )
+            }
+        }
+    }
+}
+
+/// Pasa un color de HSV a RGB, con las tres componentes en [0, 1].
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> (f64, f64, f64) {
+    let h = h.rem_euclid(1.0) * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = v - c;
+
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    (r + m, g + m, b + m)
+}
+
+impl SamplerIntegrator for DebugIntegrator {
+    fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn max_depth(&self) -> usize {
+        0
+    }
+
+    fn total_samples(&self) -> usize {
+        self.iterations
+    }
+
+    /// Sin colores falsos, la imagen final se arma aparte con los valores
+    /// tal cual. Las demás salidas se calculan como siempre.
+    fn render(&self, aovs: &[Aov]) -> Result<Vec<Image>, anyhow::Error> {
+        let film = Film::with_aovs(
+            self.camera.width() as usize,
+            self.camera.height() as usize,
+            aovs,
+        );
+
+        let crudo = !self.false_color;
+        if !crudo || film.has_extra_aovs() {
+            self.render_tiles(&film, !crudo && film.needs_light())?;
+        }
+
+        let mut imágenes = film.to_images(0.0);
+        let imagen_final = aovs.iter().position(|aov| *aov == Aov::Beauty);
+        if let Some(i) = imagen_final.filter(|_| crudo) {
+            imágenes[i] = self.raw_image();
+        }
+
+        Ok(imágenes)
+    }
+
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        _depth: usize,
+    ) -> SampledSpectrum {
+        let choque = match choque {
+            Some(intersection) => intersection,
+            None => return SampledSpectrum::new(0.0),
+        };
+
+        let escalado = self.scaled_value(&choque);
+        let (r, g, b) = if self.false_color {
+            self.false_color(escalado)
+        } else {
+            escalado
+        };
+
+        SampledSpectrum::from_RGB(
+            (r as f32, g as f32, b as f32),
+            SpectrumType::Reflectance,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{create_translation, Point, Transform, Vector};
+    use crate::integrators::auxiliar::{read_pfm, save_image};
+    use crate::material::{Material, Type};
+    use crate::shapes::{Sphere, Triangle};
+    use crate::{assert_eq_float, assert_eq_vec};
+
+    fn escena() -> Scene {
+        let rojo = Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.5)),
+            ..Default::default()
+        };
+        let verde = Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.2)),
+            ..Default::default()
+        };
+
+        let mut scene = Scene::new();
+        for (y, material) in [(-2.0, &rojo), (0.0, &verde), (2.0, &rojo)] {
+            let esfera = Sphere::new(
+                &create_translation(&Vector::new(5.0, y, 0.0)),
+                1.0,
+                material,
+            );
+            scene.add_shape(&esfera.into()).unwrap();
+        }
+        scene.build_bvh();
+
+        scene
+    }
+
+    fn integrador(scene: &Scene, channel: DebugChannel) -> DebugIntegrator {
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+
+        DebugIntegrator::new(&camera, scene, channel, false, 1.0, 1)
+    }
+
+    #[test]
+    fn profundidad_y_posición() {
+        let scene = escena();
+        let rayo = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+        let choque = scene.intersect_ray(&rayo).unwrap();
+
+        let profundidad =
+            integrador(&scene, DebugChannel::Depth).value(&choque);
+        assert_eq_float!(profundidad.0, 4.0);
+
+        let (x, y, z) =
+            integrador(&scene, DebugChannel::Position).value(&choque);
+        assert_eq_vec!(Point::new(x, y, z), Point::new(4.0, 0.0, 0.0));
+
+        // el rayo pega en el ecuador de la esfera, del lado de -x
+        let (u, v, _) = integrador(&scene, DebugChannel::UV).value(&choque);
+        assert_eq_float!(u, 0.5);
+        assert_eq_float!(v, 0.5);
+    }
+
+    #[test]
+    fn índices_de_objetos_y_materiales() {
+        let scene = escena();
+        let choque = |y: f64| {
+            let rayo = Ray::new(
+                &Point::new(0.0, y, 0.0),
+                &Vector::new(1.0, 0.0, 0.0),
+                f64::INFINITY,
+            );
+            scene.intersect_ray(&rayo).unwrap()
+        };
+
+        let objetos = integrador(&scene, DebugChannel::ShapeIndex);
+        let materiales = integrador(&scene, DebugChannel::MaterialIndex);
+
+        let [abajo, medio, arriba] = [-2.0, 0.0, 2.0].map(choque);
+
+        // cada esfera es un objeto distinto
+        let índices = [&abajo, &medio, &arriba].map(|c| objetos.value(c).0);
+        assert!(índices[0] != índices[1] && índices[1] != índices[2]);
+        assert!(índices[0] != índices[2]);
+
+        // las de los costados comparten material
+        assert_eq!(materiales.value(&abajo).0, 0.0);
+        assert_eq!(materiales.value(&medio).0, 1.0);
+        assert_eq!(materiales.value(&arriba).0, 0.0);
+    }
+
+    #[test]
+    fn crudo_se_lee_exacto() {
+        // un triángulo grande de frente a la cámara, todo el píxel lo ve a
+        // la misma profundidad
+        let mut scene = Scene::new();
+        let triángulo = Triangle::new(
+            &Point::new(7.25, -50.0, -50.0),
+            &Point::new(7.25, 50.0, -50.0),
+            &Point::new(7.25, 0.0, 50.0),
+            &Transform::identity(),
+            &Material::default(),
+        );
+        scene.add_shape(&triángulo.into()).unwrap();
+        scene.build_bvh();
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (2, 1));
+        let integrador = DebugIntegrator::new(
+            &camera,
+            &scene,
+            DebugChannel::Depth,
+            false,
+            1.0,
+            4,
+        );
+
+        let imagen = integrador.render(&[Aov::Beauty]).unwrap().remove(0);
+        let archivo = std::env::temp_dir().join("raytracer-profundidad.pfm");
+        let archivo = archivo.to_str().unwrap();
+        save_image(&imagen, archivo).unwrap();
+        let leída = read_pfm(archivo).unwrap();
+        std::fs::remove_file(archivo).unwrap();
+
+        // pasa de 1 sin recortarse, y vuelve sin cambios
+        assert_eq!(leída.dimensions(), (2, 1));
+        for pixel in leída.pixels() {
+            assert_eq!(pixel.0, [7.25; 3]);
+        }
+    }
+}
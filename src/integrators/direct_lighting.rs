@@ -0,0 +1,379 @@
+use super::integrator::SamplerIntegrator;
+use crate::bsdf::Bsdf;
+use crate::camera::Camera;
+use crate::geometry::{Normal, Ray, Vector};
+use crate::sampling::{power_heuristic, Distribution1D};
+use crate::scene::Scene;
+use crate::shapes::Intersection;
+use crate::spectrum::SampledSpectrum;
+
+/// Cómo se eligen las luces en cada punto.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightStrategy {
+    /// Se toma una muestra de cada luz y se suman.
+    SampleAll,
+    /// Se elige una sola luz, todas con la misma probabilidad.
+    Uniform,
+    /// Se elige una sola luz, con probabilidad proporcional a su potencia.
+    Power,
+}
+
+/// Integrador que solo calcula la luz directa: la que llega desde las fuentes
+/// de luz y rebota una vez hacia la cámara. Los materiales especulares se
+/// siguen hasta max_depth rebotes, para que los espejos no queden negros.
+#[derive(Clone, Debug)]
+pub struct DirectLightingIntegrator {
+    camera: Camera,
+    scene: Scene,
+    strategy: LightStrategy,
+    distribución: Distribution1D, // para elegir una luz
+    max_depth: usize,
+    iterations: usize,
+}
+
+impl DirectLightingIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        strategy: LightStrategy,
+        max_depth: usize,
+        iterations: usize,
+    ) -> Self {
+        let pesos: Vec<f64> = (0..scene.light_count())
+            .map(|luz| match strategy {
+                LightStrategy::Power => scene.light_power(luz),
+                _ => 1.0,
+            })
+            .collect();
+
+        Self {
+            camera: *camera,
+            scene: scene.clone(),
+            strategy,
+            distribución: Distribution1D::new(&pesos),
+            max_depth,
+            iterations,
+        }
+    }
+
+    /// Suma la luz directa de todas las luces, una muestra por luz.
+    fn sample_all_lights(
+        &self,
+        choque: &Intersection,
+        bsdf: &Bsdf,
+        normal: &Normal,
+        wo: &Vector,
+    ) -> SampledSpectrum {
+        (0..self.scene.light_count()).fold(
+            SampledSpectrum::new(0.0),
+            |acc, luz| {
+                acc + self.estimate_direct(luz, choque, bsdf, normal, wo)
+            },
+        )
+    }
+
+    /// Elige una sola luz según la distribución y divide por la
+    /// probabilidad de haberla elegido.
+    fn sample_one_light(
+        &self,
+        choque: &Intersection,
+        bsdf: &Bsdf,
+        normal: &Normal,
+        wo: &Vector,
+    ) -> SampledSpectrum {
+        if self.scene.light_count() == 0 {
+            return SampledSpectrum::new(0.0);
+        }
+
+        let (luz, pdf, _) = self.distribución.sample_discrete(rand::random());
+        if pdf == 0.0 {
+            return SampledSpectrum::new(0.0);
+        }
+
+        self.estimate_direct(luz, choque, bsdf, normal, wo) / pdf as f32
+    }
+
+    /// Luz que llega desde la luz número luz y se refleja hacia wo. Combina
+    /// una muestra de la luz con una del material usando MIS. Si el material
+    /// elige un lóbulo especular, la luz solo se puede ver así y cuenta
+    /// entera.
+    fn estimate_direct(
+        &self,
+        luz: usize,
+        choque: &Intersection,
+        bsdf: &Bsdf,
+        normal: &Normal,
+        wo: &Vector,
+    ) -> SampledSpectrum {
+        let mut light = SampledSpectrum::new(0.0);
+
+        // muestreo la luz
+        if let Some(muestra) =
+            self.scene
+                .sample_light_at(luz, choque.point(), rand::random())
+        {
+            let f =
+                bsdf.f(wo, &muestra.wi) * muestra.wi.dot(normal).abs() as f32;
+
+            if muestra.pdf > 0.0
+                && !f.is_black()
+                && self.scene.unoccluded(
+                    &choque.spawn_point(&muestra.wi),
+                    &muestra.punto,
+                )
+            {
+                let bsdf_pdf = bsdf.pdf(wo, &muestra.wi);
+                let peso = power_heuristic(1, muestra.pdf, 1, bsdf_pdf);
+
+                light += f * muestra.emitted * (peso / muestra.pdf) as f32;
+            }
+        }
+
+        // muestreo el material, solo cuenta si el rayo choca esta misma luz
+        if let Some(muestra) = bsdf.sample_f(wo, rand::random()) {
+            if muestra.pdf == 0.0 || muestra.f.is_black() {
+                return light;
+            }
+
+            let rayo = choque.spawn_ray(&muestra.wi);
+            let choque_luz = match self.scene.intersect_ray(&rayo) {
+                Some(c) if self.scene.light_index(&c) == Some(luz) => c,
+                _ => return light,
+            };
+
+            let emitted = match choque_luz.material().emitted() {
+                Some(e) => e,
+                None => return light,
+            };
+
+            let peso = if muestra.specular {
+                1.0
+            } else {
+                let light_pdf =
+                    self.scene.light_pdf_at(luz, choque.point(), &choque_luz);
+                power_heuristic(1, muestra.pdf, 1, light_pdf)
+            };
+            let cos = muestra.wi.dot(normal).abs();
+
+            light += muestra.f * emitted * (cos * peso / muestra.pdf) as f32;
+        }
+
+        light
+    }
+
+    /// Luz que se refleja en el choque hacia el rayo que llegó: la directa
+    /// de las luces, y la que llega siguiendo cada lóbulo especular, si
+    /// todavía quedan rebotes. Las luces que se ven por un reflejo
+    /// especular ya las cuenta estimate_direct(), así que en los choques
+    /// de los reflejos no se suma la luz emitida.
+    fn scattered_light(
+        &self,
+        choque: &Intersection,
+        depth: usize,
+    ) -> SampledSpectrum {
+        let wo = *choque.direction_out();
+        let normal = choque.facing_normal();
+        let bsdf = choque.bsdf();
+
+        let mut light = match self.strategy {
+            LightStrategy::SampleAll => {
+                self.sample_all_lights(choque, &bsdf, &normal, &wo)
+            }
+            LightStrategy::Uniform | LightStrategy::Power => {
+                self.sample_one_light(choque, &bsdf, &normal, &wo)
+            }
+        };
+
+        if depth == 0 {
+            return light;
+        }
+
+        for muestra in bsdf.specular_samples(&wo, rand::random()) {
+            let rayo = choque.spawn_ray(&muestra.wi);
+            if let Some(reflejo) = self.scene.intersect_ray(&rayo) {
+                let cos = muestra.wi.dot(&normal).abs();
+                light += muestra.f
+                    * self.scattered_light(&reflejo, depth - 1)
+                    * (cos / muestra.pdf) as f32;
+            }
+        }
+
+        light
+    }
+}
+
+impl SamplerIntegrator for DirectLightingIntegrator {
+    fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn total_samples(&self) -> usize {
+        self.iterations
+    }
+
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum {
+        let choque = match choque {
+            Some(isect) => isect,
+            None => return SampledSpectrum::new(0.0),
+        };
+
+        // la luz emitida solo se ve si se choca directamente
+        let emitted = choque.material().emitted();
+
+        emitted.unwrap_or(SampledSpectrum::new(0.0))
+            + self.scattered_light(&choque, depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{create_translation, Point};
+    use crate::material::{fresnel_dielectric, Material, Type};
+    use crate::shapes::Sphere;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    /// Un plano difuso (una esfera enorme) iluminado por dos luces chicas de
+    /// distinta potencia.
+    fn escena_con_dos_luces() -> Scene {
+        let mut scene = Scene::new();
+
+        let difuso = Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.5)),
+            ..Default::default()
+        };
+
+        let piso = Sphere::new(
+            &create_translation(&Vector::new(0.0, -1000.0, 0.0)),
+            1000.0,
+            &difuso,
+        );
+        scene.add_shape(&piso.into()).unwrap();
+
+        for (x, potencia) in [(-2.0, 1.0), (2.0, 4.0)] {
+            let luz = Material {
+                tipo: Type::Emitter,
+                emitted_color: Some(SampledSpectrum::new(potencia)),
+                ..Default::default()
+            };
+            let esfera = Sphere::new(
+                &create_translation(&Vector::new(x, 2.0, 0.0)),
+                0.25,
+                &luz,
+            );
+            scene.add_shape(&esfera.into()).unwrap();
+        }
+
+        scene.build_bvh();
+        scene
+    }
+
+    fn promedio(integrator: &DirectLightingIntegrator, n: usize) -> f64 {
+        let ray = Ray::new(
+            &Point::new(0.0, 1.0, 0.0),
+            &Vector::new(0.0, -1.0, 0.0),
+            f64::INFINITY,
+        );
+
+        (0..n)
+            .map(|_| integrator.incident_light(&ray, 5).max_value() as f64)
+            .sum::<f64>()
+            / n as f64
+    }
+
+    #[test]
+    fn estrategias_de_luces_coinciden() {
+        let scene = escena_con_dos_luces();
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+
+        let todas = DirectLightingIntegrator::new(
+            &camera,
+            &scene,
+            LightStrategy::SampleAll,
+            5,
+            1,
+        );
+        let uniforme = DirectLightingIntegrator::new(
+            &camera,
+            &scene,
+            LightStrategy::Uniform,
+            5,
+            1,
+        );
+
+        let esperado = promedio(&todas, 4000);
+        let obtenido = promedio(&uniforme, 8000);
+
+        assert!(esperado > 0.0);
+        assert!((esperado - obtenido).abs() / esperado < 0.05);
+    }
+
+    #[test]
+    fn reflejo_de_la_luz_en_un_plástico() {
+        // un plástico negro refleja la luz solo con el barniz: lo que se ve
+        // en el reflejo es la luz por la reflectancia de Fresnel
+        let mut scene = Scene::new();
+        let plástico = Material {
+            tipo: Type::Plastic,
+            ambient_color: Some(SampledSpectrum::new(0.0)),
+            specular_color: Some(SampledSpectrum::new(1.0)),
+            optical_density: Some(1.5),
+            ..Default::default()
+        };
+        let piso = Sphere::new(
+            &create_translation(&Vector::new(0.0, -1000.0, 0.0)),
+            1000.0,
+            &plástico,
+        );
+        scene.add_shape(&piso.into()).unwrap();
+
+        let luz = Material {
+            tipo: Type::Emitter,
+            emitted_color: Some(SampledSpectrum::new(1.0)),
+            ..Default::default()
+        };
+        let esfera = Sphere::new(
+            &create_translation(&Vector::new(2.0, 2.0, 0.0)),
+            0.5,
+            &luz,
+        );
+        scene.add_shape(&esfera.into()).unwrap();
+        scene.build_bvh();
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let ray = Ray::new(
+            &Point::new(-2.0, 2.0, 0.0),
+            &Vector::new(1.0, -1.0, 0.0),
+            f64::INFINITY,
+        );
+        let esperado = fresnel_dielectric(FRAC_1_SQRT_2, 1.0, 1.5);
+
+        for strategy in [LightStrategy::SampleAll, LightStrategy::Power] {
+            let integrator =
+                DirectLightingIntegrator::new(&camera, &scene, strategy, 5, 1);
+            let n = 4000;
+            let obtenido = (0..n)
+                .map(|_| integrator.incident_light(&ray, 5).max_value() as f64)
+                .sum::<f64>()
+                / n as f64;
+
+            assert!((obtenido - esperado).abs() / esperado < 0.05);
+        }
+    }
+}
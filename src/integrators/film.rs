@@ -0,0 +1,477 @@
+use super::auxiliar::Image;
+use crate::camera::Camera;
+use crate::geometry::{Normal, Point};
+use crate::shapes::Intersection;
+use crate::spectrum::SampledSpectrum;
+use image::Rgb;
+use std::sync::Mutex;
+
+/// Salidas que se pueden pedir al renderizar. Todas salen de los mismos
+/// rayos primarios.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    /// La imagen final.
+    Beauty,
+    /// El color del material en el primer choque.
+    Albedo,
+    /// La normal en el primer choque, pasada a colores como las normales.
+    Normal,
+    /// La profundidad del primer choque respecto a la cámara, dividida por
+    /// la mayor de la imagen.
+    Depth,
+    /// La posición del primer choque en coordenadas globales.
+    Position,
+    /// La luz que llega después de un rebote.
+    Direct,
+    /// La luz que llega después de dos o más rebotes.
+    Indirect,
+    /// La luz que emiten los objetos que se ven directamente.
+    Emission,
+    /// La cantidad de muestras de cada píxel, dividida por la mayor.
+    SampleCount,
+}
+
+impl Aov {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Beauty => "beauty",
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Emission => "emission",
+            Aov::SampleCount => "samples",
+        }
+    }
+
+    pub fn from_name(nombre: &str) -> Option<Aov> {
+        [
+            Aov::Beauty,
+            Aov::Albedo,
+            Aov::Normal,
+            Aov::Depth,
+            Aov::Position,
+            Aov::Direct,
+            Aov::Indirect,
+            Aov::Emission,
+            Aov::SampleCount,
+        ]
+        .into_iter()
+        .find(|aov| aov.name() == nombre)
+    }
+
+    /// Si hace falta calcular la luz para esta salida.
+    fn needs_light(&self) -> bool {
+        matches!(
+            self,
+            Aov::Beauty | Aov::Direct | Aov::Indirect | Aov::Emission
+        )
+    }
+}
+
+/// La luz que llega por un rayo, separada según cuántas veces rebotó. Los
+/// integradores que no la separan dejan las partes en negro y solo llenan
+/// el total.
+#[derive(Clone, Copy, Debug)]
+pub struct LightComponents {
+    pub total: SampledSpectrum,
+    pub emission: SampledSpectrum,
+    pub direct: SampledSpectrum,
+    pub indirect: SampledSpectrum,
+}
+
+impl Default for LightComponents {
+    fn default() -> Self {
+        LightComponents::from_total(SampledSpectrum::new(0.0))
+    }
+}
+
+impl LightComponents {
+    pub fn from_total(total: SampledSpectrum) -> LightComponents {
+        let black = SampledSpectrum::new(0.0);
+
+        LightComponents {
+            total,
+            emission: black,
+            direct: black,
+            indirect: black,
+        }
+    }
+
+    /// Suma la luz de un camino que rebotó rebotes veces antes de llegar.
+    pub fn add(&mut self, rebotes: usize, luz: SampledSpectrum) {
+        self.total += luz;
+        match rebotes {
+            0 => self.emission += luz,
+            1 => self.direct += luz,
+            _ => self.indirect += luz,
+        }
+    }
+}
+
+/// Todo lo que se calcula en una muestra de un píxel: la luz y los datos
+/// del primer choque del rayo. Si el rayo no chocó todo queda en cero.
+#[derive(Clone, Copy, Debug)]
+pub struct AovSample {
+    pub light: LightComponents,
+    pub albedo: SampledSpectrum,
+    pub normal: Normal,
+    pub depth: f64,
+    pub position: Point,
+}
+
+/// Muestra de un rayo que no chocó, sin luz.
+impl Default for AovSample {
+    fn default() -> Self {
+        AovSample {
+            light: LightComponents::default(),
+            albedo: SampledSpectrum::new(0.0),
+            normal: Normal::zeros(),
+            depth: 0.0,
+            position: Point::origin(),
+        }
+    }
+}
+
+impl AovSample {
+    /// Los datos del primer choque del rayo, todavía sin la luz. El choque
+    /// es el mismo que usa el integrador para calcular la luz, así el rayo
+    /// se traza una sola vez.
+    pub fn from_hit(choque: Option<&Intersection>, camera: &Camera) -> Self {
+        let mut muestra = AovSample::default();
+
+        if let Some(choque) = choque {
+            muestra.albedo = choque.material().albedo(choque.tex_coord());
+            muestra.normal = *choque.normal();
+            muestra.depth = camera.depth(choque.point());
+            muestra.position = *choque.point();
+        }
+
+        muestra
+    }
+
+    /// El valor de la muestra para una salida que no es la imagen final.
+    fn channel(&self, aov: Aov) -> (f32, f32, f32) {
+        let normal = |v: f64| v.mul_add(0.5, 0.5) as f32;
+
+        match aov {
+            Aov::Albedo => self.albedo.to_RGB(),
+            Aov::Normal if self.normal == Normal::zeros() => (0.0, 0.0, 0.0),
+            Aov::Normal => (
+                normal(self.normal.x),
+                normal(self.normal.y),
+                normal(self.normal.z),
+            ),
+            Aov::Depth => (self.depth as f32, 0.0, 0.0),
+            Aov::Position => (
+                self.position.x as f32, self.position.y as f32,
+                self.position.z as f32,
+            ),
+            Aov::Direct => self.light.direct.to_RGB(),
+            Aov::Indirect => self.light.indirect.to_RGB(),
+            Aov::Emission => self.light.emission.to_RGB(),
+            Aov::Beauty | Aov::SampleCount => (0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Guarda el color de cada píxel mientras se renderiza. Aparte del color
+/// que se calcula para cada píxel, acepta aportes que caen en cualquier
+/// lugar de la imagen (splats), como los de los caminos que salen de las
+/// luces y se conectan con la cámara. Las demás salidas se acumulan en
+/// canales RGB aparte, de a un tile por vez.
+pub struct Film {
+    width: usize,
+    height: usize,
+    aovs: Vec<Aov>,
+    pixels: Mutex<Vec<SampledSpectrum>>,
+    splats: Mutex<Vec<SampledSpectrum>>,
+    canales: Vec<Mutex<Vec<(f32, f32, f32)>>>, // uno por cada aov
+    cuentas: Mutex<Vec<usize>>,                // muestras de cada píxel
+}
+
+impl Film {
+    /// Película que solo guarda la imagen final.
+    #[cfg(test)]
+    pub fn new(width: usize, height: usize) -> Film {
+        Film::with_aovs(width, height, &[Aov::Beauty])
+    }
+
+    pub fn with_aovs(width: usize, height: usize, aovs: &[Aov]) -> Film {
+        let n = width * height;
+
+        Film {
+            width,
+            height,
+            aovs: aovs.to_vec(),
+            pixels: Mutex::new(vec![SampledSpectrum::new(0.0); n]),
+            splats: Mutex::new(vec![SampledSpectrum::new(0.0); n]),
+            canales: aovs
+                .iter()
+                .map(|_| Mutex::new(vec![(0.0, 0.0, 0.0); n]))
+                .collect(),
+            cuentas: Mutex::new(vec![0; n]),
+        }
+    }
+
+    /// Si alguna de las salidas necesita la luz.
+    pub fn needs_light(&self) -> bool {
+        self.aovs.iter().any(|aov| aov.needs_light())
+    }
+
+    /// Si hay salidas además de la imagen final.
+    pub fn has_extra_aovs(&self) -> bool {
+        self.aovs.iter().any(|aov| *aov != Aov::Beauty)
+    }
+
+    pub fn set_pixel(&self, (x, y): (usize, usize), color: SampledSpectrum) {
+        self.pixels.lock().unwrap()[y * self.width + x] = color;
+    }
+
+    /// Tile vacío con los píxeles de [x_0, x_1) x [y_0, y_1).
+    pub fn tile(
+        &self,
+        (x_0, y_0): (usize, usize),
+        (x_1, y_1): (usize, usize),
+    ) -> FilmTile<'_> {
+        let n = (x_1 - x_0) * (y_1 - y_0);
+
+        FilmTile {
+            aovs: &self.aovs,
+            origen: (x_0, y_0),
+            width: x_1 - x_0,
+            pixels: vec![SampledSpectrum::new(0.0); n],
+            canales: self
+                .aovs
+                .iter()
+                .map(|_| vec![(0.0, 0.0, 0.0); n])
+                .collect(),
+            cuentas: vec![0; n],
+        }
+    }
+
+    /// Suma lo que se acumuló en el tile a la película. Bloquea cada buffer
+    /// una sola vez por tile.
+    pub fn merge_tile(&self, tile: FilmTile) {
+        let (x_0, y_0) = tile.origen;
+        // índice en la película de cada píxel del tile, en orden
+        let índices = (0..tile.pixels.len()).map(|i| {
+            (y_0 + i / tile.width) * self.width + x_0 + i % tile.width
+        });
+
+        let mut pixels = self.pixels.lock().unwrap();
+        for (índice, color) in índices.clone().zip(&tile.pixels) {
+            pixels[índice] += *color;
+        }
+        drop(pixels);
+
+        if !self.has_extra_aovs() {
+            return;
+        }
+
+        for (canal, valores) in self.canales.iter().zip(&tile.canales) {
+            let mut canal = canal.lock().unwrap();
+            for (índice, (r, g, b)) in índices.clone().zip(valores) {
+                let (r_0, g_0, b_0) = canal[índice];
+                canal[índice] = (r_0 + r, g_0 + g, b_0 + b);
+            }
+        }
+
+        let mut cuentas = self.cuentas.lock().unwrap();
+        for (índice, n) in índices.zip(&tile.cuentas) {
+            cuentas[índice] += n;
+        }
+    }
+
+    /// Suma color al píxel que contiene la posición (i, j), en las mismas
+    /// coordenadas que usa Camera::get_ray. Si cae afuera no hace nada.
+    pub fn add_splat(&self, (i, j): (f64, f64), color: SampledSpectrum) {
+        // el píxel x abarca [x - 0.5, x + 0.5)
+        let (x, y) = ((i + 0.5).floor(), (j + 0.5).floor());
+        if x < 0.0 || y < 0.0 {
+            return;
+        }
+
+        let (x, y) = (x as usize, y as usize);
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let mut splats = self.splats.lock().unwrap();
+        splats[y * self.width + x] += color;
+    }
+
+    /// Suma de todos los splats de la película.
+    #[cfg(test)]
+    pub fn total_splats(&self) -> SampledSpectrum {
+        self.splats
+            .lock()
+            .unwrap()
+            .iter()
+            .fold(SampledSpectrum::new(0.0), |acc, s| acc + *s)
+    }
+
+    /// Arma una imagen por cada salida, en el orden en que se pidieron. Los
+    /// splats se suman a la imagen final multiplicados por splat_scale.
+    pub fn to_images(&self, splat_scale: f32) -> Vec<Image> {
+        self.aovs
+            .iter()
+            .zip(&self.canales)
+            .map(|(aov, canal)| match aov {
+                Aov::Beauty => self.to_image(splat_scale),
+                _ => self.channel_image(*aov, &canal.lock().unwrap()),
+            })
+            .collect()
+    }
+
+    /// Arma la imagen final. Los splats se suman a cada píxel multiplicados
+    /// por splat_scale.
+    pub fn to_image(&self, splat_scale: f32) -> Image {
+        let pixels = self.pixels.lock().unwrap();
+        let splats = self.splats.lock().unwrap();
+
+        self.image(|índice| {
+            (pixels[índice] + splats[índice] * splat_scale).to_RGB()
+        })
+    }
+
+    /// Imagen de una de las otras salidas, con el promedio de las muestras
+    /// de cada píxel.
+    fn channel_image(&self, aov: Aov, canal: &[(f32, f32, f32)]) -> Image {
+        let cuentas = self.cuentas.lock().unwrap();
+        let promedio = |índice: usize| {
+            let n = cuentas[índice].max(1) as f32;
+            let (r, g, b) = canal[índice];
+            (r / n, g / n, b / n)
+        };
+
+        // las que no son colores se dividen por el mayor valor, y quedan
+        // lineales como las demás
+        let escalar = |valores: Vec<f32>| {
+            let max = valores.iter().cloned().fold(0.0, f32::max);
+            let max = if max > 0.0 { max } else { 1.0 };
+            let gris = |v: f32| {
+                let v = v / max;
+                (v, v, v)
+            };
+
+            self.image(|índice| gris(valores[índice]))
+        };
+
+        match aov {
+            Aov::Depth => {
+                escalar((0..canal.len()).map(|i| promedio(i).0).collect())
+            }
+            Aov::SampleCount => {
+                escalar(cuentas.iter().map(|c| *c as f32).collect())
+            }
+            _ => self.image(promedio),
+        }
+    }
+
+    /// Arma una imagen con el color RGB lineal de cada píxel. Se recorta y
+    /// se le aplica la corrección gamma recién al guardarla.
+    fn image<F>(&self, color: F) -> Image
+    where
+        F: Fn(usize) -> (f32, f32, f32),
+    {
+        Image::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let (r, g, b) = color(y as usize * self.width + x as usize);
+            Rgb([r, g, b])
+        })
+    }
+}
+
+/// Un rectángulo de la película que renderiza un solo hilo. Acumula sus
+/// muestras sin bloquear nada, y se suma a la película con
+/// Film::merge_tile cuando termina.
+pub struct FilmTile<'f> {
+    aovs: &'f [Aov],
+    origen: (usize, usize),
+    width: usize,
+    pixels: Vec<SampledSpectrum>,
+    canales: Vec<Vec<(f32, f32, f32)>>, // uno por cada aov
+    cuentas: Vec<usize>,
+}
+
+impl FilmTile<'_> {
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        (y - self.origen.1) * self.width + x - self.origen.0
+    }
+
+    pub fn set_pixel(&mut self, pixel: (usize, usize), color: SampledSpectrum) {
+        let índice = self.index(pixel);
+        self.pixels[índice] = color;
+    }
+
+    /// Acumula una muestra del píxel en las salidas que no son la imagen
+    /// final.
+    pub fn add_sample(&mut self, pixel: (usize, usize), muestra: &AovSample) {
+        let índice = self.index(pixel);
+
+        for (aov, canal) in self.aovs.iter().zip(&mut self.canales) {
+            if *aov == Aov::Beauty || *aov == Aov::SampleCount {
+                continue;
+            }
+
+            let (r, g, b) = muestra.channel(*aov);
+            let (r_0, g_0, b_0) = canal[índice];
+            canal[índice] = (r_0 + r, g_0 + g, b_0 + b);
+        }
+
+        self.cuentas[índice] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splat_cae_en_el_píxel_correcto() {
+        let film = Film::new(4, 3);
+
+        film.add_splat((1.4, 0.6), SampledSpectrum::new(1.0));
+        film.add_splat((0.6, 1.4), SampledSpectrum::new(1.0));
+        // afuera de la imagen
+        film.add_splat((-0.6, 1.0), SampledSpectrum::new(1.0));
+        film.add_splat((3.5, 1.0), SampledSpectrum::new(1.0));
+
+        let splats = film.splats.lock().unwrap();
+        assert_eq!(splats[4 + 1].max_value(), 2.0);
+        assert_eq!(splats.iter().filter(|s| !s.is_black()).count(), 1);
+    }
+
+    #[test]
+    fn salidas_promedian_las_muestras() {
+        let film = Film::with_aovs(2, 1, &[Aov::Depth, Aov::SampleCount]);
+        assert!(!film.needs_light());
+
+        let muestra = |depth: f64| AovSample {
+            depth,
+            ..AovSample::default()
+        };
+
+        // el píxel de la izquierda promedia 1 y 3, el de la derecha tiene 4,
+        // y cada uno está en un tile distinto
+        let mut izquierda = film.tile((0, 0), (1, 1));
+        izquierda.add_sample((0, 0), &muestra(1.0));
+        izquierda.add_sample((0, 0), &muestra(3.0));
+        let mut derecha = film.tile((1, 0), (2, 1));
+        derecha.add_sample((1, 0), &muestra(4.0));
+        film.merge_tile(derecha);
+        film.merge_tile(izquierda);
+
+        let imágenes = film.to_images(1.0);
+        assert_eq!(imágenes.len(), 2);
+
+        // la profundidad se divide por la mayor, y las cuentas también, sin
+        // corrección gamma
+        assert_eq!(imágenes[0].get_pixel(0, 0).0, [0.5; 3]);
+        assert_eq!(imágenes[0].get_pixel(1, 0).0, [1.0; 3]);
+        assert_eq!(imágenes[1].get_pixel(0, 0).0, [1.0; 3]);
+        assert_eq!(imágenes[1].get_pixel(1, 0).0, [0.5; 3]);
+    }
+}
@@ -1,17 +1,27 @@
 use super::albedo::AlbedoIntegrator;
+use super::ambient_occlusion::AmbientOcclusionIntegrator;
 use super::auxiliar::{initialize_progress_bar, Image};
+use super::bdpt::BDPTIntegrator;
+use super::debug::DebugIntegrator;
+use super::direct_lighting::DirectLightingIntegrator;
+use super::film::{Aov, AovSample, Film, LightComponents};
+use super::mlt::MLTIntegrator;
 use super::normal::NormalIntegrator;
+use super::path::PathIntegrator;
+use super::photon_mapping::PhotonMapIntegrator;
 use super::random_walk::RandomWalkIntegrator;
+use super::sppm::SPPMIntegrator;
+use super::whitted::WhittedIntegrator;
 use crate::camera::Camera;
 use crate::geometry::Ray;
 use crate::parallel::ThreadPool;
 use crate::scene::Scene;
+use crate::shapes::Intersection;
 use crate::spectrum::SampledSpectrum;
 use enum_dispatch::enum_dispatch;
-use image::{ImageBuffer, Rgb};
 use itertools::Itertools;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 #[enum_dispatch]
 pub trait SamplerIntegrator: Sync {
@@ -27,14 +37,34 @@ pub trait SamplerIntegrator: Sync {
     /// Number of samples.
     fn total_samples(&self) -> usize;
 
-    fn render(&self) -> Result<Image, anyhow::Error> {
+    /// Renders the requested outputs in a single pass and returns one image
+    /// per output, in the same order.
+    fn render(&self, aovs: &[Aov]) -> Result<Vec<Image>, anyhow::Error> {
         // preprocess();
 
         let width = self.camera().width() as usize;
         let height = self.camera().height() as usize;
 
-        let img =
-            Arc::new(Mutex::new(vec![vec![Rgb([0, 0, 0]); height]; width]));
+        let film = Film::with_aovs(width, height, aovs);
+
+        self.render_tiles(&film, film.needs_light())?;
+
+        // cada muestra de cada píxel puede dejar splats en cualquier lado
+        Ok(film.to_images(1.0 / self.total_samples() as f32))
+    }
+
+    /// Takes every sample of every pixel, tile by tile, and stores them in
+    /// the film. If light is false the incident light isn't computed and
+    /// only the first hit outputs are filled.
+    fn render_tiles(
+        &self,
+        film: &Film,
+        light: bool,
+    ) -> Result<(), anyhow::Error> {
+        let width = self.camera().width() as usize;
+        let height = self.camera().height() as usize;
+
+        println!("Using {} threads.", num_cpus::get());
 
         let tile_size = 16;
 
@@ -52,7 +82,6 @@ pub trait SamplerIntegrator: Sync {
         let mut thread_pool = ThreadPool::new();
 
         for (tile_x, tile_y) in tiles {
-            let img_clone = img.clone();
             let contador_iter_clone = contador_iter.clone();
             let barrita_clone = barrita.clone();
 
@@ -64,32 +93,33 @@ pub trait SamplerIntegrator: Sync {
                     std::cmp::min(y_0 + tile_size, height),
                 );
 
+                let mut tile = film.tile((x_0, y_0), (x_1, y_1));
+
                 for (x, y) in (x_0..x_1).cartesian_product(y_0..y_1) {
                     // Integración de Monte Carlo
-                    let colores: Vec<SampledSpectrum> = (0..self
-                        .total_samples())
-                        .map(|sample_index| {
-                            self.evaluate_pixel_sample((x, y), sample_index)
-                        })
-                        .collect();
-
-                    let color = colores
-                        .iter()
-                        .fold(SampledSpectrum::new(0.0), |acc, x| acc + *x);
-                    let color = &color / self.total_samples() as f32;
-                    let (r, g, b) = color.to_RGB();
-                    let r = r.clamp(0.0, 1.0);
-                    let g = g.clamp(0.0, 1.0);
-                    let b = b.clamp(0.0, 1.0);
-
-                    // corrección gamma
-                    img_clone.lock().unwrap()[x][y] = Rgb([
-                        (256.0 * r.powf(1.0 / 2.2)) as u8,
-                        (256.0 * g.powf(1.0 / 2.2)) as u8,
-                        (256.0 * b.powf(1.0 / 2.2)) as u8,
-                    ]);
+                    let mut color = SampledSpectrum::new(0.0);
+                    for sample_index in 0..self.total_samples() {
+                        let muestra = self.evaluate_pixel_sample(
+                            (x, y),
+                            sample_index,
+                            film,
+                            light,
+                        );
+
+                        color += muestra.light.total;
+                        tile.add_sample((x, y), &muestra);
+                    }
+
+                    if light {
+                        tile.set_pixel(
+                            (x, y),
+                            color / self.total_samples() as f32,
+                        );
+                    }
                 }
 
+                film.merge_tile(tile);
+
                 contador_iter_clone.fetch_add(1, Ordering::SeqCst);
                 barrita_clone.inc(1);
             });
@@ -103,26 +133,15 @@ pub trait SamplerIntegrator: Sync {
 
             std::thread::sleep(std::time::Duration::from_millis(60));
         });
-
-        let mut buffer_img =
-            ImageBuffer::new(self.camera().width(), self.camera().height());
-
-        for (x, y, pixel) in buffer_img.enumerate_pixels_mut() {
-            *pixel = img.lock().unwrap()[x as usize][y as usize];
-        }
+        drop(thread_pool);
 
         barrita.finish_with_message("Finalizado.");
 
-        // save image
-        Ok(buffer_img)
+        Ok(())
     }
 
-    /// Takes a sample at the given pixel and returns the sampled color.
-    fn evaluate_pixel_sample(
-        &self,
-        (x, y): (usize, usize),
-        _sample_index: usize,
-    ) -> SampledSpectrum {
+    /// Generates the camera ray for a sample of the given pixel.
+    fn pixel_ray(&self, (x, y): (usize, usize), _sample_index: usize) -> Ray {
         // todo: cuando cambie el SampledSpectrum por SampledWavelengths tengo
         // todo: que generar acá las longitudes de onda muestreadas.
 
@@ -131,21 +150,81 @@ pub trait SamplerIntegrator: Sync {
             |val: usize| val as f64 + rand::random::<f64>() - 0.5;
         let (v_1, v_2): (f64, f64) = (random_sample(x), random_sample(y));
 
+        self.camera().get_ray(v_1, v_2)
+    }
+
+    /// Takes a sample at the given pixel and returns what it saw: the first
+    /// hit data and, if light is true, the incident light. Contributions to
+    /// other pixels can be splatted onto the film.
+    fn evaluate_pixel_sample(
+        &self,
+        pixel: (usize, usize),
+        sample_index: usize,
+        film: &Film,
+        light: bool,
+    ) -> AovSample {
         // generate ray for current sample
-        let ray = self.camera().get_ray(v_1, v_2);
+        let ray = self.pixel_ray(pixel, sample_index);
+
+        // the first hit is shared by the outputs and the light
+        let choque = self.scene().intersect_ray(&ray);
+        let mut muestra = if film.has_extra_aovs() {
+            AovSample::from_hit(choque.as_ref(), self.camera())
+        } else {
+            AovSample::default()
+        };
+
+        // ray's contribution to pixel
+        if light {
+            muestra.light = self.ray_light(&ray, choque, film);
+        }
+
+        muestra
+    }
 
-        // return ray's contribution to pixel
-        self.incident_light(&ray, self.max_depth())
+    /// Returns the light coming from the ray, whose first hit is choque,
+    /// split by the number of bounces if the integrator can tell them apart.
+    fn ray_light<'a>(
+        &'a self,
+        ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        _film: &Film,
+    ) -> LightComponents {
+        LightComponents::from_total(self.hit_light(
+            ray,
+            choque,
+            self.max_depth(),
+        ))
     }
 
     ///Returns the incident light coming from the ray.
-    fn incident_light(&self, ray: &Ray, depth: usize) -> SampledSpectrum;
+    fn incident_light(&self, ray: &Ray, depth: usize) -> SampledSpectrum {
+        self.hit_light(ray, self.scene().intersect_ray(ray), depth)
+    }
+
+    /// Returns the incident light coming from the ray, whose first hit with
+    /// the scene was already found (None if it doesn't hit anything).
+    fn hit_light<'a>(
+        &'a self,
+        ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum;
 }
 
 #[enum_dispatch(SamplerIntegrator)]
 #[derive(Clone, Debug)]
 pub enum Integrator {
     RandomWalkIntegrator,
+    PathIntegrator,
+    DirectLightingIntegrator,
+    WhittedIntegrator,
+    BDPTIntegrator,
+    PhotonMapIntegrator,
+    SPPMIntegrator,
+    MLTIntegrator,
     AlbedoIntegrator,
     NormalIntegrator,
+    AmbientOcclusionIntegrator,
+    DebugIntegrator,
 }
@@ -0,0 +1,418 @@
+use super::auxiliar::{initialize_progress_bar, Image};
+use super::film::{Aov, Film};
+use super::integrator::SamplerIntegrator;
+use super::path::PathIntegrator;
+use crate::camera::Camera;
+use crate::geometry::Ray;
+use crate::parallel::ThreadPool;
+use crate::sampling::{Distribution1D, Sampler};
+use crate::scene::Scene;
+use crate::shapes::Intersection;
+use crate::spectrum::SampledSpectrum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Número de [0, 1) en el espacio de muestras primarias, con lo necesario
+/// para deshacer la última mutación.
+#[derive(Clone, Copy, Debug, Default)]
+struct PrimarySample {
+    value: f64,
+    last_modification: usize,
+    value_backup: f64,
+    modify_backup: usize,
+}
+
+impl PrimarySample {
+    fn backup(&mut self) {
+        self.value_backup = self.value;
+        self.modify_backup = self.last_modification;
+    }
+
+    fn restore(&mut self) {
+        self.value = self.value_backup;
+        self.last_modification = self.modify_backup;
+    }
+}
+
+/// Sampler de Kelemen: guarda los números que usó el camino actual y en
+/// cada iteración los muta, con un paso grande (números nuevos) o con uno
+/// chico (una perturbación normal de cada número). Los números se mutan
+/// recién cuando el integrador los pide, así que los caminos pueden usar
+/// una cantidad variable.
+#[derive(Clone, Debug)]
+pub struct MLTSampler {
+    rng: StdRng,
+    sigma: f64,
+    large_step_probability: f64,
+    x: Vec<PrimarySample>,
+    current_iteration: usize,
+    large_step: bool,
+    last_large_step_iteration: usize,
+    índice: usize,
+}
+
+impl MLTSampler {
+    /// Con la misma semilla se generan los mismos números, así se puede
+    /// repetir un camino del bootstrap.
+    pub fn new(seed: u64, sigma: f64, large_step_probability: f64) -> Self {
+        MLTSampler {
+            rng: StdRng::seed_from_u64(seed),
+            sigma,
+            large_step_probability,
+            x: Vec::new(),
+            current_iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            índice: 0,
+        }
+    }
+
+    /// Empieza una mutación.
+    pub fn start_iteration(&mut self) {
+        self.current_iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < self.large_step_probability;
+        self.índice = 0;
+    }
+
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.current_iteration;
+        }
+    }
+
+    /// Vuelve a los números de antes de la mutación.
+    pub fn reject(&mut self) {
+        for xi in self.x.iter_mut() {
+            if xi.last_modification == self.current_iteration {
+                xi.restore();
+            }
+        }
+        self.current_iteration -= 1;
+    }
+
+    /// Pone al día el número i con todas las mutaciones que se salteó desde
+    /// la última vez que se usó.
+    fn ensure_ready(&mut self, i: usize) {
+        if i >= self.x.len() {
+            self.x.resize(i + 1, PrimarySample::default());
+        }
+        let xi = &mut self.x[i];
+
+        // si hubo un paso grande aceptado después, el número es nuevo
+        if xi.last_modification < self.last_large_step_iteration {
+            xi.value = self.rng.gen();
+            xi.last_modification = self.last_large_step_iteration;
+        }
+
+        xi.backup();
+        if self.large_step {
+            xi.value = self.rng.gen();
+        } else {
+            // varios pasos chicos juntos suman una normal con más varianza
+            let pasos = (self.current_iteration - xi.last_modification) as f64;
+            let (u_1, u_2): (f64, f64) = (self.rng.gen(), self.rng.gen());
+            let normal =
+                (-2.0 * (1.0 - u_1).ln()).sqrt() * (2.0 * PI * u_2).cos();
+
+            let valor = xi.value + normal * self.sigma * pasos.sqrt();
+            xi.value = (valor - valor.floor()).min(1.0 - f64::EPSILON);
+        }
+        xi.last_modification = self.current_iteration;
+    }
+}
+
+impl Sampler for MLTSampler {
+    fn get_1d(&mut self) -> f64 {
+        let i = self.índice;
+        self.índice += 1;
+        self.ensure_ready(i);
+
+        self.x[i].value
+    }
+}
+
+/// Parámetros del MLT. Se generan bootstrap_samples caminos para estimar el
+/// brillo total de la imagen y elegir dónde empiezan las chains cadenas de
+/// Markov. En cada mutación se hace un paso grande con probabilidad
+/// large_step_probability, y si no uno chico de desvío sigma.
+#[derive(Clone, Copy, Debug)]
+pub struct MLTSettings {
+    pub bootstrap_samples: usize,
+    pub chains: usize,
+    pub large_step_probability: f64,
+    pub sigma: f64,
+}
+
+impl Default for MLTSettings {
+    fn default() -> Self {
+        MLTSettings {
+            bootstrap_samples: 100_000,
+            chains: 1000,
+            large_step_probability: 0.3,
+            sigma: 0.01,
+        }
+    }
+}
+
+/// Metropolis light transport en el espacio de muestras primarias (Kelemen
+/// et al.). Cada muestra es el vector de números que usa el path tracer para
+/// elegir el píxel y el camino, y se recorre con cadenas de Markov que
+/// visitan cada camino con probabilidad proporcional a su luminancia. Así se
+/// quedan explorando los caminos difíciles de encontrar una vez que dan con
+/// uno.
+#[derive(Clone, Debug)]
+pub struct MLTIntegrator {
+    path: PathIntegrator,
+    settings: MLTSettings,
+    max_depth: usize,
+    mutations_per_pixel: usize,
+}
+
+impl MLTIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        settings: MLTSettings,
+        max_depth: usize,
+        mutations_per_pixel: usize,
+    ) -> Self {
+        Self {
+            path: PathIntegrator::new(camera, scene, max_depth, 1),
+            settings,
+            max_depth,
+            mutations_per_pixel,
+        }
+    }
+
+    /// Genera el camino que corresponde a los números del sampler. Devuelve
+    /// el punto de la pantalla, en las coordenadas de Camera::get_ray, y la
+    /// luz que llega por él.
+    fn l<S: Sampler>(&self, sampler: &mut S) -> ((f64, f64), SampledSpectrum) {
+        let camera = self.camera();
+        let (u, v) = sampler.get_2d();
+        let raster = (
+            u * camera.width() as f64 - 0.5,
+            v * camera.height() as f64 - 0.5,
+        );
+
+        let ray = camera.get_ray(raster.0, raster.1);
+
+        (raster, self.path.li(&ray, self.max_depth, sampler))
+    }
+
+    fn sampler(&self, seed: usize) -> MLTSampler {
+        MLTSampler::new(
+            seed as u64,
+            self.settings.sigma,
+            self.settings.large_step_probability,
+        )
+    }
+
+    /// Luminancia de los caminos del bootstrap, cada uno generado con un
+    /// sampler con su índice como semilla.
+    fn bootstrap(&self) -> Vec<f64> {
+        let n = self.settings.bootstrap_samples;
+        let mut pesos = vec![0.0; n];
+        let tamaño = n.div_ceil(num_cpus::get()).max(1);
+
+        let mut thread_pool = ThreadPool::new();
+        for (bloque, pesos) in pesos.chunks_mut(tamaño).enumerate() {
+            thread_pool.add_task(move || {
+                for (k, peso) in pesos.iter_mut().enumerate() {
+                    let mut sampler = self.sampler(bloque * tamaño + k);
+                    *peso = self.l(&mut sampler).1.y().max(0.0) as f64;
+                }
+            });
+        }
+        thread_pool.run(|| {});
+        drop(thread_pool);
+
+        pesos
+    }
+
+    /// Corre una cadena de Markov que empieza en el camino del bootstrap
+    /// elegido, dejando cada muestra en la película pesada por su
+    /// luminancia.
+    fn run_chain(&self, semilla: usize, mutaciones: usize, film: &Film) {
+        let mut sampler = self.sampler(semilla);
+        let (mut raster_actual, mut l_actual) = self.l(&mut sampler);
+        let mut y_actual = l_actual.y() as f64;
+
+        for _ in 0..mutaciones {
+            sampler.start_iteration();
+            let (raster_nuevo, l_nuevo) = self.l(&mut sampler);
+            let y_nuevo = l_nuevo.y() as f64;
+
+            let aceptación = if y_actual > 0.0 {
+                (y_nuevo / y_actual).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+
+            // se suman las dos muestras pesadas por la probabilidad de
+            // quedarse en cada una, así no se pierde el camino rechazado
+            if aceptación > 0.0 && y_nuevo > 0.0 {
+                film.add_splat(
+                    raster_nuevo,
+                    l_nuevo * (aceptación / y_nuevo) as f32,
+                );
+            }
+            if aceptación < 1.0 && y_actual > 0.0 {
+                film.add_splat(
+                    raster_actual,
+                    l_actual * ((1.0 - aceptación) / y_actual) as f32,
+                );
+            }
+
+            if rand::random::<f64>() < aceptación {
+                raster_actual = raster_nuevo;
+                l_actual = l_nuevo;
+                y_actual = y_nuevo;
+                sampler.accept();
+            } else {
+                sampler.reject();
+            }
+        }
+    }
+
+    /// Corre las cadenas de Markov dejando splats en la película. Devuelve
+    /// el brillo promedio de la imagen, que es la escala de los splats.
+    fn metropolis(&self, film: &Film) -> Result<f64, anyhow::Error> {
+        let width = self.camera().width() as usize;
+        let height = self.camera().height() as usize;
+        println!("Using {} threads.", num_cpus::get());
+
+        // el brillo promedio de la imagen, que se pierde al muestrear según
+        // la luminancia
+        let pesos = self.bootstrap();
+        let b = pesos.iter().sum::<f64>() / pesos.len().max(1) as f64;
+        if b == 0.0 {
+            return Ok(0.0);
+        }
+        let distribución = Distribution1D::new(&pesos);
+
+        let chains = self.settings.chains.max(1);
+        let total = self.mutations_per_pixel * width * height;
+
+        let barrita = initialize_progress_bar(chains as u64)?;
+        let terminadas = AtomicUsize::new(0);
+
+        let mut thread_pool = ThreadPool::new();
+        for chain in 0..chains {
+            let distribución = &distribución;
+            let (barrita, terminadas) = (&barrita, &terminadas);
+
+            thread_pool.add_task(move || {
+                let mutaciones =
+                    total / chains + usize::from(chain < total % chains);
+                let (semilla, _, _) =
+                    distribución.sample_discrete(rand::random());
+
+                self.run_chain(semilla, mutaciones, film);
+
+                terminadas.fetch_add(1, Ordering::SeqCst);
+                barrita.inc(1);
+            });
+        }
+
+        thread_pool.run(|| {
+            while terminadas.load(Ordering::SeqCst) < chains {
+                std::thread::sleep(std::time::Duration::from_millis(60));
+            }
+        });
+        drop(thread_pool);
+
+        barrita.finish_with_message("Finalizado.");
+
+        Ok(b)
+    }
+}
+
+impl SamplerIntegrator for MLTIntegrator {
+    fn camera(&self) -> &Camera {
+        self.path.camera()
+    }
+
+    fn scene(&self) -> &Scene {
+        self.path.scene()
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn total_samples(&self) -> usize {
+        self.mutations_per_pixel
+    }
+
+    /// La imagen final sale de las cadenas de Markov. Las demás salidas se
+    /// calculan aparte con rayos primarios, y en ellas la luz queda en negro.
+    fn render(&self, aovs: &[Aov]) -> Result<Vec<Image>, anyhow::Error> {
+        let width = self.camera().width() as usize;
+        let height = self.camera().height() as usize;
+
+        let film = Film::with_aovs(width, height, aovs);
+
+        let b = if aovs.contains(&Aov::Beauty) {
+            self.metropolis(&film)?
+        } else {
+            0.0
+        };
+
+        if film.has_extra_aovs() {
+            self.render_tiles(&film, false)?;
+        }
+
+        // cada mutación deja en total un peso de 1 / b
+        Ok(film.to_images((b / self.mutations_per_pixel as f64) as f32))
+    }
+
+    /// Una muestra del path tracer, para cuando se pide la luz de un solo
+    /// rayo.
+    fn hit_light<'a>(
+        &'a self,
+        ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum {
+        self.path.hit_light(ray, choque, depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rechazar_restaura_los_números() {
+        let mut sampler = MLTSampler::new(7, 0.01, 0.0);
+        let originales: Vec<f64> = (0..5).map(|_| sampler.get_1d()).collect();
+
+        // un paso chico mueve un poco cada número
+        sampler.start_iteration();
+        let mutados: Vec<f64> = (0..5).map(|_| sampler.get_1d()).collect();
+        for (a, b) in originales.iter().zip(&mutados) {
+            assert_ne!(a, b);
+            let distancia = (a - b).abs().min(1.0 - (a - b).abs());
+            assert!(distancia < 0.1);
+        }
+
+        sampler.reject();
+        assert_eq!(
+            sampler.x.iter().map(|x| x.value).collect::<Vec<_>>(),
+            originales
+        );
+    }
+
+    #[test]
+    fn misma_semilla_repite_el_camino() {
+        let mut a = MLTSampler::new(3, 0.01, 0.3);
+        let mut b = MLTSampler::new(3, 0.01, 0.3);
+
+        for _ in 0..10 {
+            assert_eq!(a.get_1d(), b.get_1d());
+        }
+    }
+}
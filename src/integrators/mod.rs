@@ -1,11 +1,32 @@
 mod albedo;
+mod ambient_occlusion;
 mod auxiliar;
+mod bdpt;
+mod debug;
+mod direct_lighting;
+mod film;
 mod integrator;
+mod mlt;
 mod normal;
+mod path;
+mod photon_mapping;
 mod random_walk;
+mod sppm;
+mod whitted;
 
+pub use auxiliar::save_image;
+pub use film::Aov;
 pub use integrator::{Integrator, SamplerIntegrator};
 
 pub use albedo::AlbedoIntegrator;
+pub use ambient_occlusion::AmbientOcclusionIntegrator;
+pub use bdpt::BDPTIntegrator;
+pub use debug::{DebugChannel, DebugIntegrator};
+pub use direct_lighting::{DirectLightingIntegrator, LightStrategy};
+pub use mlt::{MLTIntegrator, MLTSettings};
 pub use normal::NormalIntegrator;
+pub use path::PathIntegrator;
+pub use photon_mapping::{PhotonMapIntegrator, PhotonMapSettings};
 pub use random_walk::RandomWalkIntegrator;
+pub use sppm::SPPMIntegrator;
+pub use whitted::WhittedIntegrator;
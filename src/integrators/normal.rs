@@ -2,6 +2,7 @@ use super::integrator::SamplerIntegrator;
 use crate::camera::Camera;
 use crate::geometry::Ray;
 use crate::scene::Scene;
+use crate::shapes::Intersection;
 use crate::spectrum::{SampledSpectrum, SpectrumType};
 
 #[derive(Clone, Debug)]
@@ -42,8 +43,13 @@ impl SamplerIntegrator for NormalIntegrator {
         self.iterations
     }
 
-    fn incident_light(&self, ray: &Ray, _depth: usize) -> SampledSpectrum {
-        match self.scene.intersect_ray(ray) {
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        _depth: usize,
+    ) -> SampledSpectrum {
+        match choque {
             Some(intersection) => {
                 let normalize_normal = |v: f64| v.mul_add(0.5, 0.5) as f32;
 
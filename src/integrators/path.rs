@@ -0,0 +1,424 @@
+use super::film::{Film, LightComponents};
+use super::integrator::SamplerIntegrator;
+use crate::bsdf::Bsdf;
+use crate::camera::Camera;
+use crate::geometry::{Normal, Ray, Vector};
+use crate::medium::Medium;
+use crate::sampling::{
+    power_heuristic, uniform_sample_sphere, RandomSampler, Sampler,
+};
+use crate::scene::Scene;
+use crate::shapes::Intersection;
+use crate::spectrum::SampledSpectrum;
+
+/// Cantidad de rebotes a partir de la cual se empieza a usar la ruleta rusa.
+const RUSSIAN_ROULETTE_DEPTH: usize = 3;
+
+/// Cantidad máxima de choques con el medio adentro de un objeto Subsurface
+/// antes de volver a la superficie. No cuentan como rebotes del camino.
+const MAX_SCATTERING: usize = 1024;
+
+/// Path tracer con next event estimation: en cada vértice del camino se
+/// muestrea directamente una fuente de luz, y se combina con el muestreo del
+/// material usando multiple importance sampling. Los caminos se terminan con
+/// ruleta rusa, max_depth es solo un límite por las dudas.
+///
+/// Adentro de los objetos Subsurface la luz hace un camino al azar por el
+/// medio hasta que vuelve a la superficie.
+#[derive(Clone, Debug)]
+pub struct PathIntegrator {
+    camera: Camera,
+    scene: Scene,
+    max_depth: usize,
+    iterations: usize,
+}
+
+impl PathIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        max_depth: usize,
+        iterations: usize,
+    ) -> Self {
+        Self {
+            camera: *camera,
+            scene: scene.clone(),
+            max_depth,
+            iterations,
+        }
+    }
+
+    /// Luz directa que llega al punto del choque desde una fuente de luz
+    /// elegida al azar, y que se refleja hacia wo. El peso de MIS tiene en
+    /// cuenta que la misma luz se podría haber encontrado muestreando el
+    /// material.
+    fn estimate_direct<S: Sampler>(
+        &self,
+        choque: &Intersection,
+        bsdf: &Bsdf,
+        normal: &Normal,
+        wo: &Vector,
+        sampler: &mut S,
+    ) -> SampledSpectrum {
+        let black = SampledSpectrum::new(0.0);
+
+        let muestra = match self.scene.sample_light(
+            choque.point(),
+            sampler.get_1d(),
+            sampler.get_2d(),
+        ) {
+            Some(m) if m.pdf > 0.0 => m,
+            _ => return black,
+        };
+
+        let f = bsdf.f(wo, &muestra.wi) * muestra.wi.dot(normal).abs() as f32;
+        if f.is_black() {
+            return black;
+        }
+
+        if !self
+            .scene
+            .unoccluded(&choque.spawn_point(&muestra.wi), &muestra.punto)
+        {
+            return black;
+        }
+
+        let bsdf_pdf = bsdf.pdf(wo, &muestra.wi);
+        let peso = power_heuristic(1, muestra.pdf, 1, bsdf_pdf);
+
+        f * muestra.emitted * (peso / muestra.pdf) as f32
+    }
+
+    /// Luz que llega por el rayo, tomando del sampler todos los números
+    /// aleatorios del camino.
+    pub fn li<S: Sampler>(
+        &self,
+        ray: &Ray,
+        depth: usize,
+        sampler: &mut S,
+    ) -> SampledSpectrum {
+        self.li_components(ray, self.scene.intersect_ray(ray), depth, sampler)
+            .total
+    }
+
+    /// Igual que li, pero separa la luz según la cantidad de rebotes del
+    /// camino que la trajo, y recibe el primer choque del rayo ya buscado.
+    fn li_components<'a, S: Sampler>(
+        &'a self,
+        ray: &Ray,
+        mut primero: Option<Intersection<'a>>,
+        depth: usize,
+        sampler: &mut S,
+    ) -> LightComponents {
+        let mut light = LightComponents::default();
+        // throughput del camino hasta el vértice actual
+        let mut beta = SampledSpectrum::new(1.0);
+        let mut ray = *ray;
+
+        // datos del rebote anterior, para pesar la luz emitida con MIS
+        let mut specular_bounce = false;
+        let mut bsdf_pdf = 0.0;
+        let mut previous_point = *ray.origin();
+        // el medio del objeto en el que está el rayo, si tiene
+        let mut medio: Option<Medium> = None;
+
+        'camino: for bounces in 0.. {
+            let choque = if bounces == 0 {
+                primero.take()
+            } else {
+                self.scene.intersect_ray(&ray)
+            };
+            let mut choque = match choque {
+                Some(isect) => isect,
+                None => break,
+            };
+
+            // adentro de un medio, la luz choca con partículas y cambia de
+            // dirección hasta que llega otra vez a la superficie
+            if let Some(medio) = medio {
+                for pasos in 0.. {
+                    let distancia = (choque.point() - ray.origin()).norm();
+                    let (t, peso) = medio.sample(
+                        distancia,
+                        sampler.get_1d(),
+                        sampler.get_1d(),
+                    );
+                    beta = beta * peso;
+
+                    let t = match t {
+                        Some(t) => t,
+                        None => break,
+                    };
+                    if pasos >= MAX_SCATTERING || beta.is_black() {
+                        break 'camino;
+                    }
+
+                    // se dispersa para cualquier lado por igual, así que la
+                    // función de fase se cancela con su densidad
+                    let punto = ray.origin() + ray.dir() * t;
+                    let dir = uniform_sample_sphere(sampler.get_2d());
+                    ray = Ray::new(&punto, &dir, f64::INFINITY);
+                    choque = match self.scene.intersect_ray(&ray) {
+                        Some(isect) => isect,
+                        None => break 'camino,
+                    };
+
+                    let q = (1.0 - beta.max_value()).max(0.0);
+                    if sampler.get_1d() < q as f64 {
+                        break 'camino;
+                    }
+                    beta = beta / (1.0 - q);
+                }
+            }
+            let material = choque.material();
+
+            // Si choqué un objeto emisivo, sumo su luz. Si vengo de un
+            // rebote difuso esta luz también se pudo muestrear directamente
+            if let Some(emitted) = material.emitted() {
+                if bounces == 0 || specular_bounce {
+                    light.add(bounces, beta * emitted);
+                } else {
+                    let light_pdf =
+                        self.scene.light_pdf(&previous_point, &choque);
+                    let peso = power_heuristic(1, bsdf_pdf, 1, light_pdf);
+                    light.add(bounces, beta * emitted * peso as f32);
+                }
+            }
+
+            if bounces >= depth {
+                break;
+            }
+
+            let wo = *choque.direction_out();
+            let normal = choque.facing_normal();
+            let bsdf = choque.bsdf();
+
+            // la luz directa llega después de un rebote más
+            if !bsdf.is_specular() {
+                let directa =
+                    self.estimate_direct(&choque, &bsdf, &normal, &wo, sampler);
+                light.add(bounces + 1, beta * directa);
+            }
+
+            // sigo el camino muestreando el material
+            let muestra = match bsdf.sample_f(&wo, sampler.get_2d()) {
+                Some(m) if m.pdf > 0.0 && !m.f.is_black() => m,
+                _ => break,
+            };
+
+            let cos = muestra.wi.dot(&normal).abs();
+            beta = beta * muestra.f * (cos / muestra.pdf) as f32;
+
+            specular_bounce = muestra.specular;
+            bsdf_pdf = muestra.pdf;
+            previous_point = *choque.point();
+            ray = choque.spawn_ray(&muestra.wi);
+
+            // si entra al objeto, o se refleja desde adentro, queda en su
+            // medio
+            medio = material
+                .medium()
+                .filter(|_| muestra.wi.dot(choque.normal()) < 0.0);
+
+            // Ruleta rusa: corto el camino con probabilidad q, y si sigue lo
+            // divido por 1 - q para que el estimador no tenga sesgo
+            if bounces >= RUSSIAN_ROULETTE_DEPTH {
+                let q = (1.0 - beta.max_value()).max(0.05);
+                if sampler.get_1d() < q as f64 {
+                    break;
+                }
+                beta = beta / (1.0 - q);
+            }
+        }
+
+        light
+    }
+}
+
+impl SamplerIntegrator for PathIntegrator {
+    fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn total_samples(&self) -> usize {
+        self.iterations
+    }
+
+    fn ray_light<'a>(
+        &'a self,
+        ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        _film: &Film,
+    ) -> LightComponents {
+        self.li_components(ray, choque, self.max_depth, &mut RandomSampler)
+    }
+
+    fn hit_light<'a>(
+        &'a self,
+        ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum {
+        self.li_components(ray, choque, depth, &mut RandomSampler)
+            .total
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::geometry::{create_translation, Point};
+    use crate::material::{Material, Type};
+    use crate::medium::Medium;
+    use crate::shapes::Sphere;
+
+    /// Una esfera de radio 1 en (3, 0, 0), adentro de una esfera de radio 10
+    /// que emite radiancia 1 para todos lados. La esfera de adentro recibe
+    /// la misma luz desde todas las direcciones.
+    pub fn esfera_dentro_de_una_luz(material: &Material) -> Scene {
+        let mut scene = Scene::new();
+
+        let luz = Material {
+            tipo: Type::Emitter,
+            emitted_color: Some(SampledSpectrum::new(1.0)),
+            ..Default::default()
+        };
+
+        let afuera = Sphere::new(
+            &create_translation(&Vector::new(0.0, 0.0, 0.0)),
+            10.0,
+            &luz,
+        );
+        let adentro = Sphere::new(
+            &create_translation(&Vector::new(3.0, 0.0, 0.0)),
+            1.0,
+            material,
+        );
+        scene.add_shape(&afuera.into()).unwrap();
+        scene.add_shape(&adentro.into()).unwrap();
+        scene.build_bvh();
+
+        scene
+    }
+
+    /// Difuso con albedo 0.5, que adentro de la luz refleja 0.5 hacia
+    /// cualquier lado.
+    pub fn difusa() -> Material {
+        Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.5)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn esfera_difusa_dentro_de_una_luz() {
+        // Una esfera difusa con albedo 0.5 adentro de una esfera que emite
+        // radiancia 1 para todos lados recibe la misma luz desde todas las
+        // direcciones, así que refleja 0.5 hacia cualquier lado.
+        let scene = esfera_dentro_de_una_luz(&difusa());
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let integrator = PathIntegrator::new(&camera, &scene, 10, 1);
+
+        let ray = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        let n = 5000;
+        let total = (0..n)
+            .map(|_| integrator.incident_light(&ray, 10).max_value() as f64)
+            .sum::<f64>();
+
+        assert!((total / n as f64 - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn subsurface_conserva_la_energía() {
+        // Igual que con la esfera difusa, pero la luz entra a la esfera y
+        // rebota adentro. Si el medio no absorbe, sale toda. Los caminos
+        // pueden chocar muchas veces con la superficie desde adentro antes
+        // de salir, así que no los corto pronto
+        let cera = Material {
+            tipo: Type::Subsurface,
+            medium: Some(Medium::new(
+                SampledSpectrum::new(0.5),
+                SampledSpectrum::new(1.0),
+            )),
+            ..Default::default()
+        };
+        let scene = esfera_dentro_de_una_luz(&cera);
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let integrator = PathIntegrator::new(&camera, &scene, 100, 1);
+
+        let ray = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        let n = 50_000;
+        let total = (0..n)
+            .map(|_| integrator.incident_light(&ray, 1000).max_value() as f64)
+            .sum::<f64>();
+
+        assert!((total / n as f64 - 1.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn componentes_suman_el_total() {
+        // Se ve la luz directamente, así que solo hay emisión
+        let scene = esfera_dentro_de_una_luz(&difusa());
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let integrator = PathIntegrator::new(&camera, &scene, 10, 1);
+        let film = Film::new(1, 1);
+
+        let a_la_luz = Ray::new(
+            &Point::origin(),
+            &Vector::new(-1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+        let luz = integrator.ray_light(
+            &a_la_luz,
+            scene.intersect_ray(&a_la_luz),
+            &film,
+        );
+        assert_eq!(luz.emission.max_value(), 1.0);
+        assert!(luz.direct.is_black() && luz.indirect.is_black());
+
+        // Mirando la esfera difusa no hay emisión, y la luz directa y la
+        // indirecta suman el total
+        let a_la_esfera = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+        for _ in 0..100 {
+            let luz = integrator.ray_light(
+                &a_la_esfera,
+                scene.intersect_ray(&a_la_esfera),
+                &film,
+            );
+            let suma = luz.direct + luz.indirect;
+
+            assert!(luz.emission.is_black());
+            assert!(!luz.direct.is_black());
+            assert!((suma.max_value() - luz.total.max_value()).abs() < 1e-5);
+        }
+    }
+}
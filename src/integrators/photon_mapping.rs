@@ -0,0 +1,495 @@
+use super::integrator::SamplerIntegrator;
+use crate::accelerators::KdTree;
+use crate::camera::Camera;
+use crate::geometry::{Normal, Ray, Vector};
+use crate::sampling::Distribution1D;
+use crate::scene::Scene;
+use crate::shapes::Intersection;
+use crate::spectrum::SampledSpectrum;
+use std::f64::consts::PI;
+
+/// Fotón que quedó en una superficie difusa. wi apunta hacia donde vino.
+#[derive(Clone, Copy, Debug)]
+struct Photon {
+    wi: Vector,
+    power: SampledSpectrum,
+}
+
+/// Traza un fotón desde una luz elegida con la distribución, rebotando en
+/// los materiales y cortando con ruleta rusa. En cada choque con una
+/// superficie que no es especular llama a deposit con el choque, la
+/// dirección de la que vino el fotón, su potencia, la cantidad de rebotes
+/// que dio antes y si todos esos rebotes fueron especulares.
+pub(super) fn trace_photon<'s, F>(
+    scene: &'s Scene,
+    distribución: &Distribution1D,
+    max_depth: usize,
+    mut deposit: F,
+) where
+    F: FnMut(&Intersection<'s>, &Vector, &SampledSpectrum, usize, bool),
+{
+    if scene.light_count() == 0 {
+        return;
+    }
+
+    let (luz, pdf_luz, _) = distribución.sample_discrete(rand::random());
+    let muestra = match scene.sample_emission(
+        luz,
+        rand::random(),
+        rand::random(),
+        rand::random(),
+    ) {
+        Some(m) if pdf_luz > 0.0 => m,
+        _ => return,
+    };
+
+    let cos = muestra.rayo.dir().dot(&muestra.normal).abs();
+    let mut beta = muestra.emitted
+        * (cos / (pdf_luz * muestra.pdf_pos * muestra.pdf_dir)) as f32;
+    let mut ray = muestra.rayo;
+    let mut solo_especulares = true;
+
+    for rebotes in 0..max_depth {
+        let choque = match scene.intersect_ray(&ray) {
+            Some(isect) => isect,
+            None => break,
+        };
+        let wo = *choque.direction_out();
+        let normal = choque.facing_normal();
+        let bsdf = choque.bsdf();
+
+        if !bsdf.is_specular() && choque.material().emitted().is_none() {
+            deposit(&choque, &wo, &beta, rebotes, solo_especulares);
+        }
+
+        let muestra = match bsdf.sample_f(&wo, rand::random()) {
+            Some(m) if m.pdf > 0.0 && !m.f.is_black() => m,
+            _ => break,
+        };
+
+        let cos = muestra.wi.dot(&normal).abs();
+        let nuevo_beta = beta * muestra.f * (cos / muestra.pdf) as f32;
+
+        // ruleta rusa según cuánto se atenuó el fotón en este rebote
+        let q = (1.0 - nuevo_beta.max_value() / beta.max_value()).max(0.0);
+        if rand::random::<f32>() < q {
+            break;
+        }
+        beta = nuevo_beta / (1.0 - q);
+
+        solo_especulares &= muestra.specular;
+        ray = choque.spawn_ray(&muestra.wi);
+    }
+}
+
+/// Luz directa reflejada hacia wo, con una muestra de una luz elegida al
+/// azar.
+pub(super) fn sample_direct_light(
+    scene: &Scene,
+    choque: &Intersection,
+    normal: &Normal,
+    wo: &Vector,
+) -> SampledSpectrum {
+    let muestra = match scene.sample_light(
+        choque.point(),
+        rand::random(),
+        rand::random(),
+    ) {
+        Some(m) if m.pdf > 0.0 => m,
+        _ => return SampledSpectrum::new(0.0),
+    };
+
+    let f =
+        choque.bsdf().f(wo, &muestra.wi) * muestra.wi.dot(normal).abs() as f32;
+    if f.is_black()
+        || !scene.unoccluded(&choque.spawn_point(&muestra.wi), &muestra.punto)
+    {
+        return SampledSpectrum::new(0.0);
+    }
+
+    f * muestra.emitted / muestra.pdf as f32
+}
+
+/// Parámetros del photon map. photons es la cantidad de fotones que se
+/// emiten, gather_samples los rayos de final gathering en cada punto, y la
+/// radiancia se estima con los gather_photons fotones más cercanos dentro
+/// de radius.
+#[derive(Clone, Copy, Debug)]
+pub struct PhotonMapSettings {
+    pub photons: usize,
+    pub gather_samples: usize,
+    pub gather_photons: usize,
+    pub radius: f64,
+}
+
+impl Default for PhotonMapSettings {
+    fn default() -> Self {
+        PhotonMapSettings {
+            photons: 100_000,
+            gather_samples: 32,
+            gather_photons: 50,
+            radius: 0.5,
+        }
+    }
+}
+
+/// Photon mapping en dos pasadas. Antes de renderizar se emiten fotones
+/// desde las luces y se guardan en dos árboles k-d: el mapa global con todos
+/// los fotones, y el de cáusticas con los que llegaron después de rebotar
+/// solo en superficies especulares. Al renderizar, en el primer punto
+/// difuso se suma la luz directa, las cáusticas leídas del mapa, y la luz
+/// indirecta con final gathering sobre el mapa global.
+#[derive(Clone, Debug)]
+pub struct PhotonMapIntegrator {
+    camera: Camera,
+    scene: Scene,
+    global: KdTree<Photon>,
+    caustic: KdTree<Photon>,
+    settings: PhotonMapSettings,
+    max_depth: usize,
+    iterations: usize,
+}
+
+impl PhotonMapIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        settings: PhotonMapSettings,
+        max_depth: usize,
+        iterations: usize,
+    ) -> Self {
+        let distribución = scene.light_power_distribution();
+        let escala = 1.0 / settings.photons.max(1) as f32;
+
+        let mut global = Vec::new();
+        let mut caustic = Vec::new();
+
+        for _ in 0..settings.photons {
+            trace_photon(
+                scene,
+                &distribución,
+                max_depth,
+                |choque, wi, power, rebotes, solo_especulares| {
+                    let photon = Photon {
+                        wi: *wi,
+                        power: *power * escala,
+                    };
+
+                    if rebotes > 0 && solo_especulares {
+                        caustic.push((*choque.point(), photon));
+                    }
+                    global.push((*choque.point(), photon));
+                },
+            );
+        }
+
+        Self {
+            camera: *camera,
+            scene: scene.clone(),
+            global: KdTree::new(global),
+            caustic: KdTree::new(caustic),
+            settings,
+            max_depth,
+            iterations,
+        }
+    }
+
+    /// Radiancia reflejada hacia wo estimada con los fotones más cercanos
+    /// del mapa.
+    fn radiance_estimate(
+        &self,
+        mapa: &KdTree<Photon>,
+        choque: &Intersection,
+        wo: &Vector,
+    ) -> SampledSpectrum {
+        // en escenas sin superficies especulares no hay cáusticas
+        if mapa.is_empty() {
+            return SampledSpectrum::new(0.0);
+        }
+
+        let k = self.settings.gather_photons;
+        let cercanos = mapa.nearest(choque.point(), k, self.settings.radius);
+        if cercanos.is_empty() {
+            return SampledSpectrum::new(0.0);
+        }
+
+        // si se encontraron k, el radio es la distancia al más lejano
+        let radio_2 = if cercanos.len() == k {
+            cercanos[0].2
+        } else {
+            self.settings.radius * self.settings.radius
+        };
+
+        let bsdf = choque.bsdf();
+        let suma = cercanos
+            .iter()
+            .fold(SampledSpectrum::new(0.0), |acc, (_, photon, _)| {
+                acc + bsdf.f(wo, &photon.wi) * photon.power
+            });
+
+        suma / (PI * radio_2) as f32
+    }
+
+    /// Luz indirecta: se tiran rayos según el material, se siguen por las
+    /// superficies especulares y donde chocan con una difusa se lee el mapa
+    /// global. Los rayos que chocan luces no suman, esa luz ya se cuenta
+    /// con la luz directa y las cáusticas.
+    fn final_gather(
+        &self,
+        choque: &Intersection,
+        normal: &Normal,
+        wo: &Vector,
+    ) -> SampledSpectrum {
+        let n = self.settings.gather_samples;
+        let bsdf = choque.bsdf();
+        let mut light = SampledSpectrum::new(0.0);
+
+        for _ in 0..n {
+            let muestra = match bsdf.sample_f(wo, rand::random()) {
+                Some(m) if m.pdf > 0.0 && !m.f.is_black() => m,
+                _ => continue,
+            };
+            let cos = muestra.wi.dot(normal).abs();
+            let mut beta = muestra.f * (cos / muestra.pdf) as f32;
+            let mut rayo = choque.spawn_ray(&muestra.wi);
+
+            for _ in 0..self.max_depth {
+                let segundo = match self.scene.intersect_ray(&rayo) {
+                    Some(isect) => isect,
+                    None => break,
+                };
+                if segundo.material().emitted().is_some() {
+                    break;
+                }
+
+                let wo = segundo.direction_out();
+                let bsdf = segundo.bsdf();
+                if !bsdf.is_specular() {
+                    light += beta
+                        * self.radiance_estimate(&self.global, &segundo, wo);
+                    break;
+                }
+
+                let muestra = match bsdf.sample_f(wo, rand::random()) {
+                    Some(m) if m.pdf > 0.0 => m,
+                    _ => break,
+                };
+                let cos = muestra.wi.dot(segundo.normal()).abs();
+                beta = beta * muestra.f * (cos / muestra.pdf) as f32;
+                rayo = segundo.spawn_ray(&muestra.wi);
+            }
+        }
+
+        light / n as f32
+    }
+}
+
+impl SamplerIntegrator for PhotonMapIntegrator {
+    fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn total_samples(&self) -> usize {
+        self.iterations
+    }
+
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum {
+        let mut light = SampledSpectrum::new(0.0);
+        let mut beta = SampledSpectrum::new(1.0);
+        let mut siguiente = choque;
+
+        // sigo los rebotes especulares hasta el primer punto difuso
+        for bounces in 0..=depth {
+            let choque = match siguiente.take() {
+                Some(isect) => isect,
+                None => break,
+            };
+            let material = choque.material();
+            let wo = *choque.direction_out();
+            let normal = choque.facing_normal();
+            let bsdf = choque.bsdf();
+
+            if let Some(emitted) = material.emitted() {
+                light += beta * emitted;
+            }
+
+            if bsdf.is_specular() {
+                if bounces == depth {
+                    break;
+                }
+
+                let muestra = match bsdf.sample_f(&wo, rand::random()) {
+                    Some(m) if m.pdf > 0.0 => m,
+                    _ => break,
+                };
+                let cos = muestra.wi.dot(&normal).abs();
+                beta = beta * muestra.f * (cos / muestra.pdf) as f32;
+                siguiente =
+                    self.scene.intersect_ray(&choque.spawn_ray(&muestra.wi));
+                continue;
+            }
+
+            if material.emitted().is_some() {
+                break;
+            }
+
+            // sin final gathering se lee directamente el mapa global, que
+            // ya tiene toda la luz que llega al punto
+            if self.settings.gather_samples == 0 {
+                light +=
+                    beta * self.radiance_estimate(&self.global, &choque, &wo);
+                break;
+            }
+
+            light += beta
+                * (sample_direct_light(&self.scene, &choque, &normal, &wo)
+                    + self.radiance_estimate(&self.caustic, &choque, &wo)
+                    + self.final_gather(&choque, &normal, &wo));
+            break;
+        }
+
+        light
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::geometry::{create_translation, Point};
+    use crate::integrators::PathIntegrator;
+    use crate::material::{Material, Type};
+    use crate::shapes::Sphere;
+
+    /// Una habitación esférica difusa con una luz chica adentro. Casi toda
+    /// la luz que llega a las paredes es indirecta.
+    pub fn habitación() -> Scene {
+        let mut scene = Scene::new();
+
+        let luz = Material {
+            tipo: Type::Emitter,
+            emitted_color: Some(SampledSpectrum::new(4.0)),
+            ..Default::default()
+        };
+        let difuso = Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.7)),
+            ..Default::default()
+        };
+
+        let paredes = Sphere::new(
+            &create_translation(&Vector::new(0.0, 0.0, 0.0)),
+            5.0,
+            &difuso,
+        );
+        let esfera_luz = Sphere::new(
+            &create_translation(&Vector::new(0.0, 0.0, 3.0)),
+            0.5,
+            &luz,
+        );
+        scene.add_shape(&paredes.into()).unwrap();
+        scene.add_shape(&esfera_luz.into()).unwrap();
+        scene.build_bvh();
+
+        scene
+    }
+
+    pub fn referencia(scene: &Scene, ray: &Ray) -> f64 {
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let path = PathIntegrator::new(&camera, scene, 50, 1);
+
+        let n = 20_000;
+        (0..n)
+            .map(|_| path.incident_light(ray, 50).max_value() as f64)
+            .sum::<f64>()
+            / n as f64
+    }
+
+    #[test]
+    fn photon_map_coincide_con_path_tracer() {
+        let scene = habitación();
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let settings = PhotonMapSettings {
+            photons: 20_000,
+            gather_samples: 16,
+            gather_photons: 50,
+            radius: 1.0,
+        };
+        let integrator =
+            PhotonMapIntegrator::new(&camera, &scene, settings, 50, 1);
+
+        let ray = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, -0.5),
+            f64::INFINITY,
+        );
+
+        let n = 200;
+        let estimado = (0..n)
+            .map(|_| integrator.incident_light(&ray, 5).max_value() as f64)
+            .sum::<f64>()
+            / n as f64;
+        let esperado = referencia(&scene, &ray);
+
+        assert!((estimado - esperado).abs() / esperado < 0.1);
+    }
+
+    #[test]
+    fn final_gathering_sigue_los_espejos() {
+        // un espejo junto al punto que se mira: parte de la luz indirecta
+        // llega de las paredes reflejada en él
+        let mut scene = habitación();
+        let espejo = Material {
+            tipo: Type::Specular,
+            specular_color: Some(SampledSpectrum::new(1.0)),
+            ..Default::default()
+        };
+        let esfera = Sphere::new(
+            &create_translation(&Vector::new(0.0, -3.0, 0.0)),
+            1.2,
+            &espejo,
+        );
+        scene.add_shape(&esfera.into()).unwrap();
+        scene.build_bvh();
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let settings = PhotonMapSettings {
+            photons: 20_000,
+            gather_samples: 16,
+            gather_photons: 50,
+            radius: 1.0,
+        };
+        let integrator =
+            PhotonMapIntegrator::new(&camera, &scene, settings, 50, 1);
+
+        let ray = Ray::new(
+            &Point::origin(),
+            &Vector::new(0.5, -1.0, 0.0),
+            f64::INFINITY,
+        );
+
+        let n = 200;
+        let estimado = (0..n)
+            .map(|_| integrator.incident_light(&ray, 5).max_value() as f64)
+            .sum::<f64>()
+            / n as f64;
+        let esperado = referencia(&scene, &ray);
+
+        assert!((estimado - esperado).abs() / esperado < 0.1);
+    }
+}
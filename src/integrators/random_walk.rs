@@ -2,6 +2,7 @@ use super::integrator::SamplerIntegrator;
 use crate::camera::Camera;
 use crate::geometry::Ray;
 use crate::scene::Scene;
+use crate::shapes::Intersection;
 use crate::spectrum::SampledSpectrum;
 
 #[derive(Clone, Debug)]
@@ -46,10 +47,14 @@ impl SamplerIntegrator for RandomWalkIntegrator {
     }
 
     #[allow(non_snake_case)]
-    fn incident_light(&self, ray: &Ray, depth: usize) -> SampledSpectrum {
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum {
         let light = SampledSpectrum::new(0.0);
-        // busco el rayo más cercano.
-        let intersection = match self.scene.intersect_ray(ray) {
+        let intersection = match choque {
             Some(isect) => isect,
             None => return light, // acá debería sumar todas las luces
                                   // que intersecan el rayo
@@ -0,0 +1,419 @@
+use super::auxiliar::{initialize_progress_bar, Image};
+use super::film::{Aov, Film};
+use super::integrator::SamplerIntegrator;
+use super::photon_mapping::{sample_direct_light, trace_photon};
+use crate::accelerators::KdTree;
+use crate::bsdf::Bsdf;
+use crate::camera::Camera;
+use crate::geometry::{Point, Ray, Vector};
+use crate::parallel::ThreadPool;
+use crate::scene::Scene;
+use crate::shapes::Intersection;
+use crate::spectrum::SampledSpectrum;
+use std::f64::consts::PI;
+use std::sync::Mutex;
+
+/// Qué fracción de los fotones nuevos se conserva en cada iteración. Con
+/// valores menores a 1 el radio tiende a 0 y el resultado converge.
+const ALPHA: f64 = 2.0 / 3.0;
+
+/// Primer punto no especular que ve la cámara en un píxel, donde se juntan
+/// los fotones de la iteración.
+#[derive(Clone, Copy, Debug)]
+struct VisiblePoint {
+    punto: Point,
+    wo: Vector,
+    bsdf: Bsdf,
+    beta: SampledSpectrum,
+}
+
+/// Estado de cada píxel entre iteraciones.
+#[derive(Clone, Copy, Debug)]
+struct SPPMPixel {
+    radio: f64,
+    ld: SampledSpectrum, // suma de la luz directa de todas las iteraciones
+    n: f64,              // fotones acumulados
+    tau: SampledSpectrum,
+}
+
+impl SPPMPixel {
+    fn new(radio: f64) -> Self {
+        SPPMPixel {
+            radio,
+            ld: SampledSpectrum::new(0.0),
+            n: 0.0,
+            tau: SampledSpectrum::new(0.0),
+        }
+    }
+
+    /// Suma los m fotones que llegaron al punto visible en esta iteración,
+    /// con flujo phi, y achica el radio.
+    fn update(
+        &mut self,
+        beta: SampledSpectrum,
+        phi: SampledSpectrum,
+        m: usize,
+    ) {
+        if m == 0 {
+            return;
+        }
+
+        let n_nuevo = self.n + ALPHA * m as f64;
+        let radio_nuevo = self.radio * (n_nuevo / (self.n + m as f64)).sqrt();
+        let escala = (radio_nuevo / self.radio).powi(2);
+
+        self.tau = (self.tau + beta * phi) * escala as f32;
+        self.n = n_nuevo;
+        self.radio = radio_nuevo;
+    }
+
+    /// Radiancia estimada después de las iteraciones, con fotones emitidos
+    /// en total.
+    fn radiance(&self, iteraciones: usize, fotones: usize) -> SampledSpectrum {
+        let indirecta = self.tau
+            / (fotones.max(1) as f64 * PI * self.radio * self.radio) as f32;
+
+        self.ld / iteraciones.max(1) as f32 + indirecta
+    }
+}
+
+/// Stochastic progressive photon mapping. Cada iteración tira un rayo por
+/// píxel hasta el primer punto no especular, donde suma la luz directa, y
+/// después emite fotones desde las luces y junta los que caen cerca de cada
+/// punto. El radio de búsqueda de cada píxel se achica con las iteraciones,
+/// así que el sesgo del photon mapping desaparece a medida que se agregan
+/// iteraciones.
+#[derive(Clone, Debug)]
+pub struct SPPMIntegrator {
+    camera: Camera,
+    scene: Scene,
+    initial_radius: f64,
+    photons_per_iteration: usize,
+    max_depth: usize,
+    iterations: usize,
+}
+
+impl SPPMIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        initial_radius: f64,
+        photons_per_iteration: usize,
+        max_depth: usize,
+        iterations: usize,
+    ) -> Self {
+        Self {
+            camera: *camera,
+            scene: scene.clone(),
+            initial_radius,
+            photons_per_iteration,
+            max_depth,
+            iterations,
+        }
+    }
+
+    /// Sigue el rayo de la cámara, que choca primero en choque, por las
+    /// superficies especulares. Devuelve la luz que se puede calcular
+    /// directamente (emisión y luz directa) y el punto visible, si el camino
+    /// llega a una superficie difusa.
+    fn camera_pass<'a>(
+        &'a self,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> (SampledSpectrum, Option<VisiblePoint>) {
+        let mut light = SampledSpectrum::new(0.0);
+        let mut beta = SampledSpectrum::new(1.0);
+        let mut siguiente = choque;
+
+        for bounces in 0..=depth {
+            let choque = match siguiente.take() {
+                Some(isect) => isect,
+                None => break,
+            };
+            let wo = *choque.direction_out();
+            let normal = choque.facing_normal();
+            let bsdf = choque.bsdf();
+
+            if let Some(emitted) = choque.material().emitted() {
+                light += beta * emitted;
+                break;
+            }
+
+            if !bsdf.is_specular() {
+                light += beta
+                    * sample_direct_light(&self.scene, &choque, &normal, &wo);
+
+                let visible = VisiblePoint {
+                    punto: *choque.point(),
+                    wo,
+                    bsdf,
+                    beta,
+                };
+                return (light, Some(visible));
+            }
+
+            if bounces == depth {
+                break;
+            }
+
+            let muestra = match bsdf.sample_f(&wo, rand::random()) {
+                Some(m) if m.pdf > 0.0 => m,
+                _ => break,
+            };
+            let cos = muestra.wi.dot(&normal).abs();
+            beta = beta * muestra.f * (cos / muestra.pdf) as f32;
+            siguiente =
+                self.scene.intersect_ray(&choque.spawn_ray(&muestra.wi));
+        }
+
+        (light, None)
+    }
+
+    /// Emite n fotones y suma el flujo de los que caen dentro del radio de
+    /// cada punto visible. Los fotones que llegan directo desde la luz no se
+    /// cuentan, esa luz ya está en la luz directa.
+    fn photon_pass(
+        &self,
+        n: usize,
+        puntos: &KdTree<usize>,
+        visibles: &[Option<VisiblePoint>],
+        radios: &[f64],
+        radio_max: f64,
+        acumulado: &[Mutex<(SampledSpectrum, usize)>],
+    ) {
+        let distribución = self.scene.light_power_distribution();
+
+        for _ in 0..n {
+            trace_photon(
+                &self.scene,
+                &distribución,
+                self.max_depth,
+                |choque, wi, power, rebotes, _| {
+                    if rebotes == 0 {
+                        return;
+                    }
+
+                    let p = choque.point();
+                    puntos.for_each_within(p, radio_max, |_, &i, d2| {
+                        let vp = match &visibles[i] {
+                            Some(vp) if d2 < radios[i] * radios[i] => vp,
+                            _ => return,
+                        };
+
+                        let f = vp.bsdf.f(&vp.wo, wi);
+                        let mut acc = acumulado[i].lock().unwrap();
+                        acc.0 += f * *power;
+                        acc.1 += 1;
+                    });
+                },
+            );
+        }
+    }
+
+    /// Busca los fotones de una iteración para los puntos visibles.
+    /// Devuelve el flujo y la cantidad de fotones de cada uno.
+    fn gather(
+        &self,
+        visibles: &[Option<VisiblePoint>],
+        radios: &[f64],
+        tareas: usize,
+    ) -> Vec<(SampledSpectrum, usize)> {
+        let puntos = KdTree::new(
+            visibles
+                .iter()
+                .enumerate()
+                .filter_map(|(i, vp)| vp.map(|vp| (vp.punto, i)))
+                .collect(),
+        );
+        let radio_max = radios.iter().cloned().fold(0.0, f64::max);
+        let acumulado: Vec<_> = (0..visibles.len())
+            .map(|_| Mutex::new((SampledSpectrum::new(0.0), 0)))
+            .collect();
+
+        let mut thread_pool = ThreadPool::new();
+        for tarea in 0..tareas {
+            // reparto los fotones entre las tareas
+            let n = self.photons_per_iteration / tareas
+                + usize::from(tarea < self.photons_per_iteration % tareas);
+            let (puntos, acumulado) = (&puntos, &acumulado);
+
+            thread_pool.add_task(move || {
+                self.photon_pass(
+                    n, puntos, visibles, radios, radio_max, acumulado,
+                );
+            });
+        }
+        thread_pool.run(|| {});
+        drop(thread_pool);
+
+        acumulado
+            .into_iter()
+            .map(|acc| acc.into_inner().unwrap())
+            .collect()
+    }
+
+    /// Hace todas las iteraciones y guarda la radiancia de cada píxel en la
+    /// película.
+    fn progressive(&self, film: &Film) -> Result<(), anyhow::Error> {
+        let width = self.camera.width() as usize;
+        let height = self.camera.height() as usize;
+        let tareas = num_cpus::get();
+        println!("Using {} threads.", tareas);
+
+        let mut pixels =
+            vec![SPPMPixel::new(self.initial_radius); width * height];
+        let barrita = initialize_progress_bar(self.iterations as u64)?;
+
+        for _ in 0..self.iterations {
+            // un punto visible por píxel, repartiendo las filas entre tareas
+            let mut visibles = vec![None; width * height];
+            let filas_por_tarea = height.div_ceil(tareas).max(1);
+
+            let mut thread_pool = ThreadPool::new();
+            let bloques = visibles
+                .chunks_mut(filas_por_tarea * width)
+                .zip(pixels.chunks_mut(filas_por_tarea * width))
+                .enumerate();
+            for (bloque, (visibles, pixels)) in bloques {
+                thread_pool.add_task(move || {
+                    let y_0 = bloque * filas_por_tarea;
+                    for (k, (vp, pixel)) in
+                        visibles.iter_mut().zip(pixels.iter_mut()).enumerate()
+                    {
+                        let (x, y) = (k % width, y_0 + k / width);
+                        let ray = self.camera.get_ray(
+                            x as f64 + rand::random::<f64>() - 0.5,
+                            y as f64 + rand::random::<f64>() - 0.5,
+                        );
+
+                        let (ld, visible) = self.camera_pass(
+                            self.scene.intersect_ray(&ray),
+                            self.max_depth,
+                        );
+                        pixel.ld += ld;
+                        *vp = visible;
+                    }
+                });
+            }
+            thread_pool.run(|| {});
+            drop(thread_pool);
+
+            let radios: Vec<f64> = pixels.iter().map(|p| p.radio).collect();
+            let fotones = self.gather(&visibles, &radios, tareas);
+
+            for ((pixel, vp), (phi, m)) in
+                pixels.iter_mut().zip(&visibles).zip(fotones)
+            {
+                if let Some(vp) = vp {
+                    pixel.update(vp.beta, phi, m);
+                }
+            }
+
+            barrita.inc(1);
+        }
+
+        let total = self.iterations * self.photons_per_iteration;
+        for (k, pixel) in pixels.iter().enumerate() {
+            film.set_pixel(
+                (k % width, k / width),
+                pixel.radiance(self.iterations, total),
+            );
+        }
+
+        barrita.finish_with_message("Finalizado.");
+
+        Ok(())
+    }
+}
+
+impl SamplerIntegrator for SPPMIntegrator {
+    fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn total_samples(&self) -> usize {
+        self.iterations
+    }
+
+    /// La imagen final sale de las iteraciones. Las demás salidas se
+    /// calculan aparte con rayos primarios, y en ellas la luz queda en negro.
+    fn render(&self, aovs: &[Aov]) -> Result<Vec<Image>, anyhow::Error> {
+        let width = self.camera.width() as usize;
+        let height = self.camera.height() as usize;
+
+        let film = Film::with_aovs(width, height, aovs);
+
+        if aovs.contains(&Aov::Beauty) {
+            self.progressive(&film)?;
+        }
+
+        if film.has_extra_aovs() {
+            self.render_tiles(&film, false)?;
+        }
+
+        Ok(film.to_images(0.0))
+    }
+
+    /// Hace todas las iteraciones para un solo rayo. El primer choque es
+    /// siempre el mismo, así que se busca una sola vez.
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum {
+        let mut pixel = SPPMPixel::new(self.initial_radius);
+
+        for _ in 0..self.iterations {
+            let (ld, visible) = self.camera_pass(choque.clone(), depth);
+            pixel.ld += ld;
+
+            if let Some(vp) = visible {
+                let (phi, m) = self.gather(&[visible], &[pixel.radio], 1)[0];
+                pixel.update(vp.beta, phi, m);
+            }
+        }
+
+        pixel.radiance(
+            self.iterations,
+            self.iterations * self.photons_per_iteration,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrators::photon_mapping::tests::{habitación, referencia};
+
+    #[test]
+    fn sppm_coincide_con_path_tracer() {
+        let scene = habitación();
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let integrator = SPPMIntegrator::new(&camera, &scene, 1.0, 500, 50, 50);
+
+        let ray = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, -0.5),
+            f64::INFINITY,
+        );
+
+        let n = 8;
+        let estimado = (0..n)
+            .map(|_| integrator.incident_light(&ray, 5).max_value() as f64)
+            .sum::<f64>()
+            / n as f64;
+        let esperado = referencia(&scene, &ray);
+
+        assert!((estimado - esperado).abs() / esperado < 0.1);
+    }
+}
@@ -0,0 +1,246 @@
+use super::integrator::SamplerIntegrator;
+use crate::bsdf::Bsdf;
+use crate::camera::Camera;
+use crate::geometry::{Normal, Ray, Vector};
+use crate::sampling::hammersley;
+use crate::scene::Scene;
+use crate::shapes::Intersection;
+use crate::spectrum::SampledSpectrum;
+
+/// Integrador de Whitted: sigue la reflexión y la refracción perfectas de
+/// los materiales especulares, y en los demás calcula solo la luz directa
+/// con rayos de sombra. No usa números aleatorios, así que la imagen no tiene
+/// ruido, aunque las sombras de las luces grandes quedan escalonadas si
+/// light_samples es chico.
+#[derive(Clone, Debug)]
+pub struct WhittedIntegrator {
+    camera: Camera,
+    scene: Scene,
+    light_samples: usize, // se toman light_samples² puntos de cada luz
+    max_depth: usize,
+    iterations: usize,
+}
+
+impl WhittedIntegrator {
+    pub fn new(
+        camera: &Camera,
+        scene: &Scene,
+        light_samples: usize,
+        max_depth: usize,
+        iterations: usize,
+    ) -> Self {
+        Self {
+            camera: *camera,
+            scene: scene.clone(),
+            light_samples: light_samples.max(1),
+            max_depth,
+            iterations,
+        }
+    }
+
+    /// Luz directa que llega de todas las luces y se refleja hacia wo. Cada
+    /// luz se evalúa en una grilla fija de puntos.
+    fn direct_light(
+        &self,
+        choque: &Intersection,
+        bsdf: &Bsdf,
+        normal: &Normal,
+        wo: &Vector,
+    ) -> SampledSpectrum {
+        let n = self.light_samples;
+        let mut light = SampledSpectrum::new(0.0);
+
+        for luz in 0..self.scene.light_count() {
+            for i in 0..n * n {
+                let u = ((i % n) as f64 + 0.5, (i / n) as f64 + 0.5);
+                let u = (u.0 / n as f64, u.1 / n as f64);
+
+                let muestra =
+                    match self.scene.sample_light_at(luz, choque.point(), u) {
+                        Some(m) if m.pdf > 0.0 => m,
+                        _ => continue,
+                    };
+
+                let f = bsdf.f(wo, &muestra.wi)
+                    * muestra.wi.dot(normal).abs() as f32;
+                if f.is_black()
+                    || !self.scene.unoccluded(
+                        &choque.spawn_point(&muestra.wi),
+                        &muestra.punto,
+                    )
+                {
+                    continue;
+                }
+
+                light += f * muestra.emitted / muestra.pdf as f32;
+            }
+        }
+
+        light / (n * n) as f32
+    }
+
+    /// Sigue la dirección de cada lóbulo especular del material, por
+    /// ejemplo la reflexión y la refracción de un vidrio.
+    fn specular_light(
+        &self,
+        choque: &Intersection,
+        bsdf: &Bsdf,
+        normal: &Normal,
+        wo: &Vector,
+        depth: usize,
+    ) -> SampledSpectrum {
+        bsdf.specular_samples(wo, rand::random()).fold(
+            SampledSpectrum::new(0.0),
+            |acc, muestra| {
+                let cos = muestra.wi.dot(normal).abs();
+                let li = self
+                    .incident_light(&choque.spawn_ray(&muestra.wi), depth - 1);
+
+                acc + muestra.f * li * (cos / muestra.pdf) as f32
+            },
+        )
+    }
+}
+
+impl SamplerIntegrator for WhittedIntegrator {
+    fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    fn total_samples(&self) -> usize {
+        self.iterations
+    }
+
+    /// Las muestras del píxel se reparten con un conjunto de Hammersley en
+    /// vez de al azar, para que el resultado sea siempre el mismo.
+    fn pixel_ray(&self, (x, y): (usize, usize), sample_index: usize) -> Ray {
+        let (u_1, u_2) = hammersley(sample_index, self.total_samples());
+
+        self.camera()
+            .get_ray(x as f64 + u_1 - 0.5, y as f64 + u_2 - 0.5)
+    }
+
+    fn hit_light<'a>(
+        &'a self,
+        _ray: &Ray,
+        choque: Option<Intersection<'a>>,
+        depth: usize,
+    ) -> SampledSpectrum {
+        let choque = match choque {
+            Some(isect) => isect,
+            None => return SampledSpectrum::new(0.0),
+        };
+        let material = choque.material();
+
+        let mut light = material.emitted().unwrap_or(SampledSpectrum::new(0.0));
+
+        let wo = *choque.direction_out();
+        let normal = choque.facing_normal();
+        let bsdf = choque.bsdf();
+
+        if !bsdf.is_specular() {
+            light += self.direct_light(&choque, &bsdf, &normal, &wo);
+        }
+
+        if depth > 0 {
+            light += self.specular_light(&choque, &bsdf, &normal, &wo, depth);
+        }
+
+        light
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{create_translation, Point};
+    use crate::integrators::film::Film;
+    use crate::integrators::path::tests::esfera_dentro_de_una_luz;
+    use crate::material::{Material, Type};
+    use crate::shapes::Sphere;
+
+    fn vidrio() -> Material {
+        Material {
+            tipo: Type::Dielectric,
+            specular_color: Some(SampledSpectrum::new(1.0)),
+            optical_density: Some(1.5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn esfera_de_vidrio_deja_pasar_la_luz() {
+        // Una esfera de vidrio adentro de una esfera que emite 1 para todos
+        // lados: lo que se refleja más lo que se refracta vuelve a ser 1.
+        let scene = esfera_dentro_de_una_luz(&vidrio());
+
+        let camera =
+            Camera::new(&Point::origin(), 1.0, 90.0, (0.0, 0.0, 0.0), (1, 1));
+        let integrator = WhittedIntegrator::new(&camera, &scene, 1, 20, 1);
+
+        for y in [0.0, 0.5, 0.9] {
+            let ray = Ray::new(
+                &Point::origin(),
+                &Vector::new(3.0, y, 0.0),
+                f64::INFINITY,
+            );
+            let light = integrator.incident_light(&ray, 20).max_value();
+
+            assert!((light - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn es_determinístico() {
+        let mut scene = Scene::new();
+
+        let luz = Material {
+            tipo: Type::Emitter,
+            emitted_color: Some(SampledSpectrum::new(1.0)),
+            ..Default::default()
+        };
+        let difuso = Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.5)),
+            ..Default::default()
+        };
+        let esfera_luz = Sphere::new(
+            &create_translation(&Vector::new(0.0, 3.0, 0.0)),
+            0.5,
+            &luz,
+        );
+        let piso = Sphere::new(
+            &create_translation(&Vector::new(0.0, -100.0, 0.0)),
+            100.0,
+            &difuso,
+        );
+        scene.add_shape(&esfera_luz.into()).unwrap();
+        scene.add_shape(&piso.into()).unwrap();
+        scene.build_bvh();
+
+        let camera = Camera::new(
+            &Point::new(0.0, 1.0, -3.0),
+            1.0,
+            90.0,
+            (0.0, 0.0, 0.0),
+            (4, 4),
+        );
+        let integrator = WhittedIntegrator::new(&camera, &scene, 3, 5, 4);
+
+        let film = Film::new(4, 4);
+        let a = integrator.evaluate_pixel_sample((1, 2), 3, &film, true);
+        let b = integrator.evaluate_pixel_sample((1, 2), 3, &film, true);
+        let (a, b) = (a.light.total, b.light.total);
+
+        assert!(!a.is_black());
+        assert_eq!(a.max_value(), b.max_value());
+    }
+}
@@ -1,23 +1,26 @@
+mod accelerators;
 mod auxiliar;
+mod bsdf;
 mod camera;
 mod geometry;
 mod integrators;
 mod material;
+mod medium;
 mod parallel;
+mod resources;
+mod sampling;
 mod scene;
 mod scene_config;
 mod shapes;
 mod spectrum;
+mod texture;
 
 use std::env;
 
-use integrators::AlbedoIntegrator;
-use integrators::NormalIntegrator;
-use integrators::RandomWalkIntegrator;
-use integrators::{Integrator, SamplerIntegrator};
+use integrators::{save_image, Aov, SamplerIntegrator};
 
 fn print_help() {
-    println!("Uso: raytracer [scene.toml] [file.bmp]");
+    println!("Uso: raytracer [scene.toml] [file.bmp | file.pfm]");
 }
 
 fn parse_args() -> Option<(String, String)> {
@@ -34,7 +37,7 @@ fn parse_args() -> Option<(String, String)> {
     for arg in args {
         if arg.ends_with(".toml") {
             scene = arg
-        } else if arg.ends_with(".bmp") {
+        } else if arg.ends_with(".bmp") || arg.ends_with(".pfm") {
             output = arg
         } else {
             print_help();
@@ -70,32 +73,27 @@ fn program() -> Result<(), anyhow::Error> {
 
     let scene = scene_config::parse_scene(&input_toml)?;
 
-    // Primero genero una imagen de los colores
-    let integrator: Integrator =
-        AlbedoIntegrator::new(&camera, &scene, 20).into();
+    let integrator =
+        scene_config::parse_integrator(&input_toml, &camera, &scene)?;
 
-    let imagen = integrator.render()?;
+    // la imagen final y las demás salidas salen del mismo render
+    let aovs: Vec<Aov> = std::iter::once(Aov::Beauty)
+        .chain(scene_config::parse_aovs(&input_toml)?)
+        .collect();
 
-    imagen.save("output-albedo.bmp")?;
-    println!("Imagen guardada en output-albedo.bmp.");
+    let imagenes = integrator.render(&aovs)?;
 
-    // Genero una imagen de las normales
-    let integrator: Integrator =
-        NormalIntegrator::new(&camera, &scene, 20).into();
+    // las demás salidas se guardan en el mismo formato que la imagen final
+    let extensión = output.rsplit('.').next().unwrap_or("bmp");
+    for (aov, imagen) in aovs.iter().zip(imagenes) {
+        let archivo = match aov {
+            Aov::Beauty => output.clone(),
+            _ => format!("output-{}.{extensión}", aov.name()),
+        };
 
-    let imagen = integrator.render()?;
-
-    imagen.save("output-normal.bmp")?;
-    println!("Imagen guardada en output-normal.bmp.");
-
-    // todo: que el integrator reciba el número de muestras.
-    let integrator: Integrator =
-        RandomWalkIntegrator::new(&camera, &scene, 10, 100).into();
-
-    let imagen = integrator.render()?;
-
-    imagen.save(&output)?;
-    println!("Imagen guardada en \"{output}\".");
+        save_image(&imagen, &archivo)?;
+        println!("Imagen guardada en \"{archivo}\".");
+    }
 
     Ok(())
 }
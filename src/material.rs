@@ -1,14 +1,58 @@
+use crate::bsdf::{
+    Bsdf, Coating, Dispersion, Fresnel, LambertianReflection, Layered, Lobe,
+    MicrofacetDistribution, MicrofacetReflection, MicrofacetTransmission,
+    OrenNayar, PrincipledDiffuse, Sheen, SpecularReflection,
+    SpecularTransmission, Substrate, ThinDielectric, D_LINE,
+};
+use crate::geometry::Normal;
+use crate::medium::Medium;
+use crate::resources::MaterialId;
 use crate::spectrum::{SampledSpectrum, SpectrumType};
+use crate::texture::{TexCoord, Texture};
 use wavefront_obj::mtl;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Type {
     Emitter,
     Lambertian,
+    /// Difuso rugoso, para arcilla, cemento o tela: como Lambertian pero
+    /// con microfacetas de pendientes con desvío sigma.
+    OrenNayar,
     Specular,
+    /// Vidrio, agua y demás materiales transparentes: refleja y refracta
+    /// según las ecuaciones de Fresnel, con índice de refracción
+    /// optical_density, o el que da dispersion para cada longitud de onda.
+    Dielectric,
+    /// Metal: refleja como un espejo pero con la reflectancia de Fresnel de
+    /// su índice de refracción complejo, complex_ior, que le da el color.
+    Conductor,
+    /// Base difusa de color ambient_color bajo una capa de barniz que
+    /// refleja según Fresnel, con el color specular_color.
+    Plastic,
+    /// Material de principios de Disney, con los parámetros de principled.
+    Principled,
+    /// Base difusa de color ambient_color bajo el barniz coat, como la
+    /// madera barnizada.
+    CoatedDiffuse,
+    /// Metal como Conductor, con su roughness, bajo el barniz coat, como la
+    /// pintura de los autos.
+    CoatedConductor,
+    /// Lámina fina de índice optical_density, como una ventana o una
+    /// burbuja: la luz la cruza sin desviarse, teñida de specular_color.
+    ThinDielectric,
+    /// En cada choque es uno de los dos materiales de mix, al azar.
+    Mix,
+    /// Piel, cera, mármol: la superficie es como la de Dielectric, pero
+    /// adentro la luz se dispersa en el medio medium. Solo el PathIntegrator
+    /// sigue la luz adentro; los demás lo ven como un vidrio.
+    Subsurface,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Índice de refracción de los dieléctricos que no lo especifican, el de un
+/// vidrio común.
+pub const DEFAULT_IOR: f64 = 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Material {
     //nombre: String,   // no necesito nombre creo
     pub tipo: Type,
@@ -20,6 +64,275 @@ pub struct Material {
     pub specular_coefficient: Option<f64>,       // para la reflexión especular
     // creo, va de 0 a 1000 parece
     pub optical_density: Option<f64>, // el coeficiente de refracción
+    pub dispersion: Option<Dispersion>, // si el índice depende de λ
+    pub complex_ior: Option<(SampledSpectrum, SampledSpectrum)>, // (eta, k)
+    pub roughness: Option<MicrofacetDistribution>, // None si es pulido
+    pub sigma: Option<f64>, // desvío de las pendientes de un difuso, en grados
+    pub principled: Option<Principled>,
+    pub coat: Option<Coat>,
+    pub mix: Option<Mix>,
+    pub medium: Option<Medium>,
+    // si tiene, reemplaza al color del material en cada punto
+    pub color_texture: Option<Texture<SampledSpectrum>>,
+}
+
+impl Material {
+    /// Radiancia que emite el material, si es un emisor que no es negro.
+    pub fn emitted(&self) -> Option<SampledSpectrum> {
+        match self.tipo {
+            Type::Emitter => self.emitted_color.filter(|c| !c.is_black()),
+            _ => None,
+        }
+    }
+
+    /// Color representativo del material en el punto uv, sin importar cómo
+    /// refleja: el color base si es Principled, o el primero que tenga entre
+    /// el ambiente, el emitido, el difuso y el especular.
+    pub fn albedo<'a>(
+        &self,
+        coords: impl Into<TexCoord<'a>>,
+    ) -> SampledSpectrum {
+        let uv = coords.into();
+        if self.color_texture.is_some() {
+            return self.at(uv).albedo(uv);
+        }
+        if let Some(principled) = &self.principled {
+            return principled.base_color.evaluate(uv);
+        }
+        if let Some(mix) = &self.mix {
+            let [a, b] =
+                mix.materials.map(|id| uv.resources.material(id).albedo(uv));
+            return a.lerp(&b, mix.weight(uv) as f32);
+        }
+
+        self.ambient_color
+            .or(self.emitted_color)
+            .or(self.diffused_color)
+            .or(self.specular_color)
+            .unwrap_or(SampledSpectrum::new(0.0))
+    }
+
+    /// El material en el punto uv, con el color de color_texture en el campo
+    /// que usa su tipo: ambient_color en los difusos, y specular_color en
+    /// los que solo reflejan o transmiten.
+    fn at(&self, uv: TexCoord<'_>) -> Material {
+        let textura = match self.color_texture {
+            Some(textura) => textura,
+            None => return *self,
+        };
+        let color = Some(textura.evaluate(uv));
+
+        let mut material = Material {
+            color_texture: None,
+            ..*self
+        };
+        match self.tipo {
+            Type::Lambertian
+            | Type::OrenNayar
+            | Type::Plastic
+            | Type::CoatedDiffuse => material.ambient_color = color,
+            Type::Specular
+            | Type::Dielectric
+            | Type::Conductor
+            | Type::CoatedConductor
+            | Type::ThinDielectric => material.specular_color = color,
+            // el Principled tiene su propia textura en base_color
+            Type::Emitter | Type::Principled | Type::Mix | Type::Subsurface => {
+            }
+        }
+
+        material
+    }
+
+    /// El medio que hay adentro del objeto, si la luz se dispersa adentro.
+    pub fn medium(&self) -> Option<Medium> {
+        match self.tipo {
+            Type::Subsurface => self.medium,
+            _ => None,
+        }
+    }
+
+    /// Índice de refracción del material, si es transparente. Si dispersa
+    /// es el de la línea d.
+    pub fn ior(&self) -> f64 {
+        match self.dispersion {
+            Some(dispersion) => dispersion.eta(D_LINE),
+            None => self
+                .optical_density
+                .filter(|eta| *eta > 0.0)
+                .unwrap_or(DEFAULT_IOR),
+        }
+    }
+
+    /// Si es una mezcla, elige con u en [0, 1) cuál de los materiales se usa
+    /// en el punto uv, y si no lo devuelve igual.
+    pub fn choose<'a>(
+        &'a self,
+        coords: impl Into<TexCoord<'a>>,
+        u: f64,
+    ) -> &'a Material {
+        let uv = coords.into();
+        match &self.mix {
+            Some(mix) if self.tipo == Type::Mix => {
+                let (material, u) = mix.choose(uv, u);
+                material.choose(uv, u)
+            }
+            _ => self,
+        }
+    }
+
+    /// Arma el BSDF del material en un punto de la superficie con la normal
+    /// saliente y las coordenadas uv dadas. Las mezclas no tienen BSDF
+    /// propio: hay que elegir antes el material con choose().
+    pub fn bsdf<'a>(
+        &self,
+        normal: &Normal,
+        coords: impl Into<TexCoord<'a>>,
+    ) -> Bsdf {
+        let uv = coords.into();
+        if self.color_texture.is_some() {
+            return self.at(uv).bsdf(normal, uv);
+        }
+        let mut bsdf = Bsdf::new(normal);
+
+        match self.tipo {
+            Type::Lambertian => {
+                let r = self.ambient_color.unwrap_or(SampledSpectrum::new(0.0));
+                bsdf.add(LambertianReflection::new(r));
+            }
+            Type::OrenNayar => {
+                let r = self.ambient_color.unwrap_or(SampledSpectrum::new(0.0));
+                bsdf.add(OrenNayar::new(r, self.sigma.unwrap_or(0.0)));
+            }
+            Type::Specular => {
+                let r =
+                    self.specular_color.unwrap_or(SampledSpectrum::new(1.0));
+                bsdf.add(SpecularReflection::new(r, Fresnel::NoOp));
+            }
+            Type::Dielectric | Type::Subsurface => {
+                let r =
+                    self.specular_color.unwrap_or(SampledSpectrum::new(1.0));
+                let eta = self.ior();
+
+                // afuera está el vacío y adentro el material. Los lóbulos
+                // miran de qué lado de la normal saliente está wo, que es
+                // lo mismo que el inside del choque, para saber si la luz
+                // entra o sale, así que el cociente de los índices se da
+                // vuelta solo cuando el rayo viene de adentro. Si dispersa,
+                // la reflexión usa el índice de la línea d para todas las
+                // longitudes de onda, que casi no cambia el resultado
+                bsdf.add(self.reflection(
+                    r,
+                    Fresnel::Dielectric {
+                        eta_i: 1.0,
+                        eta_t: eta,
+                    },
+                ));
+
+                // la refracción rugosa no dispersa
+                let transmission: Lobe = match (self.roughness, self.dispersion)
+                {
+                    (Some(distribution), _) => {
+                        MicrofacetTransmission::new(r, distribution, 1.0, eta)
+                            .into()
+                    }
+                    (None, Some(dispersion)) => {
+                        SpecularTransmission::dispersive(r, 1.0, dispersion)
+                            .into()
+                    }
+                    (None, None) => {
+                        SpecularTransmission::new(r, 1.0, eta).into()
+                    }
+                };
+                bsdf.add(transmission);
+            }
+            Type::Conductor => {
+                let r =
+                    self.specular_color.unwrap_or(SampledSpectrum::new(1.0));
+                let fresnel = match self.complex_ior {
+                    Some((eta, k)) => Fresnel::Conductor { eta_i: 1.0, eta, k },
+                    None => Fresnel::NoOp,
+                };
+
+                bsdf.add(self.reflection(r, fresnel));
+            }
+            Type::Plastic => {
+                let kd =
+                    self.ambient_color.unwrap_or(SampledSpectrum::new(0.0));
+                let ks =
+                    self.specular_color.unwrap_or(SampledSpectrum::new(1.0));
+
+                bsdf.add(LambertianReflection::new(kd));
+                bsdf.add(self.reflection(
+                    ks,
+                    Fresnel::Dielectric {
+                        eta_i: 1.0,
+                        eta_t: self.ior(),
+                    },
+                ));
+            }
+            Type::Principled => {
+                let principled = self.principled.unwrap_or_default();
+                principled.add_lobes(&mut bsdf, uv, self.ior());
+            }
+            Type::CoatedDiffuse => {
+                let r = self.ambient_color.unwrap_or(SampledSpectrum::new(0.0));
+                bsdf.add(self.coated(LambertianReflection::new(r)));
+            }
+            Type::CoatedConductor => {
+                let r =
+                    self.specular_color.unwrap_or(SampledSpectrum::new(1.0));
+
+                // el metal está en contacto con el barniz, no con el aire
+                let fresnel = match self.complex_ior {
+                    Some((eta, k)) => Fresnel::Conductor {
+                        eta_i: self.ior(),
+                        eta,
+                        k,
+                    },
+                    None => Fresnel::NoOp,
+                };
+
+                let layered = match self.roughness {
+                    Some(distribution) => self.coated(
+                        MicrofacetReflection::new(r, distribution, fresnel),
+                    ),
+                    None => self.coated(SpecularReflection::new(r, fresnel)),
+                };
+                bsdf.add(layered);
+            }
+            Type::ThinDielectric => {
+                let t =
+                    self.specular_color.unwrap_or(SampledSpectrum::new(1.0));
+                bsdf.add(ThinDielectric::new(t, self.ior()));
+            }
+            Type::Mix | Type::Emitter => (),
+        }
+
+        bsdf
+    }
+
+    /// La base dada bajo el barniz del material, con su índice de refracción.
+    fn coated<S: Into<Substrate>>(&self, base: S) -> Layered {
+        let coat = self.coat.unwrap_or_default();
+
+        Layered::new(
+            Coating::new(coat.roughness, self.ior()),
+            base,
+            coat.thickness,
+            coat.absorption,
+        )
+    }
+
+    /// Reflexión especular, o rugosa si el material tiene roughness.
+    fn reflection(&self, r: SampledSpectrum, fresnel: Fresnel) -> Lobe {
+        match self.roughness {
+            Some(distribution) => {
+                MicrofacetReflection::new(r, distribution, fresnel).into()
+            }
+            None => SpecularReflection::new(r, fresnel).into(),
+        }
+    }
 }
 
 impl Default for Material {
@@ -32,15 +345,359 @@ impl Default for Material {
             specular_color: None,
             specular_coefficient: None,
             optical_density: None,
+            dispersion: None,
+            complex_ior: None,
+            roughness: None,
+            sigma: None,
+            principled: None,
+            coat: None,
+            mix: None,
+            medium: None,
+            color_texture: None,
         }
     }
 }
 
-impl From<&mtl::Material> for Material {
-    fn from(mat: &mtl::Material) -> Self {
+/// Mezcla de dos materiales: en cada punto es el segundo con probabilidad
+/// weight, y si no el primero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mix {
+    pub materials: [MaterialId; 2],
+    pub weight: Texture<f64>,
+}
+
+impl Mix {
+    fn weight(&self, uv: TexCoord<'_>) -> f64 {
+        self.weight.evaluate(uv).clamp(0.0, 1.0)
+    }
+
+    /// Elige uno de los dos materiales con u en [0, 1). Devuelve también u
+    /// reescalado al intervalo del elegido, para poder volver a elegir con
+    /// él si es otra mezcla.
+    fn choose<'a>(&self, uv: TexCoord<'a>, u: f64) -> (&'a Material, f64) {
+        let peso = self.weight(uv);
+        if u < peso {
+            (uv.resources.material(self.materials[1]), u / peso)
+        } else {
+            (
+                uv.resources.material(self.materials[0]),
+                (u - peso) / (1.0 - peso),
+            )
+        }
+    }
+}
+
+/// Barniz de los materiales Coated: su rugosidad, o None si es pulido, y un
+/// medio de espesor thickness entre el barniz y la base, que absorbe la luz
+/// con el coeficiente absorption por unidad de espesor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coat {
+    pub roughness: Option<MicrofacetDistribution>,
+    pub thickness: f64,
+    pub absorption: SampledSpectrum,
+}
+
+impl Default for Coat {
+    fn default() -> Self {
+        Coat {
+            roughness: None,
+            thickness: 0.01,
+            absorption: SampledSpectrum::new(0.0),
+        }
+    }
+}
+
+/// Parámetros del material de principios de Disney, como los piensan los
+/// artistas. Van de 0 a 1 y pueden cambiar sobre la superficie.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Principled {
+    pub base_color: Texture<SampledSpectrum>,
+    pub metallic: Texture<f64>,
+    pub roughness: Texture<f64>,
+    pub anisotropic: Texture<f64>,
+    /// Reflectancia especular de la parte dieléctrica: 0.5 es la de un
+    /// índice de refracción de 1.5.
+    pub specular: Texture<f64>,
+    /// Cuánto se tiñe del color base el reflejo de la parte dieléctrica.
+    pub specular_tint: Texture<f64>,
+    pub sheen: Texture<f64>,
+    pub sheen_tint: Texture<f64>,
+    /// Capa de barniz transparente arriba de todo, y su rugosidad.
+    pub clearcoat: Texture<f64>,
+    pub clearcoat_roughness: Texture<f64>,
+    pub transmission: Texture<f64>,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled {
+            base_color: SampledSpectrum::new(0.8).into(),
+            metallic: 0.0.into(),
+            roughness: 0.5.into(),
+            anisotropic: 0.0.into(),
+            specular: 0.5.into(),
+            specular_tint: 0.0.into(),
+            sheen: 0.0.into(),
+            sheen_tint: 0.5.into(),
+            clearcoat: 0.0.into(),
+            clearcoat_roughness: 0.1.into(),
+            transmission: 0.0.into(),
+        }
+    }
+}
+
+impl Principled {
+    /// Agrega al BSDF los lóbulos del material en el punto uv. eta es el
+    /// índice de refracción de la parte transparente.
+    fn add_lobes(&self, bsdf: &mut Bsdf, uv: TexCoord<'_>, eta: f64) {
+        let valor = |t: &Texture<f64>| t.evaluate(uv).clamp(0.0, 1.0);
+        let base = self.base_color.evaluate(uv);
+        let metallic = valor(&self.metallic);
+        let roughness = valor(&self.roughness);
+        let transmission = valor(&self.transmission);
+
+        // el color base sin su luminancia, para teñir los reflejos
+        let blanco = SampledSpectrum::new(1.0);
+        let luminancia = base.y();
+        let tinte = if luminancia > 0.0 {
+            base / luminancia
+        } else {
+            blanco
+        };
+
+        // los metales y los transparentes no tienen parte difusa
+        let difuso = (1.0 - metallic) * (1.0 - transmission);
+        if difuso > 0.0 {
+            bsdf.add(PrincipledDiffuse::new(base * difuso as f32, roughness));
+
+            let sheen = valor(&self.sheen);
+            if sheen > 0.0 {
+                let color = blanco.lerp(&tinte, valor(&self.sheen_tint) as f32);
+                bsdf.add(Sheen::new(color * (difuso * sheen) as f32));
+            }
+        }
+
+        // el reflejo va del de un dieléctrico, casi blanco y débil, al de un
+        // metal del color base
+        let aspecto = (1.0 - 0.9 * valor(&self.anisotropic)).sqrt();
+        let alpha = MicrofacetDistribution::roughness_to_alpha(roughness);
+        let distribution = MicrofacetDistribution::GGX {
+            alpha_x: alpha / aspecto,
+            alpha_y: alpha * aspecto,
+        };
+        let r0 = blanco.lerp(&tinte, valor(&self.specular_tint) as f32)
+            * (0.08 * valor(&self.specular)) as f32;
+        let r0 = r0.lerp(&base, metallic as f32);
+        bsdf.add(MicrofacetReflection::new(
+            blanco,
+            distribution,
+            Fresnel::Schlick { r0 },
+        ));
+
+        let clearcoat = valor(&self.clearcoat);
+        if clearcoat > 0.0 {
+            let alpha = MicrofacetDistribution::roughness_to_alpha(valor(
+                &self.clearcoat_roughness,
+            ));
+            bsdf.add(MicrofacetReflection::new(
+                SampledSpectrum::new((0.25 * clearcoat) as f32),
+                MicrofacetDistribution::GGX {
+                    alpha_x: alpha,
+                    alpha_y: alpha,
+                },
+                Fresnel::Dielectric {
+                    eta_i: 1.0,
+                    eta_t: DEFAULT_IOR,
+                },
+            ));
+        }
+
+        // la raíz del color para que no quede tan saturado al entrar y salir
+        let transparente = (1.0 - metallic) * transmission;
+        if transparente > 0.0 {
+            bsdf.add(MicrofacetTransmission::new(
+                base.sqrt() * transparente as f32,
+                distribution,
+                1.0,
+                eta,
+            ));
+        }
+    }
+}
+
+/// Parámetros de la extensión PBR de los archivos MTL. El crate de
+/// wavefront_obj no los conoce, así que parse_mtl() los saca antes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MtlPbr {
+    pub roughness: Option<f64>,           // Pr
+    pub metallic: Option<f64>,            // Pm
+    pub sheen: Option<f64>,               // Ps
+    pub clearcoat: Option<f64>,           // Pc
+    pub clearcoat_roughness: Option<f64>, // Pcr
+    pub anisotropic: Option<f64>,         // aniso
+}
+
+impl MtlPbr {
+    fn is_empty(&self) -> bool {
+        *self == MtlPbr::default()
+    }
+}
+
+/// Lo que parse_mtl() saca de cada material antes de pasárselo a
+/// wavefront_obj, que solo acepta los modelos de iluminación de 0 a 3 y no
+/// conoce Tf ni la extensión PBR.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MtlExtra {
+    pub pbr: MtlPbr,
+    pub illum: Option<u8>, // illum, de 0 a 10
+    pub transmission_filter: Option<(f64, f64, f64)>, // Tf
+}
+
+/// Lee los materiales de un archivo MTL, incluyendo los de la extensión PBR,
+/// los modelos de iluminación de 0 a 10 y el filtro de transmisión Tf. Los
+/// mapas de los parámetros PBR todavía se ignoran.
+pub fn parse_mtl(datos: &str) -> Result<Vec<Material>, anyhow::Error> {
+    let mut extras: Vec<MtlExtra> = Vec::new();
+    let mut resto = String::new();
+    let mut nombre = "";
+
+    for línea in datos.lines() {
+        let mut palabras = línea.split_whitespace();
+        let campo = palabras.next().unwrap_or("");
+        let inválido = || anyhow::anyhow!("Valor inválido en el MTL: {línea}");
+
+        if campo == "newmtl" {
+            extras.push(MtlExtra::default());
+            nombre = línea.trim()["newmtl".len()..].trim();
+        }
+
+        // los mapas se leen pero todavía no se usan
+        if campo.starts_with("map_") {
+            eprintln!(
+                "Advertencia: el material {nombre} tiene un {campo}, que no \
+                 se soporta y se ignora"
+            );
+        }
+
+        let parámetro = match (campo, extras.last_mut()) {
+            ("Pr", Some(actual)) => &mut actual.pbr.roughness,
+            ("Pm", Some(actual)) => &mut actual.pbr.metallic,
+            ("Ps", Some(actual)) => &mut actual.pbr.sheen,
+            ("Pc", Some(actual)) => &mut actual.pbr.clearcoat,
+            ("Pcr", Some(actual)) => &mut actual.pbr.clearcoat_roughness,
+            ("aniso", Some(actual)) => &mut actual.pbr.anisotropic,
+            ("illum", Some(actual)) => {
+                let illum = palabras
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .filter(|illum| *illum <= 10)
+                    .ok_or_else(inválido)?;
+                actual.illum = Some(illum);
+
+                // a wavefront_obj le paso uno que conozca, no lo usa
+                resto.push_str("illum 2\n");
+                continue;
+            }
+            ("Tf", Some(actual)) => {
+                let color = palabras
+                    .map(|v| v.parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| inválido())?;
+                actual.transmission_filter = match color[..] {
+                    [r, g, b] => Some((r, g, b)),
+                    [y] => Some((y, y, y)),
+                    _ => return Err(inválido()),
+                };
+                continue;
+            }
+            ("anisor" | "norm", _) => continue,
+            (mapa, _) if mapa.starts_with("map_P") => continue,
+            _ => {
+                resto.push_str(línea);
+                resto.push('\n');
+                continue;
+            }
+        };
+
+        let valor = palabras.next().and_then(|v| v.parse().ok());
+        *parámetro = Some(valor.ok_or_else(inválido)?);
+    }
+
+    let materiales = mtl::parse(resto)?.materials;
+
+    Ok(materiales
+        .iter()
+        .zip(extras)
+        .map(|(mat, extra)| Material::from_mtl(mat, &extra))
+        .collect())
+}
+
+impl Material {
+    /// Material de un MTL con los parámetros que leyó parse_mtl(). Si tiene
+    /// alguno de la extensión PBR es Principled, con Kd como color base, y
+    /// si no depende del modelo de iluminación.
+    pub fn from_mtl(mat: &mtl::Material, extra: &MtlExtra) -> Material {
+        let illum = extra.illum.unwrap_or(match mat.illumination {
+            mtl::Illumination::Ambient => 0,
+            mtl::Illumination::AmbientDiffuse => 1,
+            mtl::Illumination::AmbientDiffuseSpecular => 2,
+            mtl::Illumination::ReflectionRayTrace => 3,
+        });
+        let material =
+            Material::from_illum(mat, illum, extra.transmission_filter);
+
+        let pbr = &extra.pbr;
+        if pbr.is_empty() {
+            return material;
+        }
+
+        let defecto = Principled::default();
+        let parámetro = |valor: Option<f64>, defecto: Texture<f64>| {
+            valor.map_or(defecto, Texture::Constant)
+        };
+
         Material {
-            tipo: Type::Lambertian, // después ver que hacer con esto
-            ambient_color: Some(create_spectrum_from_mtl(&mat.color_ambient)),
+            tipo: Type::Principled,
+            principled: Some(Principled {
+                base_color: create_spectrum_from_mtl(&mat.color_diffuse).into(),
+                metallic: parámetro(pbr.metallic, defecto.metallic),
+                roughness: parámetro(pbr.roughness, defecto.roughness),
+                anisotropic: parámetro(pbr.anisotropic, defecto.anisotropic),
+                sheen: parámetro(pbr.sheen, defecto.sheen),
+                clearcoat: parámetro(pbr.clearcoat, defecto.clearcoat),
+                clearcoat_roughness: parámetro(
+                    pbr.clearcoat_roughness, defecto.clearcoat_roughness,
+                ),
+                transmission: (1.0 - mat.alpha).clamp(0.0, 1.0).into(),
+                ..defecto
+            }),
+            ..material
+        }
+    }
+
+    /// Material equivalente al modelo de iluminación illum del MTL:
+    ///
+    /// - 0, 1 y 10: difuso de color Kd. El 0 no se ilumina y el 10 solo
+    ///   sirve para tirar sombras sobre superficies invisibles, y eso no se
+    ///   puede hacer, así que avisan.
+    /// - 2 y 5: difuso Kd bajo un barniz que refleja Ks según Fresnel, con
+    ///   la rugosidad que da Ns. Si Ks es negro es solo difuso.
+    /// - 3 y 8: espejo de color Ks, pulido o rugoso según Ns. Si Kd no es
+    ///   negro no hay un material que sume las dos cosas sin Fresnel, así
+    ///   que es como el 2.
+    /// - 4, 6 y 7: vidrio de índice Ni, teñido de Tf.
+    /// - 9: como el 4 pero sin refracción, una lámina fina.
+    ///
+    /// Los que no son vidrio pero tienen d < 1 también son vidrios, con el
+    /// Ks como brillo del reflejo y no como color.
+    fn from_illum(
+        mat: &mtl::Material,
+        illum: u8,
+        transmission_filter: Option<(f64, f64, f64)>,
+    ) -> Material {
+        let material = Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(create_spectrum_from_mtl(&mat.color_diffuse)),
             emitted_color: mat
                 .color_emissive
                 .map(|c| create_spectrum_from_mtl(&c)),
@@ -48,10 +705,168 @@ impl From<&mtl::Material> for Material {
             specular_color: Some(create_spectrum_from_mtl(&mat.color_specular)),
             specular_coefficient: Some(mat.specular_coefficient),
             optical_density: mat.optical_density,
+            dispersion: None,
+            complex_ior: None,
+            roughness: None,
+            sigma: None,
+            principled: None,
+            coat: None,
+            mix: None,
+            medium: None,
+            color_texture: None,
+        };
+
+        let negro = |c: &mtl::Color| c.r <= 0.0 && c.g <= 0.0 && c.b <= 0.0;
+        let difuso = !negro(&mat.color_diffuse);
+        let especular = !negro(&mat.color_specular);
+        let roughness = roughness_from_ns(mat.specular_coefficient);
+
+        let vidrio = Material {
+            tipo: Type::Dielectric,
+            specular_color: transmission_filter.map(|(r, g, b)| {
+                SampledSpectrum::from_RGB(
+                    (r as f32, g as f32, b as f32),
+                    SpectrumType::Reflectance,
+                )
+            }),
+            ..material
+        };
+        let barnizado = Material {
+            tipo: if especular {
+                Type::Plastic
+            } else {
+                Type::Lambertian
+            },
+            roughness,
+            ..material
+        };
+
+        match illum {
+            4 | 6 | 7 => vidrio,
+            9 => Material {
+                tipo: Type::ThinDielectric,
+                ..vidrio
+            },
+            _ if mat.alpha < 1.0 => {
+                eprintln!(
+                    "Advertencia: el material {} es transparente (d < 1) con \
+                     el modelo de iluminación {illum}, se usa un vidrio",
+                    mat.name
+                );
+                vidrio
+            }
+            1 => material,
+            2 | 5 => barnizado,
+            3 | 8 if !difuso => Material {
+                tipo: Type::Conductor,
+                roughness,
+                ..material
+            },
+            3 | 8 => {
+                eprintln!(
+                    "Advertencia: el material {} es un espejo con color \
+                     difuso, se usa un barniz con Fresnel",
+                    mat.name
+                );
+                barnizado
+            }
+            _ => {
+                eprintln!(
+                    "Advertencia: el modelo de iluminación {illum} del \
+                     material {} no se puede simular, se usa uno difuso",
+                    mat.name
+                );
+                material
+            }
         }
     }
 }
 
+/// Rugosidad equivalente al exponente especular Ns, que va de 0 a 1000,
+/// como la calcula Blender al exportar. None si es pulido.
+fn roughness_from_ns(ns: f64) -> Option<MicrofacetDistribution> {
+    let roughness = 1.0 - (ns / 1000.0).clamp(0.0, 1.0).sqrt();
+    if roughness <= 0.0 {
+        return None;
+    }
+
+    let alpha = MicrofacetDistribution::roughness_to_alpha(roughness);
+    Some(MicrofacetDistribution::GGX {
+        alpha_x: alpha,
+        alpha_y: alpha,
+    })
+}
+
+impl From<&mtl::Material> for Material {
+    fn from(mat: &mtl::Material) -> Self {
+        Material::from_mtl(mat, &MtlExtra::default())
+    }
+}
+
+/// Reflectancia de Fresnel para una superficie entre dos dieléctricos, sin
+/// polarizar. cos_i es el coseno del ángulo con la normal del lado de donde
+/// llega la luz, y eta_i, eta_t los índices de refracción de cada lado.
+pub fn fresnel_dielectric(cos_i: f64, eta_i: f64, eta_t: f64) -> f64 {
+    let cos_i = cos_i.clamp(-1.0, 1.0);
+    let (eta_i, eta_t, cos_i) = if cos_i < 0.0 {
+        // la luz viene del otro lado
+        (eta_t, eta_i, -cos_i)
+    } else {
+        (eta_i, eta_t, cos_i)
+    };
+
+    let sen_i = (1.0 - cos_i * cos_i).max(0.0).sqrt();
+    let sen_t = eta_i / eta_t * sen_i;
+
+    // reflexión total interna
+    if sen_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sen_t * sen_t).max(0.0).sqrt();
+
+    let r_par =
+        (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+    let r_perp =
+        (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+
+    (r_par * r_par + r_perp * r_perp) / 2.0
+}
+
+/// Reflectancia de Fresnel para la superficie entre un dieléctrico de índice
+/// eta_i y un conductor de índice complejo eta + ik, sin polarizar. cos_i es
+/// el coseno del ángulo con la normal del lado del dieléctrico.
+pub fn fresnel_conductor(
+    cos_i: f64,
+    eta_i: f64,
+    eta: &SampledSpectrum,
+    k: &SampledSpectrum,
+) -> SampledSpectrum {
+    let cos_i = cos_i.clamp(-1.0, 1.0) as f32;
+    let eta = eta / eta_i as f32;
+    let k = k / eta_i as f32;
+
+    let cos_2 = SampledSpectrum::new(cos_i * cos_i);
+    let sen_2 = SampledSpectrum::new(1.0 - cos_i * cos_i);
+    let eta_2 = eta * eta;
+    let k_2 = k * k;
+
+    // a² + b² = |(eta + ik)² - sen²|, con a la parte real de la raíz
+    let t_0 = eta_2 - k_2 - sen_2;
+    let a2_mas_b2 = (t_0 * t_0 + eta_2 * k_2 * 4.0).sqrt();
+    let a = ((a2_mas_b2 + t_0) * 0.5).sqrt();
+
+    let t_1 = a2_mas_b2 + cos_2;
+    let t_2 = a * (2.0 * cos_i);
+    let r_perp = (t_1 - t_2) / (t_1 + t_2);
+
+    let t_3 = cos_2 * a2_mas_b2 + sen_2 * sen_2;
+    let t_4 = t_2 * sen_2;
+    let r_par = r_perp * (t_3 - t_4) / (t_3 + t_4);
+
+    (r_par + r_perp) * 0.5
+}
+
 #[inline]
 fn create_spectrum_from_mtl(color: &mtl::Color) -> SampledSpectrum {
     SampledSpectrum::from_RGB(
@@ -59,3 +874,586 @@ fn create_spectrum_from_mtl(color: &mtl::Color) -> SampledSpectrum {
         SpectrumType::Reflectance,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_float;
+    use crate::geometry::{random_versor_cos_density, Point, Ray, Vector};
+    use crate::resources::Resources;
+    use crate::shapes::Intersection;
+    use crate::spectrum::N_SAMPLES;
+
+    fn lambertian() -> Material {
+        Material {
+            tipo: Type::Lambertian,
+            ambient_color: Some(SampledSpectrum::new(0.5)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lambertian_conserva_energía() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let bsdf = lambertian().bsdf(&normal, (0.0, 0.0));
+        let wo = Vector::new(1.0, 0.0, 1.0).normalize();
+
+        // estimo la reflectancia como el promedio de f.cos/pdf
+        let n = 10_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let s = bsdf.sample_f(&wo, rand::random()).unwrap();
+            let cos = s.wi.dot(&normal);
+            total += s.f.max_value() as f64 * cos / s.pdf;
+
+            assert_eq_float!(s.pdf, bsdf.pdf(&wo, &s.wi));
+        }
+
+        assert!((total / n as f64 - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn materiales_rugosos() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let wo = Vector::new(1.0, 0.0, 1.0).normalize();
+        let rugosidad = MicrofacetDistribution::GGX {
+            alpha_x: 0.2,
+            alpha_y: 0.2,
+        };
+
+        for tipo in [Type::Conductor, Type::Dielectric, Type::Plastic] {
+            let pulido = Material {
+                tipo,
+                ambient_color: Some(SampledSpectrum::new(0.5)),
+                ..Default::default()
+            };
+            let rugoso = Material {
+                roughness: Some(rugosidad),
+                ..pulido
+            };
+            assert!(!rugoso.bsdf(&normal, (0.0, 0.0)).is_specular());
+
+            // las muestras tienen la densidad que dice pdf(), y no refleja
+            // más luz de la que llega
+            let bsdf = rugoso.bsdf(&normal, (0.0, 0.0));
+            let n = 10_000;
+            let mut total = 0.0;
+            for _ in 0..n {
+                if let Some(s) = bsdf.sample_f(&wo, rand::random()) {
+                    let pdf = bsdf.pdf(&wo, &s.wi);
+                    assert!((s.pdf - pdf).abs() <= 1e-6 * pdf);
+                    total += s.f.max_value() as f64 * s.wi.z.abs() / s.pdf;
+                }
+            }
+            assert!(total / n as f64 <= 1.02); // margen para el ruido
+
+            // el conductor y el dieléctrico pulidos siguen siendo espejos
+            if tipo != Type::Plastic {
+                assert!(pulido.bsdf(&normal, (0.0, 0.0)).is_specular());
+            }
+        }
+    }
+
+    #[test]
+    fn material_principled() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let wo = Vector::new(1.0, 0.0, 1.0).normalize();
+        let tablero = Texture::Checkerboard {
+            even: SampledSpectrum::new(0.9),
+            odd: SampledSpectrum::new(0.2),
+            scale: 2.0,
+        };
+        let principled = |p: Principled| Material {
+            tipo: Type::Principled,
+            principled: Some(p),
+            ..Default::default()
+        };
+
+        // no refleja más luz de la que llega, con cualquier combinación
+        for p in [
+            Principled::default(),
+            Principled {
+                metallic: 1.0.into(),
+                anisotropic: 0.8.into(),
+                ..Default::default()
+            },
+            Principled {
+                sheen: 1.0.into(),
+                clearcoat: 1.0.into(),
+                roughness: 1.0.into(),
+                ..Default::default()
+            },
+            Principled {
+                transmission: 1.0.into(),
+                roughness: 0.2.into(),
+                ..Default::default()
+            },
+        ] {
+            let bsdf = principled(p).bsdf(&normal, (0.0, 0.0));
+            let n = 10_000;
+            let mut total = 0.0;
+            for _ in 0..n {
+                if let Some(s) = bsdf.sample_f(&wo, rand::random()) {
+                    total += s.f.max_value() as f64 * s.wi.z.abs() / s.pdf;
+                }
+            }
+            assert!(total / n as f64 <= 1.02); // margen para el ruido
+        }
+
+        // un metal refleja de frente el color base, que cambia con uv
+        let metal = principled(Principled {
+            base_color: tablero,
+            metallic: 1.0.into(),
+            roughness: 0.3.into(),
+            ..Default::default()
+        });
+        let f = |uv| metal.bsdf(&normal, uv).f(&normal, &normal).max_value();
+        assert!((f((0.1, 0.1)) / f((0.6, 0.1)) - 4.5).abs() < 1e-3);
+        assert_eq!(metal.albedo((0.6, 0.1)), SampledSpectrum::new(0.2));
+
+        // y no tiene parte difusa, así que de costado refleja mucho menos
+        // que el mismo material no metálico
+        let plástico = Material {
+            principled: Some(Principled {
+                metallic: 0.0.into(),
+                ..metal.principled.unwrap()
+            }),
+            ..metal
+        };
+        let costado = Vector::new(0.0, 1.0, 1.0).normalize();
+        let f = |m: &Material| {
+            m.bsdf(&normal, (0.0, 0.0)).f(&wo, &costado).max_value()
+        };
+        assert!(f(&metal) < 0.1 * f(&plástico));
+    }
+
+    #[test]
+    fn materiales_con_barniz() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let wo = Vector::new(1.0, 0.0, 1.0).normalize();
+        let reflectancia = |material: &Material| {
+            let bsdf = material.bsdf(&normal, (0.0, 0.0));
+            let n = 10_000;
+            let mut total = 0.0;
+            for _ in 0..n {
+                if let Some(s) = bsdf.sample_f(&wo, rand::random()) {
+                    total += s.f.max_value() as f64 * s.wi.z.abs() / s.pdf;
+                }
+            }
+            total / n as f64
+        };
+
+        let madera = Material {
+            tipo: Type::CoatedDiffuse,
+            ambient_color: Some(SampledSpectrum::new(0.5)),
+            ..Default::default()
+        };
+        let oscura = Material {
+            coat: Some(Coat {
+                absorption: SampledSpectrum::new(20.0),
+                ..Default::default()
+            }),
+            ..madera
+        };
+        assert!(!madera.bsdf(&normal, (0.0, 0.0)).is_specular());
+        assert!(reflectancia(&madera) < 1.0);
+        assert!(reflectancia(&oscura) < reflectancia(&madera));
+
+        // un metal pulido bajo un barniz pulido sigue siendo un espejo
+        let auto = Material {
+            tipo: Type::CoatedConductor,
+            complex_ior: SampledSpectrum::metal_ior("Gold"),
+            ..Default::default()
+        };
+        assert!(auto.bsdf(&normal, (0.0, 0.0)).is_specular());
+        assert!(reflectancia(&auto) <= 1.0);
+    }
+
+    #[test]
+    fn mezcla() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let vidrio = Material {
+            tipo: Type::ThinDielectric,
+            specular_color: Some(SampledSpectrum::new(1.0)),
+            ..Default::default()
+        };
+        let mut recursos = Resources::new();
+        let materials = [
+            recursos.add_material(lambertian()),
+            recursos.add_material(vidrio),
+        ];
+        let uv = TexCoord {
+            uv: (0.0, 0.0),
+            width: 0.0,
+            resources: &recursos,
+        };
+        let mix = |weight: f64| Material {
+            tipo: Type::Mix,
+            mix: Some(Mix {
+                materials,
+                weight: weight.into(),
+            }),
+            ..Default::default()
+        };
+
+        let es_vidrio = |material: &Material, u: f64| {
+            material.choose(uv, u).bsdf(&normal, uv).is_specular()
+        };
+
+        // con peso 0 o 1 siempre es el mismo
+        assert!(!es_vidrio(&mix(0.0), 0.5));
+        assert!(es_vidrio(&mix(1.0), 0.5));
+
+        // y si no, elige cada uno según el peso
+        let n = 1000;
+        let vidrios = (0..n)
+            .filter(|i| es_vidrio(&mix(0.3), (*i as f64 + 0.5) / n as f64))
+            .count();
+        assert_eq!(vidrios, 300);
+
+        // en el choque elige siempre el mismo en el mismo punto, y según el
+        // peso entre puntos distintos
+        let material = mix(0.3);
+        let vidrios = (0..n)
+            .filter(|_| {
+                let punto = Point::from(rand::random::<[f64; 3]>());
+                let rayo = Ray::new(&punto, &-normal, f64::INFINITY);
+                let mut choque =
+                    Intersection::new(&material, &punto, &rayo, &normal, 1.0);
+                choque.set_resources(&recursos);
+                let vidrio = choque.bsdf().is_specular();
+                assert!((0..4).all(|_| choque.bsdf().is_specular() == vidrio));
+
+                vidrio
+            })
+            .count();
+        assert!((vidrios as f64 / n as f64 - 0.3).abs() < 0.05);
+
+        let albedo = mix(0.5).albedo(uv).max_value();
+        assert!((albedo - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn color_con_textura() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let tablero = Texture::Checkerboard {
+            even: SampledSpectrum::new(0.9),
+            odd: SampledSpectrum::new(0.2),
+            scale: 2.0,
+        };
+
+        // la textura va al color que usa cada tipo
+        for tipo in [Type::Lambertian, Type::Plastic, Type::Conductor] {
+            let material = Material {
+                tipo,
+                color_texture: Some(tablero),
+                ..Default::default()
+            };
+            let color = |uv| material.at(TexCoord::from(uv));
+
+            let (par, impar) = (color((0.1, 0.1)), color((0.6, 0.1)));
+            assert!(par.color_texture.is_none());
+            if tipo == Type::Conductor {
+                assert_eq!(par.specular_color, Some(SampledSpectrum::new(0.9)));
+                assert_eq!(
+                    impar.specular_color,
+                    Some(SampledSpectrum::new(0.2))
+                );
+            } else {
+                assert_eq!(par.ambient_color, Some(SampledSpectrum::new(0.9)));
+                assert_eq!(
+                    impar.ambient_color,
+                    Some(SampledSpectrum::new(0.2))
+                );
+            }
+            assert_eq!(material.albedo((0.6, 0.1)), SampledSpectrum::new(0.2));
+        }
+
+        let difuso = Material {
+            tipo: Type::Lambertian,
+            color_texture: Some(tablero),
+            ..Default::default()
+        };
+        let f = |uv| difuso.bsdf(&normal, uv).f(&normal, &normal).max_value();
+        assert!((f((0.1, 0.1)) - 0.9 / std::f32::consts::PI).abs() < 1e-6);
+        assert!((f((0.6, 0.1)) - 0.2 / std::f32::consts::PI).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mtl_con_pbr() {
+        let datos = "newmtl liso
+Ns 10
+Ka 0 0 0
+Kd 0.5 0.5 0.5
+Ks 0 0 0
+d 1
+illum 2
+
+newmtl pbr
+Ns 10
+Ka 0 0 0
+Kd 0.8 0.1 0.1
+Ks 0 0 0
+Pr 0.25
+Pm 1
+Pc 0.5
+map_Pr rugosidad.png
+d 1
+illum 2
+";
+        let materiales = parse_mtl(datos).unwrap();
+        assert_eq!(materiales.len(), 2);
+        assert_eq!(materiales[0].tipo, Type::Lambertian);
+        assert!(materiales[0].principled.is_none());
+
+        let pbr = materiales[1].principled.unwrap();
+        assert_eq!(materiales[1].tipo, Type::Principled);
+        assert_eq!(pbr.roughness, Texture::Constant(0.25));
+        assert_eq!(pbr.metallic, Texture::Constant(1.0));
+        assert_eq!(pbr.clearcoat, Texture::Constant(0.5));
+        assert_eq!(pbr.sheen, Principled::default().sheen);
+
+        assert!(parse_mtl("newmtl malo\nPr rugoso\n").is_err());
+    }
+
+    #[test]
+    fn lambertian_no_transmite() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let bsdf = lambertian().bsdf(&normal, (0.0, 0.0));
+        let wo = random_versor_cos_density(&normal);
+        let wi = -random_versor_cos_density(&normal);
+
+        assert!(bsdf.f(&wo, &wi).is_black());
+        assert_eq_float!(bsdf.pdf(&wo, &wi), 0.0);
+    }
+
+    #[test]
+    fn especular_refleja() {
+        let material = Material {
+            tipo: Type::Specular,
+            ..Default::default()
+        };
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let bsdf = material.bsdf(&normal, (0.0, 0.0));
+        let wo = Vector::new(1.0, 2.0, 3.0).normalize();
+
+        let s = bsdf.sample_f(&wo, rand::random()).unwrap();
+
+        assert!(bsdf.is_specular());
+        assert!(s.specular);
+        assert_eq_float!(s.wi.x, -wo.x);
+        assert_eq_float!(s.wi.y, -wo.y);
+        assert_eq_float!(s.wi.z, wo.z);
+    }
+
+    fn vidrio() -> Material {
+        Material {
+            tipo: Type::Dielectric,
+            optical_density: Some(1.5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dieléctrico_cumple_snell() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let bsdf = vidrio().bsdf(&normal, (0.0, 0.0));
+        let sen = |v: &Vector| (v.x * v.x + v.y * v.y).sqrt();
+
+        // desde afuera: sen(θ_i) = 1.5 sen(θ_t)
+        let wo = Vector::new(0.6, 0.0, 0.8);
+        let muestras: Vec<_> = bsdf.specular_samples(&wo, (0.5, 0.5)).collect();
+        assert_eq!(muestras.len(), 2);
+
+        let refractado = muestras.iter().find(|m| m.wi.z < 0.0).unwrap();
+        assert_eq_float!(sen(&wo), 1.5 * sen(&refractado.wi));
+        assert!(refractado.wi.x < 0.0);
+
+        // desde adentro el cociente se da vuelta: 1.5 sen(θ_i) = sen(θ_t)
+        let wo = Vector::new(0.3, 0.0, -(1.0f64 - 0.09).sqrt());
+        let refractado = bsdf
+            .specular_samples(&wo, (0.5, 0.5))
+            .find(|m| m.wi.z > 0.0);
+        assert_eq_float!(1.5 * sen(&wo), sen(&refractado.unwrap().wi));
+    }
+
+    #[test]
+    fn dieléctrico_reflexión_total_interna() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let bsdf = vidrio().bsdf(&normal, (0.0, 0.0));
+
+        // desde adentro, pasando el ángulo crítico, solo queda el reflejo y
+        // refleja toda la luz
+        let wo = Vector::new(0.8, 0.0, -0.6);
+        let muestras: Vec<_> = bsdf.specular_samples(&wo, (0.5, 0.5)).collect();
+        assert_eq!(muestras.len(), 1);
+
+        let reflejo = muestras[0];
+        assert!(reflejo.wi.z < 0.0);
+        let cos = reflejo.wi.z.abs();
+        assert!((reflejo.f.max_value() as f64 * cos - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn dieléctrico_conserva_energía() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let bsdf = vidrio().bsdf(&normal, (0.0, 0.0));
+
+        for wo in [Vector::new(0.0, 0.6, 0.8), Vector::new(0.2, 0.0, -0.98)] {
+            let wo = wo.normalize();
+            let total = bsdf
+                .specular_samples(&wo, (0.5, 0.5))
+                .map(|m| m.f.max_value() as f64 * m.wi.z.abs() / m.pdf)
+                .sum::<f64>();
+
+            assert!((total - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn mtl_transparente_es_dieléctrico() {
+        let mtl = |d: f64| {
+            let datos = format!(
+                "newmtl agua\nNs 10\nKa 0 0 0\nKd 0.5 0.5 0.5\n\
+                 Ks 0.5 0.5 0.5\nNi 1.33\nd {d}\nillum 2\n"
+            );
+            let materiales = mtl::parse(datos).unwrap().materials;
+            Material::from(&materiales[0])
+        };
+
+        let agua = mtl(0.5);
+        assert_eq!(agua.tipo, Type::Dielectric);
+        assert_eq_float!(agua.ior(), 1.33);
+
+        assert_eq!(mtl(1.0).tipo, Type::Plastic);
+    }
+
+    #[test]
+    fn mtl_modelos_de_iluminación() {
+        let mtl = |illum: u8, kd: f64, ks: f64, ns: f64| {
+            let datos = format!(
+                "newmtl prueba\nNs {ns}\nKa 0 0 0\nKd {kd} {kd} {kd}\n\
+                 Ks {ks} {ks} {ks}\nNi 1.33\nTf 0.5 0.5 1\nd 1\n\
+                 illum {illum}\n"
+            );
+            parse_mtl(&datos).unwrap()[0]
+        };
+
+        assert_eq!(mtl(1, 0.5, 0.5, 10.0).tipo, Type::Lambertian);
+        assert_eq!(mtl(2, 0.5, 0.0, 10.0).tipo, Type::Lambertian);
+        assert_eq!(mtl(10, 0.5, 0.5, 10.0).tipo, Type::Lambertian);
+
+        // el brillo es más rugoso cuanto menor es Ns
+        let plástico = mtl(2, 0.5, 0.5, 250.0);
+        assert_eq!(plástico.tipo, Type::Plastic);
+        assert_eq!(
+            plástico.roughness,
+            Some(MicrofacetDistribution::GGX {
+                alpha_x: 0.25,
+                alpha_y: 0.25,
+            })
+        );
+        assert_eq!(mtl(5, 0.5, 0.5, 1000.0).roughness, None);
+
+        assert_eq!(mtl(3, 0.0, 0.9, 1000.0).tipo, Type::Conductor);
+        assert_eq!(mtl(8, 0.5, 0.9, 1000.0).tipo, Type::Plastic);
+
+        for illum in [4, 6, 7] {
+            let vidrio = mtl(illum, 0.5, 0.5, 10.0);
+            assert_eq!(vidrio.tipo, Type::Dielectric);
+            assert_eq!(vidrio.roughness, None);
+            assert_eq_float!(vidrio.ior(), 1.33);
+            assert!(vidrio.specular_color.is_some());
+        }
+        assert_eq!(mtl(9, 0.5, 0.5, 10.0).tipo, Type::ThinDielectric);
+
+        // los mapas se ignoran, pero no hacen fallar el MTL
+        let mapas = "newmtl mapas\nNs 10\nKa 0 0 0\nKd 1 1 1\nKs 0 0 0\n\
+                     d 1\nillum 2\nmap_Kd color.png\nmap_Pr rugosidad.png\n";
+        assert_eq!(parse_mtl(mapas).unwrap().len(), 1);
+
+        assert!(parse_mtl("newmtl malo\nillum 11\n").is_err());
+        assert!(parse_mtl("newmtl malo\nTf 1 1\n").is_err());
+    }
+
+    #[test]
+    fn emisor_no_refleja() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let bsdf = Material::default().bsdf(&normal, (0.0, 0.0));
+
+        assert!(bsdf.sample_f(&normal, rand::random()).is_none());
+    }
+
+    #[test]
+    fn fresnel_de_vidrio() {
+        // a incidencia normal R = ((n - 1) / (n + 1))²
+        assert_eq_float!(fresnel_dielectric(1.0, 1.0, 1.5), 0.04);
+        assert_eq_float!(fresnel_dielectric(-1.0, 1.0, 1.5), 0.04);
+
+        // desde adentro, pasando el ángulo crítico, se refleja todo
+        assert_eq_float!(fresnel_dielectric(0.5, 1.5, 1.0), 1.0);
+    }
+
+    #[test]
+    fn fresnel_de_metales() {
+        let cerca = |a: f32, b: f64| (a as f64 - b).abs() < 1e-5;
+
+        // a incidencia normal R = ((n - 1)² + k²) / ((n + 1)² + k²)
+        let (n, k) = (0.2, 3.0);
+        let r = fresnel_conductor(
+            1.0,
+            1.0,
+            &SampledSpectrum::new(n),
+            &SampledSpectrum::new(k),
+        );
+        let esperado =
+            ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+        assert!(cerca(r.max_value(), esperado as f64));
+
+        // sin absorción es un dieléctrico
+        for cos in [1.0, 0.7, 0.2] {
+            let r = fresnel_conductor(
+                cos,
+                1.0,
+                &SampledSpectrum::new(1.5),
+                &SampledSpectrum::new(0.0),
+            );
+            assert!(cerca(r.max_value(), fresnel_dielectric(cos, 1.0, 1.5)));
+        }
+
+        // en el ángulo rasante refleja todo
+        let (eta, k) = SampledSpectrum::metal_ior("Gold").unwrap();
+        let r = fresnel_conductor(0.0, 1.0, &eta, &k);
+        assert!(cerca(r.max_value(), 1.0));
+    }
+
+    #[test]
+    fn el_oro_es_amarillo() {
+        // refleja mucho más el rojo que el azul, y el aluminio es casi gris
+        let reflectancia = |metal: &str| {
+            let material = Material {
+                tipo: Type::Conductor,
+                complex_ior: SampledSpectrum::metal_ior(metal),
+                ..Default::default()
+            };
+            let wo = Vector::new(0.0, 0.0, 1.0);
+            let bsdf = material.bsdf(&wo, (0.0, 0.0));
+            let muestra = bsdf.sample_f(&wo, (0.5, 0.5)).unwrap();
+            assert!(muestra.specular);
+
+            // a incidencia normal el coseno vale 1
+            (0..N_SAMPLES)
+                .map(|i| muestra.f.only_sample(i).max_value())
+                .collect::<Vec<_>>()
+        };
+
+        let oro = reflectancia("Gold");
+        let (azul, rojo) = (oro[0], oro[N_SAMPLES - 1]);
+        assert!(rojo > 0.9 && azul < 0.5);
+
+        let aluminio = reflectancia("Aluminium");
+        assert!(aluminio.iter().all(|r| (0.85..0.95).contains(r)));
+
+        assert!(SampledSpectrum::metal_ior("Kryptonita").is_none());
+    }
+}
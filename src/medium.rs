@@ -0,0 +1,93 @@
+use crate::spectrum::{SampledSpectrum, N_SAMPLES};
+
+/// Medio homogéneo adentro de un objeto cerrado, como la cera, el mármol o
+/// la leche. La luz choca con sus partículas cada tanto, y en cada choque
+/// se dispersa para cualquier lado por igual o se absorbe.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Medium {
+    sigma_t: SampledSpectrum, // choques por unidad de distancia
+    albedo: SampledSpectrum,  // fracción de los choques que dispersa
+}
+
+impl Medium {
+    /// mean_free_path es la distancia media entre choques para cada
+    /// longitud de onda, y albedo la fracción de la luz que se dispersa en
+    /// cada choque. Se recortan a valores válidos, porque los espectros que
+    /// salen de un RGB pueden ser un poco negativos o pasarse de uno.
+    pub fn new(
+        mean_free_path: SampledSpectrum,
+        albedo: SampledSpectrum,
+    ) -> Self {
+        let mut sigma_t = SampledSpectrum::new(0.0);
+        let mut albedo = albedo;
+        for i in 0..N_SAMPLES {
+            sigma_t[i] = 1.0 / mean_free_path[i].max(1e-6);
+            albedo[i] = albedo[i].clamp(0.0, 1.0);
+        }
+
+        Self { sigma_t, albedo }
+    }
+
+    /// Fracción de la luz que recorre la distancia d sin chocar.
+    pub fn tr(&self, d: f64) -> SampledSpectrum {
+        (self.sigma_t * -d as f32).exp()
+    }
+
+    /// Elige a qué distancia choca la luz que recorre el medio hasta t_max.
+    /// Devuelve la distancia, o None si llega a t_max sin chocar, y el peso
+    /// del tramo. La distancia se elige con una longitud de onda al azar,
+    /// elegida con u_c, pero la densidad es el promedio de todas para que
+    /// ninguna quede con mucho ruido.
+    pub fn sample(
+        &self,
+        t_max: f64,
+        u_c: f64,
+        u: f64,
+    ) -> (Option<f64>, SampledSpectrum) {
+        let i = ((u_c * N_SAMPLES as f64) as usize).min(N_SAMPLES - 1);
+        let t = -(1.0 - u).ln() / self.sigma_t[i] as f64;
+
+        if t < t_max {
+            let tr = self.tr(t);
+            let pdf = (self.sigma_t * tr).average() as f64;
+
+            (Some(t), self.albedo * self.sigma_t * tr / pdf as f32)
+        } else {
+            let tr = self.tr(t_max);
+
+            (None, tr / tr.average())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn muestras_sin_sesgo() {
+        // una longitud de onda choca mucho más que las otras
+        let mut mfp = SampledSpectrum::new(1.0);
+        mfp[0] = 0.1;
+        let medio = Medium::new(mfp, SampledSpectrum::new(0.8));
+
+        // el peso de los que pasan es la transmitancia, y el de los que
+        // chocan lo que se dispersa
+        let d = 0.5;
+        let n = 100_000;
+        let (mut pasa, mut choca) = (0.0, 0.0);
+        for _ in 0..n {
+            match medio.sample(d, rand::random(), rand::random()) {
+                (None, peso) => pasa += peso[0] as f64,
+                (Some(t), peso) => {
+                    assert!(t < d);
+                    choca += peso[0] as f64;
+                }
+            }
+        }
+
+        let tr = medio.tr(d)[0] as f64;
+        assert!((pasa / n as f64 - tr).abs() < 0.01);
+        assert!((choca / n as f64 - 0.8 * (1.0 - tr)).abs() < 0.02);
+    }
+}
@@ -39,7 +39,6 @@ impl<'a> ThreadPool<'a> {
         F: FnOnce(),
     {
         let threads_count = num_cpus::get();
-        println!("Using {} threads.", threads_count);
 
         thread::scope(|s| {
             let _handlers: Vec<_> = (0..threads_count)
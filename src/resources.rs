@@ -0,0 +1,84 @@
+use crate::material::Material;
+use crate::texture::MipMap;
+
+/// Índice de un material en los recursos de la escena.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaterialId(usize);
+
+/// Índice de una imagen en los recursos de la escena.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageId(usize);
+
+/// Lo que los materiales de una escena usan por índice, como los materiales
+/// de los Mix y las imágenes de las texturas, para que Material no se
+/// contenga a sí mismo y siga siendo Copy. Los carga la escena al leerse y
+/// se liberan con ella.
+#[derive(Debug, Default)]
+pub struct Resources {
+    materiales: Vec<Material>,
+    // con el nombre del archivo y si es sRGB, para no cargar dos veces la
+    // misma
+    imágenes: Vec<(String, bool, MipMap)>,
+}
+
+/// Recursos sin nada, para los choques y coordenadas que no vienen de una
+/// escena.
+static VACÍOS: Resources = Resources::new();
+
+impl Resources {
+    pub const fn new() -> Resources {
+        Resources {
+            materiales: Vec::new(),
+            imágenes: Vec::new(),
+        }
+    }
+
+    pub fn empty() -> &'static Resources {
+        &VACÍOS
+    }
+
+    /// Guarda una copia del material.
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.materiales.push(material);
+
+        MaterialId(self.materiales.len() - 1)
+    }
+
+    pub fn material(&self, id: MaterialId) -> &Material {
+        &self.materiales[id.0]
+    }
+
+    /// Carga la imagen del archivo, o devuelve la que ya estaba cargada.
+    pub fn load_image(
+        &mut self,
+        archivo: &str,
+        srgb: bool,
+    ) -> Result<ImageId, anyhow::Error> {
+        let cargada = self
+            .imágenes
+            .iter()
+            .position(|(nombre, s, _)| nombre == archivo && *s == srgb);
+        if let Some(i) = cargada {
+            return Ok(ImageId(i));
+        }
+
+        let mipmap = MipMap::open(archivo, srgb)?;
+        Ok(self.add_image(archivo, srgb, mipmap))
+    }
+
+    /// Guarda una imagen que no viene de un archivo, con el nombre dado.
+    pub fn add_image(
+        &mut self,
+        nombre: &str,
+        srgb: bool,
+        mipmap: MipMap,
+    ) -> ImageId {
+        self.imágenes.push((nombre.to_string(), srgb, mipmap));
+
+        ImageId(self.imágenes.len() - 1)
+    }
+
+    pub fn image(&self, id: ImageId) -> &MipMap {
+        &self.imágenes[id.0].2
+    }
+}
@@ -0,0 +1,236 @@
+use crate::geometry::Vector;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::hash_map::DefaultHasher;
+use std::f64::consts::PI;
+use std::hash::{Hash, Hasher};
+
+/// Distribución discreta por partes, para elegir un elemento con
+/// probabilidad proporcional a su peso.
+#[derive(Clone, Debug, Default)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    func_int: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: &[f64]) -> Distribution1D {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + func[i - 1].abs();
+        }
+
+        let func_int = cdf[n];
+
+        if func_int == 0.0 {
+            // todos los pesos son cero, uso una distribución uniforme
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= func_int;
+            }
+        }
+
+        Distribution1D {
+            func: func.to_vec(),
+            cdf,
+            func_int,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Elige un índice usando u en [0, 1). Devuelve el índice, su
+    /// probabilidad y u reescalado a [0, 1) dentro del intervalo elegido,
+    /// para poder reusarlo.
+    pub fn sample_discrete(&self, u: f64) -> (usize, f64, f64) {
+        // busco el último i tal que cdf[i] <= u
+        let i = self
+            .cdf
+            .partition_point(|c| *c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+
+        let ancho = self.cdf[i + 1] - self.cdf[i];
+        let u_remapped = if ancho > 0.0 {
+            ((u - self.cdf[i]) / ancho).min(1.0 - f64::EPSILON)
+        } else {
+            0.0
+        };
+
+        (i, self.discrete_pdf(i), u_remapped)
+    }
+
+    /// Probabilidad de elegir el índice i.
+    pub fn discrete_pdf(&self, i: usize) -> f64 {
+        if self.func_int == 0.0 {
+            1.0 / self.count() as f64
+        } else {
+            self.func[i].abs() / self.func_int
+        }
+    }
+}
+
+/// Fuente de los números en [0, 1) que usa un integrador para construir un
+/// camino. Cambiando el sampler se puede controlar cómo se eligen, por
+/// ejemplo para mutar un camino en vez de generar uno nuevo.
+pub trait Sampler {
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Sampler con números independientes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomSampler;
+
+impl Sampler for RandomSampler {
+    fn get_1d(&mut self) -> f64 {
+        rand::random()
+    }
+}
+
+/// Devuelve un versor distribuido uniformemente en la esfera unitaria.
+pub fn uniform_sample_sphere((u_1, u_2): (f64, f64)) -> Vector {
+    let z = 1.0 - 2.0 * u_1;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u_2;
+
+    Vector::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Devuelve coordenadas baricéntricas (b_0, b_1) distribuidas uniformemente
+/// en el área de un triángulo.
+pub fn uniform_sample_triangle((u_1, u_2): (f64, f64)) -> (f64, f64) {
+    let raíz = u_1.sqrt();
+
+    (1.0 - raíz, u_2 * raíz)
+}
+
+/// Punto i de un conjunto de Hammersley de n puntos en [0, 1)², corrido para
+/// que con un solo punto quede en el centro.
+pub fn hammersley(i: usize, n: usize) -> (f64, f64) {
+    // inversa radical en base 2: espejo los bits de i después de la coma
+    let inversa = (i as u32).reverse_bits() as f64 / (1u64 << 32) as f64;
+
+    (
+        (i as f64 + 0.5) / n as f64,
+        (inversa + 0.5 / n as f64).fract(),
+    )
+}
+
+fn hash(valores: &[f64]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    valores.iter().for_each(|v| v.to_bits().hash(&mut hasher));
+
+    hasher.finish()
+}
+
+/// Número en [0, 1) que depende solo de los valores dados. Sirve para elegir
+/// al azar sin el sampler y que la elección se repita cada vez que se evalúa
+/// el mismo punto.
+pub fn hash_float(valores: &[f64]) -> f64 {
+    (hash(valores) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Generador de números al azar con la semilla sacada de los valores dados.
+pub fn hash_rng(valores: &[f64]) -> StdRng {
+    StdRng::seed_from_u64(hash(valores))
+}
+
+/// Heurística de la potencia (con exponente 2) para multiple importance
+/// sampling. n_f muestras de la estrategia f con densidad f_pdf, y n_g de g.
+#[inline]
+pub fn power_heuristic(n_f: usize, f_pdf: f64, n_g: usize, g_pdf: f64) -> f64 {
+    let f = n_f as f64 * f_pdf;
+    let g = n_g as f64 * g_pdf;
+
+    if f.is_infinite() {
+        return 1.0;
+    }
+
+    if f == 0.0 && g == 0.0 {
+        return 0.0;
+    }
+
+    (f * f) / (f * f + g * g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_float;
+
+    #[test]
+    fn distribución_elige_según_pesos() {
+        let dist = Distribution1D::new(&[1.0, 0.0, 3.0]);
+
+        assert_eq!(dist.sample_discrete(0.1).0, 0);
+        assert_eq!(dist.sample_discrete(0.3).0, 2);
+        assert_eq!(dist.sample_discrete(0.999).0, 2);
+
+        assert_eq_float!(dist.discrete_pdf(0), 0.25);
+        assert_eq_float!(dist.discrete_pdf(1), 0.0);
+        assert_eq_float!(dist.discrete_pdf(2), 0.75);
+
+        // u reescalado dentro del intervalo [0.25, 1)
+        assert_eq_float!(dist.sample_discrete(0.625).2, 0.5);
+    }
+
+    #[test]
+    fn distribución_con_pesos_nulos_es_uniforme() {
+        let dist = Distribution1D::new(&[0.0, 0.0]);
+
+        assert_eq!(dist.sample_discrete(0.2).0, 0);
+        assert_eq!(dist.sample_discrete(0.7).0, 1);
+        assert_eq_float!(dist.discrete_pdf(1), 0.5);
+    }
+
+    #[test]
+    fn muestra_de_esfera_es_unitaria() {
+        for _ in 0..100 {
+            let v = uniform_sample_sphere(rand::random());
+            assert_eq_float!(v.norm(), 1.0);
+        }
+    }
+
+    #[test]
+    fn power_heuristic_suma_uno() {
+        let a = power_heuristic(1, 0.3, 1, 1.2);
+        let b = power_heuristic(1, 1.2, 1, 0.3);
+
+        assert_eq_float!(a + b, 1.0);
+        assert_eq_float!(power_heuristic(1, f64::INFINITY, 1, 1.0), 1.0);
+    }
+
+    #[test]
+    fn hammersley_queda_en_el_cuadrado() {
+        assert_eq!(hammersley(0, 1), (0.5, 0.5));
+        assert_eq!(hammersley(1, 4), (0.375, 0.625));
+
+        for i in 0..64 {
+            let (x, y) = hammersley(i, 64);
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+        }
+    }
+
+    #[test]
+    fn hash_float_se_repite() {
+        assert_eq!(hash_float(&[1.0, 2.0]), hash_float(&[1.0, 2.0]));
+        assert_ne!(hash_float(&[1.0, 2.0]), hash_float(&[2.0, 1.0]));
+
+        // y reparte los valores parejo en [0, 1)
+        let n = 10_000;
+        let suma: f64 = (0..n).map(|i| hash_float(&[i as f64])).sum();
+        assert!((suma / n as f64 - 0.5).abs() < 0.01);
+    }
+}
@@ -1,151 +1,492 @@
-use crate::geometry::Ray;
-use crate::material::Type;
+use crate::accelerators::BVH;
+use crate::geometry::{sample_versor_cos_density, Normal, Point, Ray, Vector};
+use crate::material::Material;
+use crate::resources::Resources;
+use crate::sampling::Distribution1D;
 use crate::shapes::{Intersection, Shape, ShapeOperations};
 use crate::spectrum::SampledSpectrum;
+use std::sync::Arc;
 
 #[derive(Clone, Debug)]
 pub struct Scene {
     objetos: Vec<Shape>,
+    bvh: Option<BVH>, // se construye al terminar de cargar la escena
+    luces: Vec<usize>, // índices de los objetos que emiten luz
+    materiales: Vec<Material>, // los distintos materiales de los objetos
+    recursos: Arc<Resources>, // lo que los materiales usan por índice
+}
+
+/// Punto elegido sobre una fuente de luz, visto desde un punto de referencia.
+/// wi es el versor desde la referencia hacia la luz, y pdf es la densidad
+/// respecto al ángulo sólido, incluyendo la probabilidad de elegir esa luz.
+#[derive(Clone, Copy, Debug)]
+pub struct LightSample {
+    pub punto: Point,
+    pub normal: Normal,
+    pub wi: Vector,
+    pub emitted: SampledSpectrum,
+    pub pdf: f64,
+}
+
+/// Rayo de luz que sale de un punto elegido sobre una fuente de luz, para
+/// trazar caminos desde las luces. pdf_pos es la densidad del punto respecto
+/// al área, y pdf_dir la de la dirección respecto al ángulo sólido.
+#[derive(Clone, Copy, Debug)]
+pub struct EmissionSample {
+    pub punto: Point,
+    pub normal: Normal,
+    pub rayo: Ray,
+    pub emitted: SampledSpectrum,
+    pub pdf_pos: f64,
+    pub pdf_dir: f64,
 }
 
 impl Scene {
     pub fn new() -> Scene {
         Scene {
             objetos: Vec::new(),
+            bvh: None,
+            luces: Vec::new(),
+            materiales: Vec::new(),
+            recursos: Arc::default(),
         }
     }
 
+    /// Guarda lo que usan por índice los materiales de los objetos.
+    pub fn set_resources(&mut self, recursos: Resources) {
+        self.recursos = Arc::new(recursos);
+    }
+
     pub fn add_shape(&mut self, objeto: &Shape) -> Result<(), anyhow::Error> {
+        if objeto.material().emitted().is_some() {
+            self.luces.push(self.objetos.len());
+        }
+        if !self.materiales.contains(objeto.material()) {
+            self.materiales.push(*objeto.material());
+        }
         self.objetos.push(objeto.clone());
+        // la jerarquía ya no es válida
+        self.bvh = None;
         Ok(())
     }
 
+    /// Índice del material en la lista de materiales distintos de la
+    /// escena, en el orden en que se agregaron los objetos.
+    pub fn material_index(&self, material: &Material) -> Option<usize> {
+        self.materiales.iter().position(|m| m == material)
+    }
+
+    /// Construye la BVH sobre los objetos de la escena. Hay que llamarlo una
+    /// vez que se agregaron todos los objetos, si no las intersecciones se
+    /// calculan probando contra cada objeto.
+    pub fn build_bvh(&mut self) {
+        self.objetos.iter_mut().for_each(Shape::build_bvh);
+        self.bvh = Some(BVH::new(&mut self.objetos));
+
+        // la BVH reordena los objetos
+        self.luces = self
+            .objetos
+            .iter()
+            .enumerate()
+            .filter(|(_, obj)| obj.material().emitted().is_some())
+            .map(|(i, _)| i)
+            .collect();
+    }
+
+    /// Cantidad de fuentes de luz de la escena.
+    pub fn light_count(&self) -> usize {
+        self.luces.len()
+    }
+
+    /// El objeto de la luz número luz.
+    pub fn light(&self, luz: usize) -> &Shape {
+        &self.objetos[self.luces[luz]]
+    }
+
+    /// Potencia emitida por la luz número luz, usando la luminancia de la
+    /// radiancia. La luz emite para los dos lados: 2.π.L.A
+    pub fn light_power(&self, luz: usize) -> f64 {
+        let objeto = self.light(luz);
+        let radiancia = objeto.material().emitted().map_or(0.0, |e| e.y());
+
+        2.0 * std::f64::consts::PI * radiancia as f64 * objeto.area()
+    }
+
+    /// Distribución para elegir las luces proporcionalmente a su potencia.
+    pub fn light_power_distribution(&self) -> Distribution1D {
+        let potencias: Vec<f64> = (0..self.light_count())
+            .map(|luz| self.light_power(luz))
+            .collect();
+
+        Distribution1D::new(&potencias)
+    }
+
+    /// Elige un punto sobre la luz número luz con u_pos, y una dirección de
+    /// salida con densidad coseno con u_dir, del lado que elige u_lado.
+    pub fn sample_emission(
+        &self,
+        luz: usize,
+        u_pos: (f64, f64),
+        u_dir: (f64, f64),
+        u_lado: f64,
+    ) -> Option<EmissionSample> {
+        let objeto = self.light(luz);
+        let emitted = objeto.material().emitted()?;
+
+        let (punto, normal) = objeto.sample(u_pos);
+        let pdf_pos = objeto.pdf(&punto);
+
+        // la luz emite para los dos lados
+        let lado = if u_lado < 0.5 { normal } else { -normal };
+        let dir = sample_versor_cos_density(&lado, u_dir);
+        let pdf_dir = dir.dot(&lado) / (2.0 * std::f64::consts::PI);
+
+        if pdf_pos == 0.0 || pdf_dir <= 0.0 {
+            return None;
+        }
+
+        Some(EmissionSample {
+            punto,
+            normal,
+            rayo: Ray::new(&(punto + lado * 1e-9), &dir, f64::INFINITY),
+            emitted,
+            pdf_pos,
+            pdf_dir,
+        })
+    }
+
+    /// Elige una fuente de luz uniformemente con u_luz y un punto sobre ella
+    /// con u. Devuelve None si no hay luces o si el punto no se ve desde la
+    /// referencia (no se fija si hay algo en el medio).
+    pub fn sample_light(
+        &self,
+        referencia: &Point,
+        u_luz: f64,
+        u: (f64, f64),
+    ) -> Option<LightSample> {
+        if self.luces.is_empty() {
+            return None;
+        }
+
+        let n = self.luces.len();
+        let luz = ((u_luz * n as f64) as usize).min(n - 1);
+
+        let mut muestra = self.sample_light_at(luz, referencia, u)?;
+        muestra.pdf /= n as f64;
+
+        Some(muestra)
+    }
+
+    /// Elige un punto sobre la luz número luz con u. El pdf no incluye la
+    /// probabilidad de haber elegido esa luz.
+    pub fn sample_light_at(
+        &self,
+        luz: usize,
+        referencia: &Point,
+        u: (f64, f64),
+    ) -> Option<LightSample> {
+        let objeto = self.light(luz);
+
+        let (punto, normal) = objeto.sample(u);
+        let wi = punto - referencia;
+        let distancia_2 = wi.norm_squared();
+        if distancia_2 == 0.0 {
+            return None;
+        }
+        let wi = wi / distancia_2.sqrt();
+
+        // la luz emite para los dos lados
+        let cos = normal.dot(&wi).abs();
+        if cos == 0.0 {
+            return None;
+        }
+
+        // paso la densidad del área al ángulo sólido
+        let pdf = objeto.pdf(&punto) * distancia_2 / cos;
+
+        Some(LightSample {
+            punto,
+            normal,
+            wi,
+            emitted: objeto.material().emitted()?,
+            pdf,
+        })
+    }
+
+    /// Número de luz del objeto chocado, si es una luz.
+    pub fn light_index(&self, choque: &Intersection) -> Option<usize> {
+        let índice = choque.shape_index()?;
+
+        self.luces.iter().position(|&i| i == índice)
+    }
+
+    /// Densidad, respecto al ángulo sólido, con la que sample_light() habría
+    /// elegido el punto del choque visto desde la referencia. Es 0 si lo que
+    /// se chocó no es una luz.
+    pub fn light_pdf(&self, referencia: &Point, choque: &Intersection) -> f64 {
+        match self.light_index(choque) {
+            Some(luz) => {
+                self.light_pdf_at(luz, referencia, choque)
+                    / self.luces.len() as f64
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Densidad, respecto al ángulo sólido, con la que sample_light_at()
+    /// habría elegido el punto del choque sobre la luz número luz.
+    pub fn light_pdf_at(
+        &self,
+        luz: usize,
+        referencia: &Point,
+        choque: &Intersection,
+    ) -> f64 {
+        let wi = choque.point() - referencia;
+        let cos = choque.normal().dot(&wi.normalize()).abs();
+        if cos == 0.0 {
+            return 0.0;
+        }
+
+        self.light(luz).pdf(choque.point()) * wi.norm_squared() / cos
+    }
+
     fn trace_ray(&self, rayo: &Ray, iteraciones: usize) -> SampledSpectrum {
         if iteraciones == 0 {
             return SampledSpectrum::new(0.0);
         }
 
+        // el BSDF sabe de qué lado de la normal llega el rayo
         match self.intersect_ray(rayo) {
-            Some(mut choque) => {
-                if choque.normal().dot(rayo.dir()) > 0.0 {
-                    // la normal apunta para "adentro" del objeto
-                    choque.invert_normal()
-                } else {
-                    // apunta para afuera
-                    // no hago nada por ahora
-                }
-
-                // devuelvo el color en el punto
-                self.shade_point(&choque, iteraciones)
-            }
+            Some(choque) => self.shade_point(&choque, iteraciones),
             None => SampledSpectrum::new(0.0),
         }
     }
 
+    /// Color que sale del punto hacia el rayo incidente: la luz que emite
+    /// el material o, si no emite, la luz que llega por una dirección elegida
+    /// con su BSDF.
     pub fn shade_point(
         &self,
         choque: &Intersection,
         iteraciones: usize,
     ) -> SampledSpectrum {
-        let objeto = choque.model();
-        let punto = choque.point();
-        let incidente = choque.incident_ray().dir();
-        let normal = choque.normal();
-
-        match objeto.material().tipo {
-            Type::Emitter => {
-                if let Some(col) = objeto.material().emitted_color {
-                    col
-                } else {
-                    SampledSpectrum::new(0.0)
-                }
-            }
-            Type::Lambertian => {
-                let dirección =
-                    crate::geometry::random_versor_cos_density(normal);
-                let rayo = Ray::new(
-                    &(punto + normal * 1e-10),
-                    &dirección,
-                    std::f64::INFINITY,
-                );
+        if let Some(col) = choque.material().emitted() {
+            return col;
+        }
 
-                if let Some(col) = objeto.material().ambient_color {
-                    //sumar_colores(&self.trazar_rayo(&rayo, iteraciones - 1),
-                    //              &col)
-                    self.trace_ray(&rayo, iteraciones - 1) * col
-                } else {
-                    SampledSpectrum::new(0.0)
-                }
-            }
-            Type::Specular => {
-                let color = if let Some(col) = objeto.material().specular_color
-                {
-                    col
-                } else {
-                    SampledSpectrum::new(1.0)
-                };
-
-                // si i es el rayo incidente, n es la normal, y r el reflejado
-                // respecto a esa normal, entonces r = i + 2.a, 2.a es la
-                // diferencia entre ambos vectores.
-                // a tiene dirección de n y módulo i*cos(angulo(i,n)). o sea
-                // a = <d, n>.n
-                // Asumo que n viene normalizado
-                let dirección =
-                    incidente - normal * (2.0 * incidente.dot(normal));
-
-                let rayo = Ray::new(
-                    &(punto + normal * 1e-10),
-                    &dirección,
-                    std::f64::INFINITY,
-                );
+        let wo = choque.direction_out();
+        let muestra = match choque.bsdf().sample_f(wo, rand::random()) {
+            Some(m) if m.pdf > 0.0 && !m.f.is_black() => m,
+            _ => return SampledSpectrum::new(0.0),
+        };
+
+        let cos = muestra.wi.dot(choque.normal()).abs();
+        let rayo = choque.spawn_ray(&muestra.wi);
+
+        self.trace_ray(&rayo, iteraciones - 1)
+            * muestra.f
+            * (cos / muestra.pdf) as f32
+    }
 
-                //let tita = normal.dot(&dirección);
-                // Aproximación de Schlick a las ecuaciones de Fresnel
-                // R(t) = R_0 + (1 - R_0)*(1 - cos(t))⁵
-                //let color =
-                //    color.map(|r| r + (1.0 - r) * (1.0 - tita.cos()).powi(5));
+    // Si el rayo choca contra algo, devuelve el coso chocado y el t a evaluar
+    // en el rayo para el choque.
+    pub fn intersect_ray(&self, rayo: &Ray) -> Option<Intersection<'_>> {
+        let (índice, mut choque) = match &self.bvh {
+            Some(bvh) => bvh.intersect(&self.objetos, rayo)?,
+            None => self.intersect_ray_linear(rayo)?,
+        };
+
+        choque.set_shape_index(índice);
+        choque.set_resources(&self.recursos);
+        Some(choque)
+    }
+
+    /// Devuelve true si el rayo choca algún objeto antes de su max_t. Es más
+    /// barato que intersect_ray porque no busca el choque más cercano, sirve
+    /// para los shadow rays.
+    pub fn is_intersecting(&self, rayo: &Ray) -> bool {
+        match &self.bvh {
+            Some(bvh) => bvh.is_intersecting(&self.objetos, rayo),
+            None => self.objetos.iter().any(|obj| obj.is_intersecting(rayo)),
+        }
+    }
 
-                //sumar_colores(&self.trazar_rayo(&rayo, iteraciones - 1),
-                //              &color)
-                self.trace_ray(&rayo, iteraciones - 1) * color
+    /// Devuelve true si no hay nada entre los dos puntos.
+    pub fn unoccluded(&self, p_0: &Point, p_1: &Point) -> bool {
+        !self.is_intersecting(&Ray::between(p_0, p_1))
+    }
+
+    /// Prueba el rayo contra todos los objetos de la escena.
+    fn intersect_ray_linear(
+        &self,
+        rayo: &Ray,
+    ) -> Option<(usize, Intersection<'_>)> {
+        // el objeto más cercano que atraviesa el rayo, cada choque achica el
+        // intervalo de búsqueda para los objetos siguientes
+        let mut rayo = *rayo;
+        let mut menor = None;
+
+        for (i, obj) in self.objetos.iter().enumerate() {
+            if let Some(choque) = obj.intersect(&rayo) {
+                rayo = rayo.with_max_t(choque.t());
+                menor = Some((i, choque));
             }
         }
 
-        // por ahora saco los shadow rays
-        /*
-        for luz in &self.luces {
-            let dirección = luz.fuente() - punto;
-            // corro el origen del rayo para que no choque con el objeto que
-            // quiero sombrear
-            let rayo = Rayo::new(&(punto + normal * 1e-10), &dirección);
-            let obstáculo = self.intersecar_rayo(&rayo);
+        menor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_float;
+    use crate::geometry::{create_translation, Transform, Vector};
+    use crate::material::Material;
+    use crate::shapes::{Sphere, Triangle};
+
+    fn random_point(scale: f64) -> Point {
+        Point::new(
+            scale * (rand::random::<f64>() - 0.5),
+            scale * (rand::random::<f64>() - 0.5),
+            scale * (rand::random::<f64>() - 0.5),
+        )
+    }
+
+    fn random_scene() -> Scene {
+        let mut scene = Scene::new();
+
+        for i in 0..400 {
+            let centro = random_point(40.0);
+            let objeto: Shape = if i % 2 == 0 {
+                Sphere::new(
+                    &create_translation(&centro.coords),
+                    0.5 * rand::random::<f64>(),
+                    &Material::default(),
+                )
+                .into()
+            } else {
+                Triangle::new(
+                    &(centro + random_point(2.0).coords),
+                    &(centro + random_point(2.0).coords),
+                    &(centro + random_point(2.0).coords),
+                    &Transform::identity(),
+                    &Material::default(),
+                )
+                .into()
+            };
+            scene.add_shape(&objeto).unwrap();
+        }
+
+        scene
+    }
+
+    #[test]
+    fn bvh_encuentra_el_mismo_choque_que_fuerza_bruta() {
+        let mut scene = random_scene();
+        scene.build_bvh();
+
+        for _ in 0..500 {
+            let rayo = Ray::new(
+                &random_point(60.0),
+                &random_point(1.0).coords,
+                f64::INFINITY,
+            );
+
+            let esperado = scene.intersect_ray_linear(&rayo);
+            let obtenido = scene.intersect_ray(&rayo);
 
-            if obstáculo.is_none() {
-                if let Some(col) = objeto.material().color_ambiente {
-                    colores.push(col * luz.atenuación(punto));
-                }
+            assert_eq!(esperado.is_some(), obtenido.is_some());
+            assert_eq!(esperado.is_some(), scene.is_intersecting(&rayo));
+            if let (Some((i, esperado)), Some(obtenido)) = (esperado, obtenido)
+            {
+                assert_eq!(obtenido.shape_index(), Some(i));
+                assert_eq_float!(esperado.t(), obtenido.t());
+                assert_eq_float!(
+                    (esperado.point() - obtenido.point()).norm(),
+                    0.0
+                );
             }
         }
-        */
     }
 
-    // Si el rayo choca contra algo, devuelve el coso chocado y el t a evaluar
-    // en el rayo para el choque.
-    pub fn intersect_ray(&self, rayo: &Ray) -> Option<Intersection> {
-        // el objeto más cercano que atraviesa el rayo
-        let menor = self
-            .objetos
+    #[test]
+    fn agregar_objeto_invalida_la_bvh() {
+        let mut scene = Scene::new();
+        scene.build_bvh();
+
+        let esfera = Sphere::new(
+            &create_translation(&Vector::new(5.0, 0.0, 0.0)),
+            1.0,
+            &Material::default(),
+        );
+        scene.add_shape(&esfera.into()).unwrap();
+
+        let rayo = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        assert!(scene.intersect_ray(&rayo).is_some());
+    }
+
+    #[test]
+    fn shadow_ray_respeta_max_t() {
+        let mut scene = Scene::new();
+        let esfera = Sphere::new(
+            &create_translation(&Vector::new(5.0, 0.0, 0.0)),
+            1.0,
+            &Material::default(),
+        );
+        scene.add_shape(&esfera.into()).unwrap();
+        scene.build_bvh();
+
+        let origen = Point::origin();
+
+        // la esfera está detrás del punto
+        assert!(scene.unoccluded(&origen, &Point::new(3.0, 0.0, 0.0)));
+        // la esfera está en el medio
+        assert!(!scene.unoccluded(&origen, &Point::new(7.0, 0.0, 0.0)));
+        // el rayo termina justo en la superficie
+        assert!(scene.unoccluded(&origen, &Point::new(4.0, 0.0, 0.0)));
+    }
+
+    /// Mide el tiempo promedio de intersecar un rayo contra una escena con
+    /// el mono. Correr con
+    /// `cargo test --release -- --ignored --nocapture benchmark`.
+    #[test]
+    #[ignore]
+    fn benchmark_intersect_ray() {
+        use crate::shapes::ModelObj;
+
+        let mut scene = Scene::new();
+        let mono = ModelObj::new("mono.obj").unwrap();
+        let centro = mono.bounding_box().centroid();
+        scene.add_shape(&mono.into()).unwrap();
+        scene.build_bvh();
+
+        let rayos: Vec<Ray> = (0..200_000)
+            .map(|_| {
+                let origen =
+                    centro + random_point(1.0).coords.normalize() * 5.0;
+                let objetivo = centro + random_point(1.0).coords;
+                Ray::new(&origen, &(objetivo - origen), f64::INFINITY)
+            })
+            .collect();
+
+        let inicio = std::time::Instant::now();
+        let choques = rayos
             .iter()
-            .filter_map(|obj| obj.intersect(rayo))
-            .reduce(|menor, actual| {
-                if actual.t() < menor.t() {
-                    actual
-                } else {
-                    menor
-                }
-            });
+            .filter(|rayo| scene.intersect_ray(rayo).is_some())
+            .count();
+        let duración = inicio.elapsed();
 
-        menor
+        println!(
+            "{} rayos, {} choques, {:.1} ns por rayo",
+            rayos.len(),
+            choques,
+            duración.as_nanos() as f64 / rayos.len() as f64
+        );
     }
 }
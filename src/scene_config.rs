@@ -1,11 +1,23 @@
 use crate::auxiliar;
+use crate::bsdf::{Dispersion, MicrofacetDistribution};
 use crate::camera::Camera;
 use crate::geometry::{self, Point, Transform, Vector};
-use crate::material::{self, Material};
+use crate::integrators::{
+    AlbedoIntegrator, AmbientOcclusionIntegrator, Aov, BDPTIntegrator,
+    DebugChannel, DebugIntegrator, DirectLightingIntegrator, Integrator,
+    LightStrategy, MLTIntegrator, MLTSettings, NormalIntegrator,
+    PathIntegrator, PhotonMapIntegrator, PhotonMapSettings,
+    RandomWalkIntegrator, SPPMIntegrator, WhittedIntegrator,
+};
+use crate::material::{self, Coat, Material, Mix, Principled};
+use crate::medium::Medium;
+use crate::resources::Resources;
 use crate::scene::Scene;
 use crate::shapes::{ModelObj, Sphere, Triangle};
 use crate::spectrum::{SampledSpectrum, SpectrumType};
+use crate::texture::{Filter, Texture, Wrap};
 use anyhow::Error;
+use std::collections::HashMap;
 use toml::{Table, Value};
 
 pub fn parse_file(path: &str) -> Result<Table, anyhow::Error> {
@@ -72,16 +84,238 @@ impl Camera {
     }
 }
 
+/// Lee la tabla [Integrator]. Si no está, usa un RandomWalkIntegrator.
+pub fn parse_integrator(
+    table: &Table,
+    camera: &Camera,
+    scene: &Scene,
+) -> Result<Integrator, anyhow::Error> {
+    let table = match table.get("Integrator") {
+        Some(Value::Table(t)) => t,
+        Some(_) => {
+            return Err(anyhow::anyhow!("No se pudo cargar el integrador."))
+        }
+        None => {
+            return Ok(RandomWalkIntegrator::new(camera, scene, 10, 100).into())
+        }
+    };
+
+    Integrator::from_toml(table, camera, scene)
+}
+
+/// Lee las salidas que se guardan además de la imagen final, de la lista
+/// aovs de la tabla [Output]. Si no está, guarda el albedo y las normales.
+pub fn parse_aovs(table: &Table) -> Result<Vec<Aov>, anyhow::Error> {
+    let error = || anyhow::anyhow!("No se pudieron cargar las salidas.");
+
+    let lista = match table.get("Output").map(|o| o.get("aovs")) {
+        Some(Some(Value::Array(lista))) => lista,
+        Some(None) | None => return Ok(vec![Aov::Albedo, Aov::Normal]),
+        Some(Some(_)) => return Err(error()),
+    };
+
+    let mut aovs = vec![];
+    for nombre in lista {
+        let nombre = nombre.as_str().ok_or(error())?;
+        let aov = Aov::from_name(nombre)
+            .ok_or(anyhow::anyhow!("Salida desconocida: {nombre}"))?;
+
+        // la imagen final se guarda siempre
+        if aov != Aov::Beauty && !aovs.contains(&aov) {
+            aovs.push(aov);
+        }
+    }
+
+    Ok(aovs)
+}
+
+impl Integrator {
+    fn from_toml(
+        table: &Table,
+        camera: &Camera,
+        scene: &Scene,
+    ) -> Result<Integrator, anyhow::Error> {
+        let error = || anyhow::anyhow!("No se pudo cargar el integrador.");
+
+        let get_usize = |key: &str, default: usize| match table.get(key) {
+            Some(v) => v
+                .as_integer()
+                .filter(|i| *i >= 0)
+                .map(|i| i as usize)
+                .ok_or(error()),
+            None => Ok(default),
+        };
+
+        let get_f64 = |key: &str, default: f64| match table.get(key) {
+            Some(v) => v
+                .as_float()
+                .or(v.as_integer().map(|i| i as f64))
+                .filter(|x| *x > 0.0)
+                .ok_or(error()),
+            None => Ok(default),
+        };
+
+        let samples = get_usize("samples", 100)?;
+
+        match table.get("type").and_then(|t| t.as_str()) {
+            Some("RandomWalk") => {
+                let max_depth = get_usize("max_depth", 10)?;
+                Ok(RandomWalkIntegrator::new(camera, scene, max_depth, samples)
+                    .into())
+            }
+            Some("Path") => {
+                // la ruleta rusa corta los caminos, el límite es por las dudas
+                let max_depth = get_usize("max_depth", 100)?;
+                Ok(PathIntegrator::new(camera, scene, max_depth, samples)
+                    .into())
+            }
+            Some("DirectLighting") => {
+                // solo se usa para seguir reflejos especulares
+                let max_depth = get_usize("max_depth", 5)?;
+                let strategy = match table
+                    .get("light_strategy")
+                    .map(|s| s.as_str().ok_or(error()))
+                    .transpose()?
+                {
+                    Some("all") | None => LightStrategy::SampleAll,
+                    Some("uniform") => LightStrategy::Uniform,
+                    Some("power") => LightStrategy::Power,
+                    Some(s) => {
+                        return Err(anyhow::anyhow!(
+                            "Estrategia de luces desconocida: {s}"
+                        ))
+                    }
+                };
+                Ok(DirectLightingIntegrator::new(
+                    camera, scene, strategy, max_depth, samples,
+                )
+                .into())
+            }
+            Some("BDPT") => {
+                let max_depth = get_usize("max_depth", 5)?;
+                Ok(BDPTIntegrator::new(camera, scene, max_depth, samples)
+                    .into())
+            }
+            Some("Whitted") => {
+                let max_depth = get_usize("max_depth", 10)?;
+                let light_samples = get_usize("light_samples", 4)?;
+                Ok(WhittedIntegrator::new(
+                    camera, scene, light_samples, max_depth, samples,
+                )
+                .into())
+            }
+            Some("PhotonMap") => {
+                let max_depth = get_usize("max_depth", 10)?;
+                let default = PhotonMapSettings::default();
+                let settings = PhotonMapSettings {
+                    photons: get_usize("photons", default.photons)?,
+                    gather_samples: get_usize(
+                        "gather_samples", default.gather_samples,
+                    )?,
+                    gather_photons: get_usize(
+                        "gather_photons", default.gather_photons,
+                    )?,
+                    radius: get_f64("radius", default.radius)?,
+                };
+                Ok(PhotonMapIntegrator::new(
+                    camera, scene, settings, max_depth, samples,
+                )
+                .into())
+            }
+            Some("SPPM") => {
+                // cada iteración es una muestra por píxel
+                let iterations = get_usize("iterations", 64)?;
+                let radius = get_f64("radius", 0.5)?;
+                let photons = get_usize("photons", 100_000)?;
+                let max_depth = get_usize("max_depth", 10)?;
+                Ok(SPPMIntegrator::new(
+                    camera, scene, radius, photons, max_depth, iterations,
+                )
+                .into())
+            }
+            Some("MLT") => {
+                // samples es la cantidad de mutaciones por píxel
+                let max_depth = get_usize("max_depth", 100)?;
+                let default = MLTSettings::default();
+                let settings = MLTSettings {
+                    bootstrap_samples: get_usize(
+                        "bootstrap_samples", default.bootstrap_samples,
+                    )?,
+                    chains: get_usize("chains", default.chains)?,
+                    large_step_probability: get_f64(
+                        "large_step_probability",
+                        default.large_step_probability,
+                    )?,
+                    sigma: get_f64("sigma", default.sigma)?,
+                };
+                Ok(MLTIntegrator::new(
+                    camera, scene, settings, max_depth, samples,
+                )
+                .into())
+            }
+            Some("AmbientOcclusion") => {
+                let ao_samples = get_usize("ao_samples", 16)?;
+                let max_distance = get_f64("max_distance", f64::INFINITY)?;
+                Ok(AmbientOcclusionIntegrator::new(
+                    camera, scene, ao_samples, max_distance, samples,
+                )
+                .into())
+            }
+            Some("Albedo") => {
+                Ok(AlbedoIntegrator::new(camera, scene, samples).into())
+            }
+            Some("Normal") => {
+                Ok(NormalIntegrator::new(camera, scene, samples).into())
+            }
+            Some("Debug") => {
+                let channel = match table
+                    .get("channel")
+                    .map(|s| s.as_str().ok_or(error()))
+                    .transpose()?
+                {
+                    Some("depth") | None => DebugChannel::Depth,
+                    Some("position") => DebugChannel::Position,
+                    Some("uv") => DebugChannel::UV,
+                    Some("shape") => DebugChannel::ShapeIndex,
+                    Some("material") => DebugChannel::MaterialIndex,
+                    Some(s) => {
+                        return Err(anyhow::anyhow!("Canal desconocido: {s}"))
+                    }
+                };
+                let false_color = match table.get("false_color") {
+                    Some(v) => v.as_bool().ok_or(error())?,
+                    None => true,
+                };
+                let scale = get_f64("scale", 1.0)?;
+                Ok(DebugIntegrator::new(
+                    camera, scene, channel, false_color, scale, samples,
+                )
+                .into())
+            }
+            Some(s) => Err(anyhow::anyhow!("Integrador desconocido: {s}")),
+            None => Err(error()),
+        }
+    }
+}
+
 pub fn parse_scene(table: &Table) -> Result<Scene, anyhow::Error> {
+    let mut recursos = Resources::new();
+    let named = parse_materials(table, &mut recursos)?;
     let table = table
         .get("Scene")
         .and_then(|s| s.as_array())
         .ok_or(anyhow::anyhow!("No se ha especificado la escena."))?;
-    Scene::from_toml(table)
+    Scene::from_toml(table, &named, recursos)
 }
 
 impl Scene {
-    pub fn from_toml(models: &Vec<Value>) -> Result<Scene, anyhow::Error> {
+    /// recursos son los que usan los materiales de named, y se les agregan
+    /// los de los materiales de los modelos.
+    pub fn from_toml(
+        models: &Vec<Value>,
+        named: &HashMap<String, Material>,
+        mut recursos: Resources,
+    ) -> Result<Scene, anyhow::Error> {
         let error = || anyhow::anyhow!("Error con la escena definida.");
 
         let mut scene = Scene::new();
@@ -90,11 +324,13 @@ impl Scene {
             let model = model.as_table().ok_or(error())?;
             match model.get("type").ok_or(error())?.as_str() {
                 Some("Sphere") => {
-                    let objeto = Sphere::from_toml(model)?;
+                    let objeto =
+                        Sphere::from_toml(model, named, &mut recursos)?;
                     scene.add_shape(&objeto.into())?;
                 }
                 Some("Triangle") => {
-                    let objeto = Triangle::from_toml(model)?;
+                    let objeto =
+                        Triangle::from_toml(model, named, &mut recursos)?;
                     scene.add_shape(&objeto.into())?;
                 }
                 Some("ModelObj") => {
@@ -110,6 +346,9 @@ impl Scene {
             }
         }
 
+        scene.set_resources(recursos);
+        scene.build_bvh();
+
         Ok(scene)
     }
 }
@@ -125,7 +364,11 @@ impl ModelObj {
 }
 
 impl Sphere {
-    pub fn from_toml(toml: &Table) -> Result<Sphere, anyhow::Error> {
+    pub fn from_toml(
+        toml: &Table,
+        named: &HashMap<String, Material>,
+        recursos: &mut Resources,
+    ) -> Result<Sphere, anyhow::Error> {
         let error = || anyhow::anyhow!("No se pudo cargar el modelo de esfera");
         let center: Vec<f64> = toml
             .get("center")
@@ -157,7 +400,7 @@ impl Sphere {
             .ok_or(error())?
             .as_float()
             .ok_or(error())?;
-        let material = get_material(toml, error)?;
+        let material = get_material(toml, named, recursos, error)?;
 
         let transform = geometry::create_translation(&center)
             * geometry::create_scaling(&scale);
@@ -167,7 +410,11 @@ impl Sphere {
 }
 
 impl Triangle {
-    pub fn from_toml(toml: &Table) -> Result<Triangle, anyhow::Error> {
+    pub fn from_toml(
+        toml: &Table,
+        named: &HashMap<String, Material>,
+        recursos: &mut Resources,
+    ) -> Result<Triangle, anyhow::Error> {
         let error =
             || anyhow::anyhow!("No se pudo cargar el modelo de triángulo.");
         let vértices = toml
@@ -180,7 +427,7 @@ impl Triangle {
         let p_2 = create_point_from_toml(&vértices[1])?;
         let p_3 = create_point_from_toml(&vértices[2])?;
 
-        let material = get_material(toml, error)?;
+        let material = get_material(toml, named, recursos, error)?;
 
         Ok(Triangle::new(
             &p_1,
@@ -194,30 +441,75 @@ impl Triangle {
 
 impl Material {
     // Todavía ni se como van a ser mis materiales, por ahora solo leo el color
-    pub fn from_toml(toml: &Table) -> Result<Self, anyhow::Error> {
+    /// named son los materiales con nombre que puede usar un Mix, y en
+    /// recursos se guardan los que usa.
+    pub fn from_toml(
+        toml: &Table,
+        named: &HashMap<String, Material>,
+        recursos: &mut Resources,
+    ) -> Result<Self, anyhow::Error> {
         let error = || anyhow::anyhow!("No se pudo cargar el material");
 
-        let color = toml
-            .get("albedo")
-            .and_then(|c| c.as_array())
-            .ok_or(error())?;
-        let color: Vec<f64> = color
-            .iter()
-            .map(|c| c.as_float().ok_or(error()))
-            .collect::<Result<_, _>>()?;
-        anyhow::ensure!(color.len() == 3, error());
-
         let type_ = toml.get("type").ok_or(error())?.as_str();
 
-        let color = SampledSpectrum::from_RGB(
-            (color[0] as f32, color[1] as f32, color[2] as f32),
-            match type_ {
-                Some("Emitter") => SpectrumType::Illuminant,
-                _ => SpectrumType::Reflectance,
-            },
+        // el Principled puede dar el color en base_color, y el Mix usa el
+        // de sus materiales. Si el albedo es una tabla es una textura, que
+        // no pueden tener ni los emisores ni el medio de Subsurface
+        let (color, color_texture) = match toml.get("albedo") {
+            Some(Value::Table(_))
+                if matches!(type_, Some("Emitter" | "Subsurface" | "Mix")) =>
+            {
+                anyhow::bail!("Este material no puede tener textura")
+            }
+            Some(c @ Value::Table(_)) => (
+                None,
+                Some(parse_texture(
+                    c,
+                    |v| create_spectrum_from_toml(v, SpectrumType::Reflectance),
+                    recursos,
+                )?),
+            ),
+            Some(c) => (
+                Some(create_spectrum_from_toml(
+                    c,
+                    match type_ {
+                        Some("Emitter") => SpectrumType::Illuminant,
+                        _ => SpectrumType::Reflectance,
+                    },
+                )?),
+                None,
+            ),
+            None => (None, None),
+        };
+        anyhow::ensure!(
+            color.is_some()
+                || color_texture.is_some()
+                || matches!(type_, Some("Principled" | "Mix")),
+            error()
         );
 
-        let mut material = Material::default();
+        // índice de refracción, para los materiales transparentes
+        let mut material = Material {
+            optical_density: toml
+                .get("ior")
+                .or(toml.get("optical_density"))
+                .map(|d| d.as_float().filter(|d| *d > 0.0).ok_or(error()))
+                .transpose()?,
+            dispersion: toml
+                .get("dispersion")
+                .map(parse_dispersion)
+                .transpose()?,
+            // la del Principled es un parámetro más, que puede ser textura
+            roughness: match type_ {
+                Some("Principled") => None,
+                _ => parse_roughness(toml, "roughness")?,
+            },
+            color_texture: match type_ {
+                Some("Principled") => None,
+                _ => color_texture,
+            },
+            ..Default::default()
+        };
 
         match type_ {
             Some("mtl") => {
@@ -226,17 +518,122 @@ impl Material {
             }
             Some("Lambertian") => {
                 material.tipo = material::Type::Lambertian;
-                material.ambient_color = Some(color);
+                material.ambient_color = color;
+                Ok(material)
+            }
+            Some("OrenNayar") => {
+                // sigma en grados, entre 0 (Lambertian) y 90
+                let sigma = toml
+                    .get("sigma")
+                    .map(|s| {
+                        s.as_float()
+                            .or(s.as_integer().map(|s| s as f64))
+                            .filter(|s| (0.0..=90.0).contains(s))
+                            .ok_or(error())
+                    })
+                    .transpose()?;
+
+                material.tipo = material::Type::OrenNayar;
+                material.ambient_color = color;
+                material.sigma = sigma;
                 Ok(material)
             }
             Some("Specular") => {
                 material.tipo = material::Type::Specular;
-                material.specular_color = Some(color);
+                material.specular_color = color;
+                Ok(material)
+            }
+            Some("Dielectric") => {
+                material.tipo = material::Type::Dielectric;
+                material.specular_color = color;
+                Ok(material)
+            }
+            Some("Conductor") => {
+                // metal elegido por nombre, teñido con albedo
+                let metal =
+                    toml.get("metal").and_then(|m| m.as_str()).ok_or(error())?;
+
+                material.tipo = material::Type::Conductor;
+                material.specular_color = color;
+                material.complex_ior =
+                    Some(SampledSpectrum::metal_ior(metal).ok_or_else(
+                        || anyhow::anyhow!("Metal desconocido: {metal}"),
+                    )?);
+                Ok(material)
+            }
+            Some("CoatedDiffuse") => {
+                material.tipo = material::Type::CoatedDiffuse;
+                material.ambient_color = color;
+                material.coat = Some(parse_coat(toml)?);
+                Ok(material)
+            }
+            Some("CoatedConductor") => {
+                // como Conductor, con roughness la del metal
+                let metal =
+                    toml.get("metal").and_then(|m| m.as_str()).ok_or(error())?;
+
+                material.tipo = material::Type::CoatedConductor;
+                material.specular_color = color;
+                material.complex_ior =
+                    Some(SampledSpectrum::metal_ior(metal).ok_or_else(
+                        || anyhow::anyhow!("Metal desconocido: {metal}"),
+                    )?);
+                material.coat = Some(parse_coat(toml)?);
+                Ok(material)
+            }
+            Some("Subsurface") => {
+                // albedo es el del medio, y mean_free_path la distancia
+                // media entre choques, un número o un color
+                let mean_free_path = match toml.get("mean_free_path") {
+                    Some(d @ Value::Array(_)) => {
+                        create_spectrum_from_toml(d, SpectrumType::Reflectance)?
+                    }
+                    Some(d) => SampledSpectrum::new(
+                        d.as_float().filter(|d| *d > 0.0).ok_or(error())?
+                            as f32,
+                    ),
+                    None => return Err(error()),
+                };
+
+                material.tipo = material::Type::Subsurface;
+                material.medium =
+                    Some(Medium::new(mean_free_path, color.ok_or(error())?));
+                Ok(material)
+            }
+            Some("ThinDielectric") => {
+                material.tipo = material::Type::ThinDielectric;
+                material.specular_color = color;
+                Ok(material)
+            }
+            Some("Mix") => {
+                material.tipo = material::Type::Mix;
+                material.mix = Some(parse_mix(toml, named, recursos)?);
+                Ok(material)
+            }
+            Some("Plastic") => {
+                // albedo es el color difuso, y specular el del barniz
+                material.tipo = material::Type::Plastic;
+                material.ambient_color = color;
+                material.specular_color = toml
+                    .get("specular")
+                    .map(|c| {
+                        create_spectrum_from_toml(c, SpectrumType::Reflectance)
+                    })
+                    .transpose()?;
+                Ok(material)
+            }
+            Some("Principled") => {
+                material.tipo = material::Type::Principled;
+                material.principled = Some(parse_principled(
+                    toml,
+                    color.map(Texture::from).or(color_texture),
+                    recursos,
+                )?);
                 Ok(material)
             }
             Some("Emitter") => {
                 material.tipo = material::Type::Emitter;
-                material.emitted_color = Some(color);
+                material.emitted_color = color;
                 Ok(material)
             }
             Some(s) => {
@@ -262,10 +659,367 @@ pub fn create_point_from_toml(arr: &Value) -> Result<Point, anyhow::Error> {
     Ok(Point::new(x, y, z))
 }
 
-fn get_material(toml: &Table, error: fn() -> Error) -> Result<Material, Error> {
+/// Lee un color RGB, con cada componente en [0, 1] si es una reflectancia.
+fn create_spectrum_from_toml(
+    arr: &Value,
+    type_: SpectrumType,
+) -> Result<SampledSpectrum, anyhow::Error> {
+    let error = || anyhow::anyhow!("No se pudo cargar el color");
+    let arr = arr.as_array().ok_or(error())?;
+    anyhow::ensure!(arr.len() == 3, error());
+
+    let r = arr[0].as_float().ok_or(error())?;
+    let g = arr[1].as_float().ok_or(error())?;
+    let b = arr[2].as_float().ok_or(error())?;
+
+    Ok(SampledSpectrum::from_RGB(
+        (r as f32, g as f32, b as f32),
+        type_,
+    ))
+}
+
+/// Lee el barniz de los materiales Coated: coat_roughness como roughness,
+/// thickness el espesor del medio debajo, y absorption = [r, g, b] cuánto
+/// absorbe por unidad de espesor. Sin absorption no absorbe.
+fn parse_coat(toml: &Table) -> Result<Coat, Error> {
+    let error = || anyhow::anyhow!("No se pudo cargar el barniz");
+    let defecto = Coat::default();
+
+    Ok(Coat {
+        roughness: parse_roughness(toml, "coat_roughness")?,
+        thickness: toml
+            .get("thickness")
+            .map(|t| t.as_float().filter(|t| *t >= 0.0).ok_or(error()))
+            .transpose()?
+            .unwrap_or(defecto.thickness),
+        absorption: toml
+            .get("absorption")
+            .map(|a| create_spectrum_from_toml(a, SpectrumType::Reflectance))
+            .transpose()?
+            .unwrap_or(defecto.absorption),
+    })
+}
+
+/// Lee los parámetros de un material Principled. Todos son opcionales, y
+/// pueden ser una constante o una textura. El color base es albedo si no
+/// está base_color.
+fn parse_principled(
+    toml: &Table,
+    albedo: Option<Texture<SampledSpectrum>>,
+    recursos: &mut Resources,
+) -> Result<Principled, Error> {
+    let defecto = Principled {
+        base_color: albedo.unwrap_or(Principled::default().base_color),
+        ..Default::default()
+    };
+
+    let color =
+        |v: &Value| create_spectrum_from_toml(v, SpectrumType::Reflectance);
+    let base_color =
+        toml.get("base_color").map_or(Ok(defecto.base_color), |v| {
+            parse_texture(v, color, recursos)
+        })?;
+
+    let número = |v: &Value| {
+        v.as_float()
+            .or(v.as_integer().map(|v| v as f64))
+            .filter(|v| (0.0..=1.0).contains(v))
+            .ok_or(anyhow::anyhow!("Parámetro inválido: {v}"))
+    };
+    let mut parámetro = |key: &str, defecto: Texture<f64>| {
+        toml.get(key)
+            .map_or(Ok(defecto), |v| parse_texture(v, número, recursos))
+    };
+
+    Ok(Principled {
+        base_color,
+        metallic: parámetro("metallic", defecto.metallic)?,
+        roughness: parámetro("roughness", defecto.roughness)?,
+        anisotropic: parámetro("anisotropic", defecto.anisotropic)?,
+        specular: parámetro("specular", defecto.specular)?,
+        specular_tint: parámetro("specular_tint", defecto.specular_tint)?,
+        sheen: parámetro("sheen", defecto.sheen)?,
+        sheen_tint: parámetro("sheen_tint", defecto.sheen_tint)?,
+        clearcoat: parámetro("clearcoat", defecto.clearcoat)?,
+        clearcoat_roughness: parámetro(
+            "clearcoat_roughness", defecto.clearcoat_roughness,
+        )?,
+        transmission: parámetro("transmission", defecto.transmission)?,
+    })
+}
+
+/// Lee un parámetro que puede cambiar sobre la superficie: un valor
+/// constante, o una tabla con la textura, como
+/// { type = "Checkerboard", even = ..., odd = ..., scale = 4.0 } o
+/// { type = "Image", file = "madera.png", filter = "Trilinear",
+/// wrap = "Repeat", encoding = "sRGB", scale = 1.0 }. Las imágenes de 8 bits
+/// son sRGB salvo que encoding sea "linear"; las HDR siempre son lineales.
+fn parse_texture<T>(
+    value: &Value,
+    parse: impl Fn(&Value) -> Result<T, Error>,
+    recursos: &mut Resources,
+) -> Result<Texture<T>, Error> {
+    let error = || anyhow::anyhow!("No se pudo cargar la textura");
+
+    let toml = match value {
+        Value::Table(toml) => toml,
+        value => return Ok(Texture::Constant(parse(value)?)),
+    };
+
+    let scale = toml
+        .get("scale")
+        .map(|s| s.as_float().ok_or(error()))
+        .transpose()?
+        .unwrap_or(1.0);
+    let opción = |key: &str| toml.get(key).map(|v| v.as_str().ok_or(error()));
+
+    match toml.get("type").and_then(|t| t.as_str()) {
+        Some("Checkerboard") => Ok(Texture::Checkerboard {
+            even: parse(toml.get("even").ok_or(error())?)?,
+            odd: parse(toml.get("odd").ok_or(error())?)?,
+            scale,
+        }),
+        Some("Image") => {
+            let archivo = opción("file").ok_or(error())??;
+            let srgb = match opción("encoding").transpose()? {
+                None | Some("sRGB") => true,
+                Some("linear") => false,
+                Some(_) => return Err(error()),
+            };
+            let filter = match opción("filter").transpose()? {
+                None | Some("Trilinear") => Filter::Trilinear,
+                Some("Bilinear") => Filter::Bilinear,
+                Some(_) => return Err(error()),
+            };
+            let wrap = match opción("wrap").transpose()? {
+                None | Some("Repeat") => Wrap::Repeat,
+                Some("Clamp") => Wrap::Clamp,
+                Some("Mirror") => Wrap::Mirror,
+                Some(_) => return Err(error()),
+            };
+
+            Ok(Texture::Image {
+                image: recursos.load_image(archivo, srgb)?,
+                filter,
+                wrap,
+                scale,
+            })
+        }
+        _ => Err(error()),
+    }
+}
+
+/// El material de un modelo: una tabla, o el nombre de uno de named.
+fn get_material(
+    toml: &Table,
+    named: &HashMap<String, Material>,
+    recursos: &mut Resources,
+    error: fn() -> Error,
+) -> Result<Material, Error> {
     match toml.get("material") {
-        Some(Value::Table(toml)) => Ok(Material::from_toml(toml)?),
+        Some(Value::Table(toml)) => {
+            Ok(Material::from_toml(toml, named, recursos)?)
+        }
+        Some(Value::String(nombre)) => named
+            .get(nombre)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Material desconocido: {nombre}")),
         Some(_) => Err(error()),
         None => Ok(Default::default()),
     }
 }
+
+/// Lee los materiales con nombre de la tabla Materials. Los modelos y los
+/// Mix los usan por el nombre, en lugar de repetirlos. Lo que usan por
+/// índice queda en recursos.
+pub fn parse_materials(
+    table: &Table,
+    recursos: &mut Resources,
+) -> Result<HashMap<String, Material>, Error> {
+    let mut named = HashMap::new();
+
+    let tablas = match table.get("Materials") {
+        Some(Value::Table(tablas)) => tablas,
+        Some(_) => anyhow::bail!("No se pudieron cargar los materiales"),
+        None => return Ok(named),
+    };
+
+    for nombre in tablas.keys() {
+        resolve_material(
+            nombre,
+            tablas,
+            &mut named,
+            recursos,
+            &mut Vec::new(),
+        )?;
+    }
+
+    Ok(named)
+}
+
+/// Lee el material nombre de tablas, después de los que usa. pendientes son
+/// los que se están leyendo, para no dar vueltas si se usan en círculo.
+fn resolve_material(
+    nombre: &str,
+    tablas: &Table,
+    named: &mut HashMap<String, Material>,
+    recursos: &mut Resources,
+    pendientes: &mut Vec<String>,
+) -> Result<Material, Error> {
+    if let Some(material) = named.get(nombre) {
+        return Ok(*material);
+    }
+    anyhow::ensure!(
+        !pendientes.iter().any(|p| p == nombre),
+        "El material {nombre} se usa a sí mismo"
+    );
+
+    let toml = tablas
+        .get(nombre)
+        .and_then(|t| t.as_table())
+        .ok_or_else(|| anyhow::anyhow!("Material desconocido: {nombre}"))?;
+
+    pendientes.push(nombre.to_string());
+    if let Some(usados) = toml.get("materials").and_then(|m| m.as_array()) {
+        for usado in usados.iter().filter_map(|u| u.as_str()) {
+            resolve_material(usado, tablas, named, recursos, pendientes)?;
+        }
+    }
+    pendientes.pop();
+
+    let material = Material::from_toml(toml, named, recursos)?;
+    named.insert(nombre.to_string(), material);
+
+    Ok(material)
+}
+
+/// Lee un Mix: materials son los nombres de los dos materiales, y weight la
+/// probabilidad de usar el segundo, constante o una textura.
+fn parse_mix(
+    toml: &Table,
+    named: &HashMap<String, Material>,
+    recursos: &mut Resources,
+) -> Result<Mix, Error> {
+    let error = || anyhow::anyhow!("No se pudo cargar la mezcla");
+
+    let nombres = toml
+        .get("materials")
+        .and_then(|m| m.as_array())
+        .filter(|m| m.len() == 2)
+        .ok_or(error())?;
+
+    let ids = nombres
+        .iter()
+        .map(|nombre| {
+            let nombre = nombre.as_str().ok_or(error())?;
+            let material = named.get(nombre).ok_or_else(|| {
+                anyhow::anyhow!("Material desconocido: {nombre}")
+            })?;
+
+            Ok(recursos.add_material(*material))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let weight = match toml.get("weight") {
+        Some(weight) => parse_texture(
+            weight,
+            |v| {
+                v.as_float()
+                    .or(v.as_integer().map(|v| v as f64))
+                    .filter(|v| (0.0..=1.0).contains(v))
+                    .ok_or(error())
+            },
+            recursos,
+        )?,
+        None => 0.5.into(),
+    };
+
+    Ok(Mix {
+        materials: [ids[0], ids[1]],
+        weight,
+    })
+}
+
+/// Lee la rugosidad de un material en key: es un número en [0, 1], o dos si
+/// es anisotrópica, y distribution es "GGX" (por defecto) o "Beckmann". Sin
+/// rugosidad, o con rugosidad cero, el material es pulido.
+fn parse_roughness(
+    toml: &Table,
+    key: &str,
+) -> Result<Option<MicrofacetDistribution>, Error> {
+    let error = || anyhow::anyhow!("No se pudo cargar la rugosidad");
+
+    let (r_x, r_y) = match toml.get(key) {
+        None => return Ok(None),
+        Some(Value::Array(arr)) => {
+            anyhow::ensure!(arr.len() == 2, error());
+            let r_x = arr[0].as_float().ok_or(error())?;
+            let r_y = arr[1].as_float().ok_or(error())?;
+            (r_x, r_y)
+        }
+        Some(r) => {
+            let r = r.as_float().ok_or(error())?;
+            (r, r)
+        }
+    };
+    anyhow::ensure!((0.0..=1.0).contains(&r_x), error());
+    anyhow::ensure!((0.0..=1.0).contains(&r_y), error());
+
+    if r_x == 0.0 && r_y == 0.0 {
+        return Ok(None);
+    }
+
+    let alpha_x = MicrofacetDistribution::roughness_to_alpha(r_x);
+    let alpha_y = MicrofacetDistribution::roughness_to_alpha(r_y);
+
+    match toml.get("distribution").map(|d| d.as_str()) {
+        None | Some(Some("GGX")) => {
+            Ok(Some(MicrofacetDistribution::GGX { alpha_x, alpha_y }))
+        }
+        Some(Some("Beckmann")) => {
+            Ok(Some(MicrofacetDistribution::Beckmann { alpha_x, alpha_y }))
+        }
+        _ => Err(error()),
+    }
+}
+
+/// Lee la dispersión de un dieléctrico. Puede ser el nombre de uno conocido
+/// ("BK7", "FusedSilica" o "Diamond"), o una tabla con los coeficientes:
+/// { type = "Cauchy", a = 1.5, b = 0.004 } o
+/// { type = "Sellmeier", b = [b1, b2, b3], c = [c1, c2, c3] }.
+fn parse_dispersion(value: &Value) -> Result<Dispersion, Error> {
+    let error = || anyhow::anyhow!("No se pudo cargar la dispersión");
+
+    let toml = match value {
+        Value::String(name) => {
+            return Dispersion::from_name(name).ok_or(error());
+        }
+        Value::Table(toml) => toml,
+        _ => return Err(error()),
+    };
+
+    let float = |key: &str| toml.get(key).and_then(|v| v.as_float());
+    let terna = |key: &str| -> Result<[f64; 3], Error> {
+        let arr = toml.get(key).and_then(|v| v.as_array()).ok_or(error())?;
+        anyhow::ensure!(arr.len() == 3, error());
+
+        let mut result = [0.0; 3];
+        for (x, v) in result.iter_mut().zip(arr) {
+            *x = v.as_float().ok_or(error())?;
+        }
+
+        Ok(result)
+    };
+
+    match toml.get("type").and_then(|t| t.as_str()) {
+        Some("Cauchy") => Ok(Dispersion::Cauchy {
+            a: float("a").ok_or(error())?,
+            b: float("b").ok_or(error())?,
+        }),
+        Some("Sellmeier") => Ok(Dispersion::Sellmeier {
+            b: terna("b")?,
+            c: terna("c")?,
+        }),
+        _ => Err(error()),
+    }
+}
@@ -1,4 +1,5 @@
-use crate::geometry::{Ray, AABB};
+use crate::accelerators::BVH;
+use crate::geometry::{Normal, Point, Ray, AABB};
 use crate::material::Material;
 use crate::shapes::{Intersection, Shape, ShapeOperations};
 
@@ -6,22 +7,32 @@ use crate::shapes::{Intersection, Shape, ShapeOperations};
 #[derive(Clone, Debug)]
 pub struct BoxAABB {
     objetos: Vec<Shape>,
-    mat: Material, // No lo uso, está para devolver algo
+    bvh: Option<BVH>, // se construye con build_bvh
+    mat: Material,    // No lo uso, está para devolver algo
     caja: AABB,
 }
 
+// Ninguna escena arma cajas todavía, solo los tests
+#[allow(dead_code)]
 impl BoxAABB {
     pub fn new() -> BoxAABB {
         BoxAABB {
             objetos: Vec::new(),
+            bvh: None,
             mat: Default::default(),
             caja: AABB::empty(),
         }
     }
 
     pub fn add_model(&mut self, modelo: &Shape) {
-        self.caja.resize_box(modelo.bounding_box());
+        if self.objetos.is_empty() {
+            // la caja vacía está en el origen, no la quiero incluir
+            self.caja = *modelo.bounding_box();
+        } else {
+            self.caja.resize_box(modelo.bounding_box());
+        }
         self.objetos.push(modelo.clone());
+        self.bvh = None;
     }
 
     fn intersection_ray_box(&self, rayo: &Ray) -> bool {
@@ -34,21 +45,117 @@ impl ShapeOperations for BoxAABB {
         &self.mat
     }
 
-    fn intersect(&self, rayo: &Ray) -> Option<Intersection> {
+    fn intersect(&self, rayo: &Ray) -> Option<Intersection<'_>> {
+        if let Some(bvh) = &self.bvh {
+            return bvh
+                .intersect(&self.objetos, rayo)
+                .map(|(_, choque)| choque);
+        }
+
         if !self.intersection_ray_box(rayo) {
             return None;
         }
 
+        // el choque más cercano, achicando el intervalo con cada choque
+        let mut rayo = *rayo;
+        let mut menor = None;
+
         for obj in &self.objetos {
-            let choque = obj.intersect(rayo);
-            if choque.is_some() {
-                return choque;
+            if let Some(choque) = obj.intersect(&rayo) {
+                rayo = rayo.with_max_t(choque.t());
+                menor = Some(choque);
             }
         }
-        None
+
+        menor
+    }
+
+    fn is_intersecting(&self, rayo: &Ray) -> bool {
+        if let Some(bvh) = &self.bvh {
+            return bvh.is_intersecting(&self.objetos, rayo);
+        }
+
+        self.intersection_ray_box(rayo)
+            && self.objetos.iter().any(|obj| obj.is_intersecting(rayo))
     }
 
     fn bounding_box(&self) -> &AABB {
         &self.caja
     }
+
+    /// Construye la BVH sobre los modelos de la caja, y las de los modelos.
+    /// Mientras no se llame, se prueba el rayo contra cada modelo.
+    fn build_bvh(&mut self) {
+        self.objetos.iter_mut().for_each(Shape::build_bvh);
+        self.bvh = Some(BVH::new(&mut self.objetos));
+    }
+
+    fn area(&self) -> f64 {
+        self.objetos.iter().map(|obj| obj.area()).sum()
+    }
+
+    /// Elige un modelo proporcionalmente a su área. El pdf por defecto es
+    /// correcto siempre que los modelos también muestreen uniforme en el área.
+    fn sample(&self, (u_1, u_2): (f64, f64)) -> (Point, Normal) {
+        let objetivo = u_1 * self.area();
+        let mut acumulado = 0.0;
+
+        for obj in &self.objetos {
+            let área = obj.area();
+            if objetivo < acumulado + área {
+                // reuso u_1 reescalado dentro del intervalo del modelo
+                return obj.sample(((objetivo - acumulado) / área, u_2));
+            }
+            acumulado += área;
+        }
+
+        // solo por errores de redondeo
+        self.objetos[self.objetos.len() - 1].sample((1.0 - f64::EPSILON, u_2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_float;
+    use crate::geometry::{create_translation, Point, Vector};
+    use crate::shapes::Sphere;
+
+    fn caja_con_esferas() -> BoxAABB {
+        let mut caja = BoxAABB::new();
+
+        // la esfera más lejana se agrega primero
+        for x in [6.0, 3.0] {
+            let esfera = Sphere::new(
+                &create_translation(&Vector::new(x, 0.0, 0.0)),
+                1.0,
+                &Material::default(),
+            );
+            caja.add_model(&esfera.into());
+        }
+
+        caja
+    }
+
+    #[test]
+    fn caja_devuelve_el_choque_más_cercano() {
+        let rayo = Ray::new(
+            &Point::origin(),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        let mut caja = caja_con_esferas();
+        assert_eq_float!(caja.intersect(&rayo).unwrap().t(), 2.0);
+
+        caja.build_bvh();
+        assert_eq_float!(caja.intersect(&rayo).unwrap().t(), 2.0);
+    }
+
+    #[test]
+    fn caja_no_incluye_el_origen() {
+        let caja = caja_con_esferas();
+
+        assert_eq_float!(caja.bounding_box().min().x, 2.0);
+    }
 }
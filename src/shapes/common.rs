@@ -1,32 +1,50 @@
+use crate::bsdf::Bsdf;
 use crate::geometry::{Normal, Point, Ray, Vector};
-use crate::shapes::Shape;
+use crate::material::Material;
+use crate::resources::Resources;
+use crate::sampling::hash_float;
+use crate::texture::TexCoord;
 
 /// punto es el punto donde chocaron.
 /// normal es la dirección normal del modelo en dirección saliente al objeto,
 /// no la normal del mismo lado de donde venía el rayo.
 /// t es el valor en el que se evaluó el rayo para el choque.
+/// El material se toma prestado de la primitiva chocada, así el choque no
+/// copia la figura, y los recursos que usa de la escena.
+/// shape_index es el índice del objeto chocado en la escena, si el choque lo
+/// devolvió la escena.
+/// uv son las coordenadas paramétricas del punto en la figura, y uv_width
+/// el ancho en uv de lo que abarca el rayo en el punto.
 #[derive(Debug, Clone)]
-pub struct Intersection {
-    modelo: Shape,
+pub struct Intersection<'a> {
+    material: &'a Material,
+    recursos: &'a Resources,
+    shape_index: Option<usize>,
     punto: Point,
+    uv: (f64, f64),
+    uv_width: f64,
     rayo_incidente: Ray,
     direction_out: Vector,
     normal: Normal,
-    inside: bool, // capaz sirva esto??
+    inside: bool, // si el rayo venía desde adentro del objeto
     t: f64,
 }
 
-impl Intersection {
+impl<'a> Intersection<'a> {
     pub fn new(
-        modelo: &Shape,
+        material: &'a Material,
         punto: &Point,
         rayo: &Ray,
         normal: &Normal,
         t: f64,
-    ) -> Intersection {
+    ) -> Intersection<'a> {
         Intersection {
-            modelo: modelo.clone(),
+            material,
+            recursos: Resources::empty(),
+            shape_index: None,
             punto: *punto,
+            uv: (0.0, 0.0),
+            uv_width: 0.0,
             rayo_incidente: *rayo,
             direction_out: -rayo.dir(),
             normal: *normal,
@@ -35,14 +53,52 @@ impl Intersection {
         }
     }
 
-    pub fn model(&self) -> &Shape {
-        &self.modelo
+    pub fn material(&self) -> &'a Material {
+        self.material
+    }
+
+    /// Los recursos de la escena del choque, para los materiales que los
+    /// usan. La escena los pone al devolver el choque.
+    pub fn set_resources(&mut self, recursos: &'a Resources) {
+        self.recursos = recursos;
+    }
+
+    pub fn shape_index(&self) -> Option<usize> {
+        self.shape_index
+    }
+
+    pub fn set_shape_index(&mut self, index: usize) {
+        self.shape_index = Some(index);
     }
 
     pub fn point(&self) -> &Point {
         &self.punto
     }
 
+    pub fn uv(&self) -> (f64, f64) {
+        self.uv
+    }
+
+    pub fn set_uv(&mut self, uv: (f64, f64)) {
+        self.uv = uv;
+    }
+
+    /// Guarda cuánto cambian las coordenadas uv por unidad de distancia
+    /// sobre la superficie, para saber el ancho en uv de lo que abarca el
+    /// rayo.
+    pub fn set_uv_density(&mut self, densidad: f64) {
+        self.uv_width = self.rayo_incidente.spread() * self.t * densidad;
+    }
+
+    /// Las coordenadas uv con su ancho, para evaluar las texturas.
+    pub fn tex_coord(&self) -> TexCoord<'a> {
+        TexCoord {
+            uv: self.uv,
+            width: self.uv_width,
+            resources: self.recursos,
+        }
+    }
+
     pub fn incident_ray(&self) -> &Ray {
         &self.rayo_incidente
     }
@@ -58,7 +114,39 @@ impl Intersection {
         self.t
     }
 
-    pub fn invert_normal(&mut self) {
-        self.normal = -self.normal;
+    /// El BSDF del material en el punto del choque. Si el material es una
+    /// mezcla, el componente sale de un hash del choque, así cada llamada
+    /// en el mismo punto da el mismo BSDF. El hash no sale del sampler, así
+    /// que MLT no puede mutar esa elección.
+    pub fn bsdf(&self) -> Bsdf {
+        let (p, wo) = (&self.punto, &self.direction_out);
+        let u = hash_float(&[p.x, p.y, p.z, wo.x, wo.y, wo.z]);
+        let coords = self.tex_coord();
+
+        self.material.choose(coords, u).bsdf(&self.normal, coords)
+    }
+
+    /// La normal del mismo lado del que venía el rayo.
+    pub fn facing_normal(&self) -> Normal {
+        if self.inside {
+            -self.normal
+        } else {
+            self.normal
+        }
+    }
+
+    /// Devuelve el punto del choque corrido un poco hacia el lado de dir, para
+    /// que un rayo que sale de ahí no vuelva a chocar con la misma superficie.
+    pub fn spawn_point(&self, dir: &Vector) -> Point {
+        if self.normal.dot(dir) > 0.0 {
+            self.punto + self.normal * 1e-9
+        } else {
+            self.punto - self.normal * 1e-9
+        }
+    }
+
+    /// Rayo que sale del punto del choque en dirección dir.
+    pub fn spawn_ray(&self, dir: &Vector) -> Ray {
+        Ray::new(&self.spawn_point(dir), dir, f64::INFINITY)
     }
 }
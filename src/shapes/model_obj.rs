@@ -1,14 +1,22 @@
+use crate::accelerators::BVH;
 use crate::auxiliar::read_file;
-use crate::geometry::{create_point_from_vertex, Ray, Transform, AABB};
-use crate::material::Material;
+use crate::geometry::{
+    create_point_from_vertex, Normal, Point, Ray, Transform, AABB,
+};
+use crate::material::{parse_mtl, Material};
+use crate::sampling::Distribution1D;
 use crate::shapes::{Intersection, ShapeOperations, Triangle};
-use wavefront_obj::{mtl, obj};
+use wavefront_obj::obj;
 
 #[derive(Clone, Debug)]
 pub struct ModelObj {
     triángulos: Vec<Triangle>,
+    bvh: BVH,
     material: Material,
     caja: AABB,
+    área: f64,
+    // para elegir un triángulo proporcionalmente a su área
+    distribución: Distribution1D,
 }
 
 impl ModelObj {
@@ -19,14 +27,12 @@ impl ModelObj {
         let material = match objetos.material_library {
             Some(nombre) => {
                 let datos = read_file(&nombre)?;
-                Material::from(
-                    mtl::parse(datos)?.materials.first().ok_or_else(|| {
-                        anyhow::anyhow!(
-                            "No se pudo cargar el material de {:?}",
-                            nombre
-                        )
-                    })?,
-                )
+                *parse_mtl(&datos)?.first().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No se pudo cargar el material de {:?}",
+                        nombre
+                    )
+                })?
             }
             None => Default::default(),
         };
@@ -41,7 +47,7 @@ impl ModelObj {
                         figura.primitive
                     {
                         // vtn: vértice, textura, normal
-                        triángulos.push(Triangle::new(
+                        let triángulo = Triangle::new(
                             &create_point_from_vertex(
                                 &objeto.vertices[vtn_1.0],
                             ),
@@ -53,37 +59,64 @@ impl ModelObj {
                             ),
                             &Transform::identity(), // TODO: añadir transformaciones al archivo
                             &material,
-                        ));
+                        );
+
+                        // en el OBJ v = 0 es el borde de abajo de la imagen,
+                        // y en las texturas el de arriba
+                        let uv = |t: usize| {
+                            let vt = &objeto.tex_vertices[t];
+                            (vt.u, 1.0 - vt.v)
+                        };
+                        triángulos.push(match (vtn_1.1, vtn_2.1, vtn_3.1) {
+                            (Some(t_1), Some(t_2), Some(t_3)) => {
+                                triángulo.with_uvs([uv(t_1), uv(t_2), uv(t_3)])
+                            }
+                            _ => triángulo,
+                        });
                     }
                 }
             }
         }
 
-        let mut caja = AABB::empty();
+        ModelObj::from_triangles(triángulos, &material)
+    }
 
-        for triángulo in &triángulos {
-            caja.resize_box(triángulo.bounding_box());
-        }
+    /// Crea el modelo a partir de una lista de triángulos ya cargados.
+    pub fn from_triangles(
+        mut triángulos: Vec<Triangle>,
+        material: &Material,
+    ) -> Result<ModelObj, anyhow::Error> {
+        let caja = triángulos
+            .iter()
+            .map(|t| *t.bounding_box())
+            .reduce(|caja, otra| caja.union(&otra))
+            .ok_or_else(|| anyhow::anyhow!("El modelo no tiene triángulos"))?;
+
+        // reordena los triángulos
+        let bvh = BVH::new(&mut triángulos);
+
+        let áreas: Vec<f64> = triángulos.iter().map(|t| t.area()).collect();
 
         Ok(ModelObj {
             triángulos,
-            material,
+            bvh,
+            material: *material,
             caja,
+            área: áreas.iter().sum(),
+            distribución: Distribution1D::new(&áreas),
         })
     }
 }
 
 impl ShapeOperations for ModelObj {
-    fn intersect(&self, rayo: &Ray) -> Option<Intersection> {
-        self.caja.intersect_ray(rayo)?;
-
-        for triángulo in &self.triángulos {
-            if let Some(choque) = triángulo.intersect(rayo) {
-                return Some(choque);
-            }
-        }
+    fn intersect(&self, rayo: &Ray) -> Option<Intersection<'_>> {
+        self.bvh
+            .intersect(&self.triángulos, rayo)
+            .map(|(_, choque)| choque)
+    }
 
-        None
+    fn is_intersecting(&self, rayo: &Ray) -> bool {
+        self.bvh.is_intersecting(&self.triángulos, rayo)
     }
 
     fn bounding_box(&self) -> &AABB {
@@ -93,4 +126,194 @@ impl ShapeOperations for ModelObj {
     fn material(&self) -> &Material {
         &self.material
     }
+
+    fn area(&self) -> f64 {
+        self.área
+    }
+
+    fn sample(&self, (u_1, u_2): (f64, f64)) -> (Point, Normal) {
+        // cada triángulo se elige con probabilidad área_i / área, así que el
+        // punto queda uniforme en toda la superficie y alcanza con el pdf
+        // por defecto
+        let (i, _, u_1) = self.distribución.sample_discrete(u_1);
+
+        self.triángulos[i].sample((u_1, u_2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_eq_float;
+    use crate::geometry::{Point, Vector};
+    use crate::resources::Resources;
+    use crate::texture::{Filter, Texture, Wrap};
+
+    /// Dos caras paralelas al plano YZ, una en x = 1 y otra en x = 2, como si
+    /// fuera una U vista desde adentro. La cara lejana va primero en la lista.
+    fn u_shape() -> Vec<Triangle> {
+        let cara = |x: f64| {
+            vec![
+                Triangle::new(
+                    &Point::new(x, -1.0, -1.0),
+                    &Point::new(x, 1.0, -1.0),
+                    &Point::new(x, 1.0, 1.0),
+                    &Transform::identity(),
+                    &Material::default(),
+                ),
+                Triangle::new(
+                    &Point::new(x, -1.0, -1.0),
+                    &Point::new(x, 1.0, 1.0),
+                    &Point::new(x, -1.0, 1.0),
+                    &Transform::identity(),
+                    &Material::default(),
+                ),
+            ]
+        };
+
+        let mut triángulos = cara(2.0);
+        triángulos.append(&mut cara(1.0));
+        triángulos
+    }
+
+    #[test]
+    fn modelo_cóncavo_devuelve_el_choque_más_cercano() {
+        let modelo =
+            ModelObj::from_triangles(u_shape(), &Material::default()).unwrap();
+
+        let rayo = Ray::new(
+            &Point::new(0.0, 0.1, 0.2),
+            &Vector::new(1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        let choque = modelo.intersect(&rayo).unwrap();
+        assert_eq_float!(choque.t(), 1.0);
+        assert_eq_float!(choque.point().x, 1.0);
+
+        // desde el otro lado se ve la otra cara
+        let rayo = Ray::new(
+            &Point::new(3.0, 0.1, 0.2),
+            &Vector::new(-1.0, 0.0, 0.0),
+            f64::INFINITY,
+        );
+
+        let choque = modelo.intersect(&rayo).unwrap();
+        assert_eq_float!(choque.t(), 1.0);
+        assert_eq_float!(choque.point().x, 2.0);
+    }
+
+    #[test]
+    fn área_del_modelo() {
+        let modelo =
+            ModelObj::from_triangles(u_shape(), &Material::default()).unwrap();
+
+        assert_eq_float!(modelo.area(), 8.0);
+        assert_eq_float!(modelo.pdf(&Point::new(1.0, 0.0, 0.0)), 0.125);
+
+        let (punto, _) = modelo.sample(rand::random());
+        assert!(punto.x == 1.0 || punto.x == 2.0);
+    }
+
+    #[test]
+    fn mono_coincide_con_fuerza_bruta() {
+        let modelo = ModelObj::new("mono.obj").unwrap();
+        let centro = modelo.bounding_box().centroid();
+
+        for i in 0..500 {
+            // rayos desde una esfera alrededor del mono apuntando cerca del
+            // centro, así atraviesan las partes cóncavas (orejas, ojos)
+            let fi = i as f64 * 0.37;
+            let tita = i as f64 * 0.11;
+            let origen = centro
+                + Vector::new(
+                    5.0 * fi.cos() * tita.sin(),
+                    5.0 * fi.sin() * tita.sin(),
+                    5.0 * tita.cos(),
+                );
+            let objetivo = centro
+                + Vector::new(
+                    rand::random::<f64>() - 0.5,
+                    rand::random::<f64>() - 0.5,
+                    rand::random::<f64>() - 0.5,
+                );
+            let rayo = Ray::new(&origen, &(objetivo - origen), f64::INFINITY);
+
+            let esperado = modelo
+                .triángulos
+                .iter()
+                .filter_map(|t| t.intersect(&rayo))
+                .min_by(|a, b| a.t().total_cmp(&b.t()));
+            let obtenido = modelo.intersect(&rayo);
+
+            assert_eq!(esperado.is_some(), obtenido.is_some());
+            if let (Some(esperado), Some(obtenido)) = (esperado, obtenido) {
+                assert_eq_float!(esperado.t(), obtenido.t());
+            }
+        }
+    }
+
+    #[test]
+    fn cuadrado_con_textura() {
+        // un cuadrado de lado 2 con las coordenadas de textura de la mitad
+        // derecha de una imagen de 2x2
+        let obj = "o cuadrado
+v 0 0 0
+v 2 0 0
+v 2 2 0
+v 0 2 0
+vt 0.5 0
+vt 1 0
+vt 1 1
+vt 0.5 1
+f 1/1 2/2 3/3
+f 1/1 3/3 4/4
+";
+        let archivo = std::env::temp_dir().join("raytracer-cuadrado.obj");
+        let archivo = archivo.to_str().unwrap();
+        std::fs::write(archivo, obj).unwrap();
+        let modelo = ModelObj::new(archivo);
+        std::fs::remove_file(archivo).unwrap();
+        let modelo = modelo.unwrap();
+
+        // la mitad derecha es negra arriba y blanca abajo
+        let png = std::env::temp_dir().join("raytracer-cuadrado.png");
+        let png = png.to_str().unwrap();
+        image::GrayImage::from_raw(2, 2, vec![128, 0, 128, 255])
+            .unwrap()
+            .save(png)
+            .unwrap();
+        let mut recursos = Resources::new();
+        let imagen = recursos.load_image(png, false);
+        std::fs::remove_file(png).unwrap();
+        let textura = Texture::<f64>::Image {
+            image: imagen.unwrap(),
+            filter: Filter::Bilinear,
+            wrap: Wrap::Clamp,
+            scale: 1.0,
+        };
+
+        let choque = |x, y| {
+            let rayo = Ray::new(
+                &Point::new(x, y, 1.0),
+                &Vector::new(0.0, 0.0, -1.0),
+                f64::INFINITY,
+            );
+            let mut choque = modelo.intersect(&rayo.with_spread(0.1)).unwrap();
+            choque.set_resources(&recursos);
+            choque.tex_coord()
+        };
+
+        // v del OBJ va para arriba, así que abajo está la fila de abajo de
+        // la imagen
+        let abajo = choque(1.0, 0.5);
+        assert_eq_float!(abajo.uv.0, 0.75);
+        assert_eq_float!(abajo.uv.1, 0.75);
+        assert_eq_float!(textura.evaluate(abajo), 1.0);
+        assert_eq_float!(textura.evaluate(choque(1.0, 1.5)), 0.0);
+
+        // por unidad de distancia u crece 0.25 y v 0.5, y el ancho usa la
+        // media geométrica
+        assert_eq_float!(abajo.width, 0.1 * 0.125f64.sqrt());
+    }
 }
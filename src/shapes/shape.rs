@@ -1,8 +1,8 @@
 use super::box_aabb::BoxAABB;
 use super::model_obj::ModelObj;
 use super::Intersection;
-use crate::geometry::Ray;
 use crate::geometry::AABB;
+use crate::geometry::{Normal, Point, Ray};
 use crate::material::Material;
 use crate::shapes::triangle::Triangle;
 use crate::shapes::Sphere;
@@ -14,7 +14,7 @@ pub trait ShapeOperations {
 
     /// Devuelve el valor t en el que hay que evaluar el rayo para el choque,
     /// si es que chocan
-    fn intersect(&self, rayo: &Ray) -> Option<Intersection>;
+    fn intersect(&self, rayo: &Ray) -> Option<Intersection<'_>>;
 
     /// Devuelve true si hay choque, reescribir este método en las implementaciones para que sea más
     /// eficiente.
@@ -24,8 +24,22 @@ pub trait ShapeOperations {
 
     fn bounding_box(&self) -> &AABB;
 
-    // Calcula el área de la figura
-    //fn area(&self) -> f64;
+    /// Construye las estructuras de aceleración internas de la figura, si
+    /// tiene. La escena lo llama sobre cada objeto antes de armar su BVH.
+    fn build_bvh(&mut self) {}
+
+    /// Calcula el área de la figura en coordenadas globales.
+    fn area(&self) -> f64;
+
+    /// Elige un punto de la superficie usando los números aleatorios u en
+    /// [0, 1). Devuelve el punto y la normal saliente en coordenadas globales.
+    fn sample(&self, u: (f64, f64)) -> (Point, Normal);
+
+    /// Densidad de probabilidad, respecto al área, con la que sample() elige
+    /// el punto, que tiene que estar sobre la superficie.
+    fn pdf(&self, _punto: &Point) -> f64 {
+        1.0 / self.area()
+    }
 }
 
 #[allow(clippy::upper_case_acronyms)]
@@ -1,8 +1,11 @@
 use super::common::Intersection;
-use super::shape::{Shape, ShapeOperations};
+use super::shape::ShapeOperations;
 use crate::geometry;
 use crate::geometry::{Normal, Point, Ray, Transform, AABB};
 use crate::material::Material;
+use crate::sampling::uniform_sample_sphere;
+use nalgebra::Matrix3;
+use std::f64::consts::PI;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Sphere {
@@ -41,13 +44,42 @@ impl Sphere {
 
         (transform * punto.coords).normalize()
     }
+
+    /// Coordenadas esféricas de un punto local, llevadas a [0, 1]: u es el
+    /// ángulo alrededor del eje z y v el ángulo desde el polo de arriba.
+    fn uv(&self, punto: &Point) -> (f64, f64) {
+        let phi = punto.y.atan2(punto.x).rem_euclid(2.0 * PI);
+        let theta = (punto.z / self.radio).clamp(-1.0, 1.0).acos();
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Parte lineal de la transformación (sin la traslación).
+    fn linear_part(&self) -> Matrix3<f64> {
+        self.local_to_world
+            .matrix()
+            .fixed_view::<3, 3>(0, 0)
+            .into_owned()
+    }
+
+    /// Cuánto se agranda un elemento de área de la esfera local en el punto
+    /// con normal n_local al aplicarle la transformación: |det(M)|.|M^-T.n|
+    fn area_scale(&self, n_local: &Normal) -> f64 {
+        let m = self.linear_part();
+        let inversa_transpuesta = m
+            .try_inverse()
+            .unwrap_or_else(Matrix3::identity)
+            .transpose();
+
+        m.determinant().abs() * (inversa_transpuesta * n_local).norm()
+    }
 }
 
 impl ShapeOperations for Sphere {
     fn material(&self) -> &Material {
         &self.material
     }
-    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+    fn intersect(&self, ray: &Ray) -> Option<Intersection<'_>> {
         // paso rayo a coordenadas locales
         let local_ray = self.local_to_world.inverse() * ray;
         let (dir, orig) = (local_ray.dir(), local_ray.origin().coords);
@@ -87,24 +119,59 @@ impl ShapeOperations for Sphere {
             t_1
         };
 
-        let punto_local = match local_ray.at(t) {
-            Some(p) => p,
-            None => return None,
-        };
+        // at() descarta los choques más allá de max_t
+        let punto_local = local_ray.at(t)?;
+        let punto = self.local_to_world * punto_local;
 
-        let model = Shape::from(*self);
-        Some(Intersection::new(
-            &model,
-            &(self.local_to_world * punto_local),
+        // el t local está escalado, el global es la distancia al origen
+        let mut choque = Intersection::new(
+            &self.material,
+            &punto,
             ray,
             &self.normal(&punto_local),
-            t,
-        ))
+            (punto - ray.origin()).norm(),
+        );
+        choque.set_uv(self.uv(&punto_local));
+        // v recorre medio meridiano, y u una vuelta entera que en el
+        // ecuador es el doble de larga
+        let radio = (punto - self.local_to_world * Point::origin()).norm();
+        choque.set_uv_density(1.0 / (PI * radio));
+
+        Some(choque)
     }
 
     fn bounding_box(&self) -> &AABB {
         &self.caja
     }
+
+    fn area(&self) -> f64 {
+        // la esfera transformada es un elipsoide con semiejes iguales a los
+        // valores singulares de la transformación por el radio. Uso la
+        // aproximación de Knud Thomsen, que es exacta para la esfera.
+        let s = self.linear_part().singular_values() * self.radio;
+        let p = 1.6075;
+        let (a, b, c) = (s[0].powf(p), s[1].powf(p), s[2].powf(p));
+
+        4.0 * PI * ((a * b + a * c + b * c) / 3.0).powf(p.recip())
+    }
+
+    fn sample(&self, u: (f64, f64)) -> (Point, Normal) {
+        // elijo uniformemente en la esfera local y lo transformo. Si la
+        // escala no es uniforme los puntos ya no quedan uniformes en el área,
+        // eso lo tiene en cuenta pdf()
+        let punto_local = Point::from(uniform_sample_sphere(u) * self.radio);
+
+        (self.local_to_world * punto_local, self.normal(&punto_local))
+    }
+
+    fn pdf(&self, punto: &Point) -> f64 {
+        let punto_local = self.local_to_world.inverse() * punto;
+        let n_local = punto_local.coords.normalize();
+
+        let area_local = 4.0 * PI * self.radio * self.radio;
+
+        1.0 / (area_local * self.area_scale(&n_local))
+    }
 }
 
 #[cfg(test)]
@@ -159,6 +226,83 @@ mod tests {
         assert_eq_vec!(isect.normal(), &Vector::new(-1.0, 0.0, 0.0));
     }
 
+    #[test]
+    fn sphere_beyond_max_t() {
+        let sphere = Sphere::new(
+            &geometry::create_scaling(&Vector::new(2.0, 2.0, 2.0)),
+            1.0,
+            &Material::default(),
+        );
+
+        let ray = Ray::new(
+            &Point::new(0.0, 0.0, -5.0),
+            &Vector::new(0.0, 0.0, 1.0),
+            2.9,
+        );
+        assert!(sphere.intersect(&ray).is_none());
+
+        let ray = ray.with_max_t(3.1);
+        assert_eq_float!(sphere.intersect(&ray).unwrap().t(), 3.0);
+    }
+
+    #[test]
+    fn area_of_scaled_sphere() {
+        let sphere = Sphere::new(
+            &geometry::create_scaling(&Vector::new(2.0, 2.0, 2.0)),
+            1.5,
+            &Material::default(),
+        );
+
+        assert_eq_float!(sphere.area(), 4.0 * PI * 9.0);
+        assert_eq_float!(
+            sphere.pdf(&Point::new(3.0, 0.0, 0.0)),
+            1.0 / sphere.area()
+        );
+    }
+
+    #[test]
+    fn pdf_of_non_uniformly_scaled_sphere_integrates_to_one() {
+        let sphere = Sphere::new(
+            &geometry::create_scaling(&Vector::new(1.0, 0.5, 2.0)),
+            1.0,
+            &Material::default(),
+        );
+
+        // E[1 / pdf(X)] con X elegido por sample() es el área de la superficie
+        let n = 200_000;
+        let area = (0..n)
+            .map(|_| 1.0 / sphere.pdf(&sphere.sample(rand::random()).0))
+            .sum::<f64>()
+            / n as f64;
+
+        // el área exacta de este elipsoide es 15.8692...
+        assert!((area - 15.8692).abs() < 0.1);
+        // la aproximación de Thomsen tiene un error de hasta un 1%
+        assert!((sphere.area() - 15.8692).abs() < 0.16);
+    }
+
+    #[test]
+    fn sampled_point_is_on_surface() {
+        let sphere = Sphere::new(
+            &(geometry::create_translation(&Vector::new(1.0, 2.0, 3.0))
+                * geometry::create_scaling(&Vector::new(1.0, 3.0, 2.0))),
+            1.0,
+            &Material::default(),
+        );
+
+        for _ in 0..100 {
+            let (punto, normal) = sphere.sample(rand::random());
+
+            // tiro un rayo desde afuera hacia el punto, tiene que chocar ahí
+            let origen = punto + normal * 10.0;
+            let ray = Ray::new(&origen, &(punto - origen), f64::INFINITY);
+            let isect = sphere.intersect(&ray).unwrap();
+
+            assert!((isect.point() - punto).norm() < 1e-6);
+            assert!((isect.normal() - normal).norm() < 1e-6);
+        }
+    }
+
     #[test]
     fn aabb_in_sphere_without_transform() {
         let sphere =
@@ -203,7 +347,7 @@ mod tests {
         assert!(isect.is_some());
         let isect = isect.unwrap();
 
-        //assert_eq_float!(isect.t(), 0.5);
+        assert_eq_float!(isect.t(), 0.5);
 
         dbg!(&isect);
         assert_eq_vec!(isect.point(), &Point::new(0.0, 0.0, -0.5));
@@ -3,7 +3,8 @@ use crate::geometry::{
     intersect_ray_and_triangle, Normal, Point, Ray, Transform, AABB,
 };
 use crate::material::Material;
-use crate::shapes::{Intersection, Shape, ShapeOperations};
+use crate::sampling::uniform_sample_triangle;
+use crate::shapes::{Intersection, ShapeOperations};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Triangle {
@@ -12,6 +13,7 @@ pub struct Triangle {
     material: Material,
     caja: AABB, // bounding box en coordenadas globales
     normal: Normal,
+    uvs: [(f64, f64); 3], // coordenadas uv de cada vértice
 }
 
 impl Triangle {
@@ -28,9 +30,16 @@ impl Triangle {
             material: *material,
             caja: Triangle::get_box(p_1, p_2, p_3, local_to_world),
             normal: (p_2 - p_1).cross(&(p_3 - p_1)).normalize(),
+            // sin coordenadas propias, las uv son las baricéntricas
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
         }
     }
 
+    /// El mismo triángulo con las coordenadas uv dadas en cada vértice.
+    pub fn with_uvs(&self, uvs: [(f64, f64); 3]) -> Triangle {
+        Triangle { uvs, ..*self }
+    }
+
     /// Calcula el bounding box de un triángulo, pasando los puntos a coordenadas globales
     fn get_box(
         p_1: &Point,
@@ -63,6 +72,32 @@ impl Triangle {
     fn normal(&self, _punto: &Point) -> Normal {
         self.normal
     }
+
+    /// Vértices en coordenadas globales.
+    fn world_vertices(&self) -> [Point; 3] {
+        self.vértices.map(|v| self.local_to_world * v)
+    }
+
+    /// Las coordenadas uv en el punto de coordenadas baricéntricas (u, v).
+    fn uv(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        let [uv_1, uv_2, uv_3] = self.uvs;
+
+        (
+            uv_1.0 * (1.0 - u - v) + uv_2.0 * u + uv_3.0 * v,
+            uv_1.1 * (1.0 - u - v) + uv_2.1 * u + uv_3.1 * v,
+        )
+    }
+
+    /// Área del triángulo en el espacio de las coordenadas uv.
+    fn uv_area(&self) -> f64 {
+        let [uv_1, uv_2, uv_3] = self.uvs;
+        let (a, b) = (
+            (uv_2.0 - uv_1.0, uv_2.1 - uv_1.1),
+            (uv_3.0 - uv_1.0, uv_3.1 - uv_1.1),
+        );
+
+        0.5 * (a.0 * b.1 - a.1 * b.0).abs()
+    }
 }
 
 impl ShapeOperations for Triangle {
@@ -70,11 +105,11 @@ impl ShapeOperations for Triangle {
         &self.material
     }
 
-    fn intersect(&self, rayo: &Ray) -> Option<Intersection> {
+    fn intersect(&self, rayo: &Ray) -> Option<Intersection<'_>> {
         let local_ray = self.local_to_world.inverse() * rayo;
 
         match intersect_ray_and_triangle(&self.vértices, &local_ray) {
-            Some((t, ..)) => {
+            Some((t, u, v)) => {
                 let punto = match local_ray.at(t) {
                     Some(p) => self.local_to_world * p,
                     None => return None,
@@ -82,15 +117,20 @@ impl ShapeOperations for Triangle {
 
                 let normal = self.local_to_world * self.normal(&punto);
 
-                let model = Shape::from(*self);
-
-                Some(Intersection::new(
-                    &model,
+                // at() ya descartó los choques más allá de max_t, el t global
+                // es la distancia al origen del rayo
+                let mut choque = Intersection::new(
+                    &self.material,
                     &punto,
                     rayo,
                     &normal.normalize(),
-                    t / normal.norm(),
-                ))
+                    (punto - rayo.origin()).norm(),
+                );
+                choque.set_uv(self.uv((u, v)));
+                // la escala entre el área en uv y la del triángulo
+                choque.set_uv_density((self.uv_area() / self.area()).sqrt());
+
+                Some(choque)
             }
             None => None,
         }
@@ -99,6 +139,26 @@ impl ShapeOperations for Triangle {
     fn bounding_box(&self) -> &AABB {
         &self.caja
     }
+
+    fn area(&self) -> f64 {
+        let [p_1, p_2, p_3] = self.world_vertices();
+
+        0.5 * (p_2 - p_1).cross(&(p_3 - p_1)).norm()
+    }
+
+    fn sample(&self, u: (f64, f64)) -> (Point, Normal) {
+        let [p_1, p_2, p_3] = self.world_vertices();
+        let (b_0, b_1) = uniform_sample_triangle(u);
+
+        let punto = Point::from(
+            p_1.coords * b_0
+                + p_2.coords * b_1
+                + p_3.coords * (1.0 - b_0 - b_1),
+        );
+        let normal = (self.local_to_world * self.normal).normalize();
+
+        (punto, normal)
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -132,11 +192,36 @@ mod tests {
         assert_eq_vec!(isect.normal(), &Vector::new(0.0, 0.0, 1.0));
     }
 
+    #[test]
+    fn area_of_transformed_triangle() {
+        let triangle = Triangle::new(
+            &Point::new(0.0, 0.0, 0.0),
+            &Point::new(1.0, 0.0, 0.0),
+            &Point::new(0.0, 1.0, 0.0),
+            &geometry::create_scaling(&Vector::new(2.0, 3.0, 1.0)),
+            &Material::default(),
+        );
+
+        assert_eq_float!(triangle.area(), 3.0);
+
+        for _ in 0..100 {
+            let (punto, normal) = triangle.sample(rand::random());
+
+            assert_eq_float!(punto.z, 0.0);
+            assert!(punto.x >= 0.0 && punto.y >= 0.0);
+            assert!(punto.x / 2.0 + punto.y / 3.0 <= 1.0 + 1e-10);
+            assert_eq_vec!(normal, Vector::new(0.0, 0.0, 1.0));
+        }
+    }
+
     #[test]
     fn triangle_transformed_intersects_ray() {
         let translation =
             geometry::create_translation(&Vector::new(1.0, 0.0, 0.0));
-        let rotation = geometry::create_rotation(&Vector::x_axis(), 90.0);
+        let rotation = geometry::create_rotation(
+            &Vector::x_axis(),
+            std::f64::consts::FRAC_PI_2,
+        );
         let scaling = geometry::create_scaling(&Vector::new(2.0, 2.0, 2.0));
 
         let transform = translation * rotation * scaling;
@@ -162,6 +247,7 @@ mod tests {
         dbg!(isect.clone());
         assert_eq_float!(isect.t(), 1.0);
         assert_eq_vec!(isect.point(), &Point::new(1.5, 0.0, 1.0));
-        assert_eq_vec!(isect.normal(), &Vector::new(0.0, 1.0, 0.0));
+        // la rotación lleva el eje Z al -Y
+        assert_eq_vec!(isect.normal(), &Vector::new(0.0, -1.0, 0.0));
     }
 }
@@ -519,3 +519,37 @@ pub static RGB_ILLUM_2_SPECT_BLUE: [f32; N_R2S_SAMPLES] = [
     1.4878477178237029e-01, 1.6624255403475907e-01, 1.6997613960634927e-01,
     1.5769743995852967e-01, 1.9069090525482305e-01,
 ];
+
+// Índices de refracción complejos (eta + ik) de metales
+// valores de Johnson y Christy (1972), y de Rakić (1995) para el aluminio,
+// interpolados cada 50 nm
+
+const N_METAL_SAMPLES: usize = 7;
+
+pub static METAL_LAMBDA: [f32; N_METAL_SAMPLES] =
+    [400.0, 450.0, 500.0, 550.0, 600.0, 650.0, 700.0];
+
+pub static GOLD_ETA: [f32; N_METAL_SAMPLES] =
+    [1.66, 1.40, 0.97, 0.43, 0.25, 0.17, 0.16];
+pub static GOLD_K: [f32; N_METAL_SAMPLES] =
+    [1.96, 1.88, 1.87, 2.46, 2.98, 3.50, 4.00];
+
+pub static SILVER_ETA: [f32; N_METAL_SAMPLES] =
+    [0.05, 0.04, 0.05, 0.06, 0.06, 0.05, 0.04];
+pub static SILVER_K: [f32; N_METAL_SAMPLES] =
+    [2.10, 2.65, 3.10, 3.60, 4.00, 4.40, 4.80];
+
+pub static COPPER_ETA: [f32; N_METAL_SAMPLES] =
+    [1.18, 1.24, 1.12, 0.94, 0.27, 0.21, 0.21];
+pub static COPPER_K: [f32; N_METAL_SAMPLES] =
+    [2.21, 2.40, 2.60, 2.58, 3.41, 3.85, 4.20];
+
+pub static ALUMINIUM_ETA: [f32; N_METAL_SAMPLES] =
+    [0.49, 0.62, 0.77, 0.96, 1.20, 1.47, 1.83];
+pub static ALUMINIUM_K: [f32; N_METAL_SAMPLES] =
+    [4.86, 5.47, 6.08, 6.69, 7.26, 7.79, 8.31];
+
+pub static CHROMIUM_ETA: [f32; N_METAL_SAMPLES] =
+    [2.10, 2.42, 2.75, 3.03, 3.18, 3.26, 3.30];
+pub static CHROMIUM_K: [f32; N_METAL_SAMPLES] =
+    [3.20, 3.30, 3.33, 3.33, 3.33, 3.32, 3.30];
@@ -2,7 +2,7 @@ mod data;
 
 use crate::auxiliar;
 use std::cmp::PartialEq;
-use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, Neg, Sub};
 
 #[derive(Clone, Copy, Debug)]
 pub enum SpectrumType {
@@ -71,12 +71,50 @@ impl<const N: usize> CoefficientSpectrum<N> {
         (1.0 - t) * self + t * other
     }
 
+    /// El mayor de los coeficientes.
+    #[inline]
+    pub fn max_value(&self) -> f32 {
+        self.coefficients
+            .iter()
+            .fold(f32::NEG_INFINITY, |max, f| max.max(*f))
+    }
+
+    /// El promedio de los coeficientes.
+    #[inline]
+    pub fn average(&self) -> f32 {
+        self.coefficients.iter().sum::<f32>() / N as f32
+    }
+
+    /// Copia con todos los coeficientes en cero salvo el i-ésimo.
+    pub fn only_sample(&self, i: usize) -> CoefficientSpectrum<N> {
+        let mut result = CoefficientSpectrum::new(0.0);
+        result.coefficients[i] = self.coefficients[i];
+
+        result
+    }
+
     #[inline]
     fn has_nan(&self) -> bool {
         self.coefficients.iter().any(|f| f.is_nan())
     }
 }
 
+impl<const N: usize> Index<usize> for CoefficientSpectrum<N> {
+    type Output = f32;
+
+    #[inline]
+    fn index(&self, i: usize) -> &f32 {
+        &self.coefficients[i]
+    }
+}
+
+impl<const N: usize> IndexMut<usize> for CoefficientSpectrum<N> {
+    #[inline]
+    fn index_mut(&mut self, i: usize) -> &mut f32 {
+        &mut self.coefficients[i]
+    }
+}
+
 impl<const N: usize> Add for CoefficientSpectrum<N> {
     type Output = CoefficientSpectrum<N>;
 
@@ -186,6 +224,21 @@ impl<const N: usize> Mul<f32> for &CoefficientSpectrum<N> {
     }
 }
 
+impl<const N: usize> Mul<f32> for CoefficientSpectrum<N> {
+    type Output = CoefficientSpectrum<N>;
+
+    #[inline]
+    fn mul(self, rhs: f32) -> CoefficientSpectrum<N> {
+        let mut result = self.coefficients;
+
+        result.iter_mut().for_each(|f| *f *= rhs);
+
+        CoefficientSpectrum {
+            coefficients: result,
+        }
+    }
+}
+
 impl<const N: usize> Mul<&CoefficientSpectrum<N>> for f32 {
     type Output = CoefficientSpectrum<N>;
 
@@ -244,6 +297,21 @@ impl<const N: usize> Div<f32> for &CoefficientSpectrum<N> {
     }
 }
 
+impl<const N: usize> Div<f32> for CoefficientSpectrum<N> {
+    type Output = CoefficientSpectrum<N>;
+
+    #[inline]
+    fn div(self, rhs: f32) -> CoefficientSpectrum<N> {
+        let mut result = self.coefficients;
+
+        result.iter_mut().for_each(|f| *f /= rhs);
+
+        CoefficientSpectrum {
+            coefficients: result,
+        }
+    }
+}
+
 impl<const N: usize> Neg for CoefficientSpectrum<N> {
     type Output = CoefficientSpectrum<N>;
 
@@ -261,9 +329,19 @@ impl<const N: usize> Neg for CoefficientSpectrum<N> {
 
 const SAMPLED_LAMBDA_START: f32 = 400.0;
 const SAMPLED_LAMBDA_END: f32 = 700.0;
-const N_SAMPLES: usize = 60;
+pub const N_SAMPLES: usize = 60;
 pub type SampledSpectrum = CoefficientSpectrum<N_SAMPLES>;
 
+/// Longitud de onda, en nanómetros, del centro del intervalo que representa
+/// la muestra i de un SampledSpectrum.
+pub fn sample_wavelength(i: usize) -> f32 {
+    auxiliar::lerp(
+        SAMPLED_LAMBDA_START,
+        SAMPLED_LAMBDA_END,
+        (i as f32 + 0.5) / N_SAMPLES as f32,
+    )
+}
+
 // Funciones XYZ
 static mut CIE_X: SampledSpectrum = SampledSpectrum::new(0.0);
 static mut CIE_Y: SampledSpectrum = SampledSpectrum::new(0.0);
@@ -294,6 +372,12 @@ impl SampledSpectrum {
         let lambdas = samples.iter().map(|(l, _)| *l).collect::<Vec<_>>();
         let samples = samples.iter().map(|(_, s)| *s).collect::<Vec<_>>();
 
+        Self::from_table(&lambdas, &samples)
+    }
+
+    /// Crea un SPD a partir de una tabla de longitudes de onda ordenadas y
+    /// sus muestras.
+    fn from_table(lambdas: &[f32], samples: &[f32]) -> SampledSpectrum {
         let mut result = SampledSpectrum::new(0.0);
 
         for i in 0..N_SAMPLES {
@@ -309,12 +393,30 @@ impl SampledSpectrum {
             );
 
             result.coefficients[i] =
-                Self::average_spectrum_sample(&lambdas, &samples, l_0, l_1);
+                Self::average_spectrum_sample(lambdas, samples, l_0, l_1);
         }
 
         result
     }
 
+    /// Índice de refracción complejo (eta, k) de uno de los metales
+    /// conocidos: "Gold", "Silver", "Copper", "Aluminium" o "Chromium".
+    pub fn metal_ior(name: &str) -> Option<(SampledSpectrum, SampledSpectrum)> {
+        let (eta, k) = match name {
+            "Gold" => (&data::GOLD_ETA, &data::GOLD_K),
+            "Silver" => (&data::SILVER_ETA, &data::SILVER_K),
+            "Copper" => (&data::COPPER_ETA, &data::COPPER_K),
+            "Aluminium" => (&data::ALUMINIUM_ETA, &data::ALUMINIUM_K),
+            "Chromium" => (&data::CHROMIUM_ETA, &data::CHROMIUM_K),
+            _ => return None,
+        };
+
+        Some((
+            Self::from_table(&data::METAL_LAMBDA, eta),
+            Self::from_table(&data::METAL_LAMBDA, k),
+        ))
+    }
+
     /// Interpolo la muestra y calculo el área bajo la curva en el rango pedido.
     /// Luego lo divido por este rango.
     fn average_spectrum_sample(
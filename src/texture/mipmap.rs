@@ -0,0 +1,289 @@
+/// Color RGB lineal de un texel.
+pub type Rgb = [f32; 3];
+
+/// Qué se lee en las coordenadas que caen afuera de [0, 1].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Wrap {
+    /// La imagen se repite como un mosaico.
+    #[default]
+    Repeat,
+    /// Se repite el borde de la imagen.
+    Clamp,
+    /// La imagen se repite dada vuelta cada vez, así no se notan los cortes.
+    Mirror,
+}
+
+impl Wrap {
+    /// Lleva el índice i de un texel a [0, n).
+    fn apply(&self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(n),
+            Wrap::Clamp => i.clamp(0, n - 1),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n {
+                    i
+                } else {
+                    2 * n - 1 - i
+                }
+            }
+        };
+
+        i as usize
+    }
+}
+
+/// Cómo se combinan los texels que hay alrededor de un punto.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    /// Interpola los cuatro texels más cercanos de la imagen original.
+    Bilinear,
+    /// Interpola entre los dos niveles del mipmap cuyos texels miden más o
+    /// menos lo que el ancho del punto, con Bilinear en cada uno. Así las
+    /// texturas lejanas no tienen aliasing.
+    #[default]
+    Trilinear,
+}
+
+/// Una imagen y sus versiones reducidas, cada una con la mitad del ancho y
+/// del alto de la anterior hasta llegar a un solo texel. El texel (0, 0)
+/// es el de arriba a la izquierda.
+#[derive(Debug)]
+pub struct MipMap {
+    niveles: Vec<Nivel>,
+}
+
+#[derive(Debug)]
+struct Nivel {
+    ancho: usize,
+    alto: usize,
+    texels: Vec<Rgb>,
+}
+
+impl Nivel {
+    fn texel(&self, x: i64, y: i64, wrap: Wrap) -> Rgb {
+        let x = wrap.apply(x, self.ancho);
+        let y = wrap.apply(y, self.alto);
+
+        self.texels[y * self.ancho + x]
+    }
+
+    /// Promedio de los cuatro texels más cercanos al punto, pesados según
+    /// la distancia a sus centros.
+    fn bilinear(&self, (u, v): (f64, f64), wrap: Wrap) -> Rgb {
+        let x = u * self.ancho as f64 - 0.5;
+        let y = v * self.alto as f64 - 0.5;
+        let (x_0, y_0) = (x.floor(), y.floor());
+        let (dx, dy) = ((x - x_0) as f32, (y - y_0) as f32);
+        let (x_0, y_0) = (x_0 as i64, y_0 as i64);
+
+        let arriba = lerp(
+            self.texel(x_0, y_0, wrap),
+            self.texel(x_0 + 1, y_0, wrap),
+            dx,
+        );
+        let abajo = lerp(
+            self.texel(x_0, y_0 + 1, wrap),
+            self.texel(x_0 + 1, y_0 + 1, wrap),
+            dx,
+        );
+
+        lerp(arriba, abajo, dy)
+    }
+
+    /// El nivel siguiente del mipmap: cada texel es el promedio de cuatro
+    /// de este. Si un lado es impar, el último texel se repite.
+    fn reduce(&self) -> Nivel {
+        let ancho = self.ancho.div_ceil(2);
+        let alto = self.alto.div_ceil(2);
+
+        let texels = (0..alto)
+            .flat_map(|y| (0..ancho).map(move |x| (x as i64, y as i64)))
+            .map(|(x, y)| {
+                let texel =
+                    |dx, dy| self.texel(2 * x + dx, 2 * y + dy, Wrap::Clamp);
+                let arriba = lerp(texel(0, 0), texel(1, 0), 0.5);
+                let abajo = lerp(texel(0, 1), texel(1, 1), 0.5);
+
+                lerp(arriba, abajo, 0.5)
+            })
+            .collect();
+
+        Nivel {
+            ancho,
+            alto,
+            texels,
+        }
+    }
+}
+
+fn lerp(a: Rgb, b: Rgb, t: f32) -> Rgb {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// Pasa una componente de un color sRGB, como los de los PNG o JPEG, a su
+/// valor lineal.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl MipMap {
+    /// Mipmap de una imagen de ancho x alto con los texels por filas, de
+    /// arriba a abajo.
+    pub fn new(ancho: usize, alto: usize, texels: Vec<Rgb>) -> MipMap {
+        assert_eq!(texels.len(), ancho * alto);
+
+        let mut niveles = vec![Nivel {
+            ancho,
+            alto,
+            texels,
+        }];
+        while let Some(nivel) = niveles.last().filter(|n| n.ancho * n.alto > 1)
+        {
+            niveles.push(nivel.reduce());
+        }
+
+        MipMap { niveles }
+    }
+
+    /// Carga una imagen PNG, JPEG, BMP, TGA o HDR. Los HDR ya guardan
+    /// colores lineales; los demás se convierten desde sRGB si srgb es
+    /// true, y si no se toman como lineales, como en los mapas de
+    /// rugosidad.
+    pub fn open(archivo: &str, srgb: bool) -> Result<MipMap, anyhow::Error> {
+        let imagen = image::open(archivo).map_err(|err| {
+            anyhow::anyhow!("No se pudo cargar la imagen {archivo:?}: {err}")
+        })?;
+
+        let lineal = !srgb
+            || matches!(
+                imagen.color(),
+                image::ColorType::Rgb32F | image::ColorType::Rgba32F
+            );
+        let imagen = imagen.into_rgb32f();
+
+        let texels = imagen
+            .pixels()
+            .map(|pixel| {
+                pixel.0.map(|c| if lineal { c } else { srgb_to_linear(c) })
+            })
+            .collect();
+
+        Ok(MipMap::new(
+            imagen.width() as usize,
+            imagen.height() as usize,
+            texels,
+        ))
+    }
+
+    /// Color en el punto uv de un área de ancho width, en coordenadas uv.
+    /// Con ancho 0 se usa la imagen original.
+    pub fn lookup(
+        &self,
+        uv: (f64, f64),
+        width: f64,
+        filter: Filter,
+        wrap: Wrap,
+    ) -> Rgb {
+        match filter {
+            Filter::Bilinear => self.niveles[0].bilinear(uv, wrap),
+            Filter::Trilinear => {
+                // en el nivel l los texels miden 2^l / tamaño del original
+                let original = &self.niveles[0];
+                let tamaño = original.ancho.max(original.alto) as f64;
+                let último = (self.niveles.len() - 1) as f64;
+                let nivel = (width * tamaño).log2().clamp(0.0, último);
+
+                let l = nivel.floor() as usize;
+                if l as f64 == nivel {
+                    return self.niveles[l].bilinear(uv, wrap);
+                }
+
+                lerp(
+                    self.niveles[l].bilinear(uv, wrap),
+                    self.niveles[l + 1].bilinear(uv, wrap),
+                    (nivel - l as f64) as f32,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Imagen de 4x4 con la mitad izquierda negra y la derecha blanca.
+    fn mitades() -> MipMap {
+        let texels = (0..16)
+            .map(|i| if i % 4 < 2 { [0.0; 3] } else { [1.0; 3] })
+            .collect();
+
+        MipMap::new(4, 4, texels)
+    }
+
+    #[test]
+    fn niveles() {
+        let mipmap = MipMap::new(5, 3, vec![[0.5; 3]; 15]);
+        let tamaños = mipmap
+            .niveles
+            .iter()
+            .map(|n| (n.ancho, n.alto))
+            .collect::<Vec<_>>();
+
+        assert_eq!(tamaños, [(5, 3), (3, 2), (2, 1), (1, 1)]);
+        // promediar no cambia una imagen de un solo color
+        assert_eq!(mipmap.niveles[3].texels[0], [0.5; 3]);
+    }
+
+    #[test]
+    fn bilinear_y_bordes() {
+        let mipmap = mitades();
+        let gris =
+            |uv, wrap| mipmap.lookup(uv, 0.0, Filter::Bilinear, wrap)[0] as f64;
+
+        // en el centro de un texel vale lo mismo que el texel
+        assert_eq!(gris((0.125, 0.5), Wrap::Repeat), 0.0);
+        assert_eq!(gris((0.875, 0.5), Wrap::Repeat), 1.0);
+        // en el medio interpola
+        assert!((gris((0.5, 0.5), Wrap::Repeat) - 0.5).abs() < 1e-6);
+
+        // en el borde izquierdo se mezcla con el derecho solo si se repite
+        assert!((gris((0.0, 0.5), Wrap::Repeat) - 0.5).abs() < 1e-6);
+        assert_eq!(gris((0.0, 0.5), Wrap::Clamp), 0.0);
+        assert_eq!(gris((0.0, 0.5), Wrap::Mirror), 0.0);
+        assert_eq!(gris((1.125, 0.5), Wrap::Repeat), 0.0);
+        assert_eq!(gris((-0.125, 0.5), Wrap::Mirror), 0.0);
+        assert_eq!(gris((1.125, 0.5), Wrap::Mirror), 1.0);
+    }
+
+    #[test]
+    fn trilinear_promedia_de_lejos() {
+        let mipmap = mitades();
+        let gris = |width| {
+            mipmap.lookup((0.125, 0.5), width, Filter::Trilinear, Wrap::Clamp)
+                [0]
+        };
+
+        // de cerca es la imagen original, y de lejos el promedio
+        assert_eq!(gris(0.0), 0.0);
+        assert_eq!(gris(0.25), 0.0);
+        assert!((gris(1.0) - 0.5).abs() < 1e-6);
+        assert!((gris(10.0) - 0.5).abs() < 1e-6);
+        // entre los niveles 1 y 2
+        let medio = gris(0.7);
+        assert!(0.0 < medio && medio < 0.5);
+    }
+
+    #[test]
+    fn srgb() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,160 @@
+mod mipmap;
+
+pub use mipmap::{Filter, MipMap, Wrap};
+
+use crate::resources::{ImageId, Resources};
+use crate::spectrum::{SampledSpectrum, SpectrumType};
+
+/// Coordenadas uv de un punto de la superficie, con el ancho en uv de lo que
+/// abarca el punto visto desde la cámara, para filtrar las imágenes. Con
+/// ancho 0 no se filtran. resources son los de la escena del punto, donde
+/// están las imágenes y los materiales que los materiales usan por índice.
+#[derive(Clone, Copy, Debug)]
+pub struct TexCoord<'a> {
+    pub uv: (f64, f64),
+    pub width: f64,
+    pub resources: &'a Resources,
+}
+
+impl From<(f64, f64)> for TexCoord<'_> {
+    fn from(uv: (f64, f64)) -> Self {
+        TexCoord {
+            uv,
+            width: 0.0,
+            resources: Resources::empty(),
+        }
+    }
+}
+
+/// Valor que se puede leer de una imagen: un color, o un número que es el
+/// promedio de los canales.
+pub trait Texel: Copy {
+    fn from_rgb(rgb: [f32; 3]) -> Self;
+}
+
+impl Texel for SampledSpectrum {
+    fn from_rgb([r, g, b]: [f32; 3]) -> Self {
+        SampledSpectrum::from_RGB((r, g, b), SpectrumType::Reflectance)
+    }
+}
+
+impl Texel for f64 {
+    fn from_rgb(rgb: [f32; 3]) -> Self {
+        rgb.iter().sum::<f32>() as f64 / 3.0
+    }
+}
+
+/// Parámetro de un material que puede cambiar sobre la superficie, según las
+/// coordenadas uv del choque.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Texture<T> {
+    /// Vale lo mismo en toda la superficie.
+    Constant(T),
+    /// Tablero de ajedrez con scale casillas por unidad de u y de v.
+    Checkerboard { even: T, odd: T, scale: f64 },
+    /// Imagen repetida scale veces por unidad de u y de v. v = 0 es el borde
+    /// de arriba de la imagen.
+    Image {
+        image: ImageId,
+        filter: Filter,
+        wrap: Wrap,
+        scale: f64,
+    },
+}
+
+impl<T: Texel> Texture<T> {
+    /// Valor en el punto de coordenadas uv.
+    pub fn evaluate<'a>(&self, coords: impl Into<TexCoord<'a>>) -> T {
+        let TexCoord {
+            uv: (u, v),
+            width,
+            resources,
+        } = coords.into();
+
+        match *self {
+            Texture::Constant(valor) => valor,
+            Texture::Checkerboard { even, odd, scale } => {
+                let casilla =
+                    (u * scale).floor() as i64 + (v * scale).floor() as i64;
+                if casilla.rem_euclid(2) == 0 {
+                    even
+                } else {
+                    odd
+                }
+            }
+            Texture::Image {
+                image,
+                filter,
+                wrap,
+                scale,
+            } => T::from_rgb(resources.image(image).lookup(
+                (u * scale, v * scale),
+                width * scale,
+                filter,
+                wrap,
+            )),
+        }
+    }
+}
+
+impl<T> From<T> for Texture<T> {
+    fn from(valor: T) -> Self {
+        Texture::Constant(valor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tablero() {
+        let tablero = Texture::Checkerboard {
+            even: 0.0,
+            odd: 1.0,
+            scale: 2.0,
+        };
+
+        assert_eq!(tablero.evaluate((0.1, 0.1)), 0.0);
+        assert_eq!(tablero.evaluate((0.6, 0.1)), 1.0);
+        assert_eq!(tablero.evaluate((0.6, 0.6)), 0.0);
+        assert_eq!(tablero.evaluate((-0.1, 0.1)), 1.0);
+
+        assert_eq!(Texture::from(0.3).evaluate((0.6, 0.1)), 0.3);
+    }
+
+    #[test]
+    fn imagen() {
+        // 2x1, negro a la izquierda y blanco a la derecha
+        let mut recursos = Resources::new();
+        let imagen = recursos.add_image(
+            "prueba",
+            false,
+            mipmap::MipMap::new(2, 1, vec![[0.0; 3], [1.0; 3]]),
+        );
+        let textura = |scale| Texture::<f64>::Image {
+            image: imagen,
+            filter: Filter::Trilinear,
+            wrap: Wrap::Repeat,
+            scale,
+        };
+
+        let cerca = |uv| TexCoord {
+            uv,
+            width: 0.0,
+            resources: &recursos,
+        };
+
+        assert_eq!(textura(1.0).evaluate(cerca((0.25, 0.5))), 0.0);
+        assert_eq!(textura(1.0).evaluate(cerca((0.75, 0.5))), 1.0);
+        assert_eq!(textura(2.0).evaluate(cerca((0.375, 0.5))), 1.0);
+
+        // de lejos se ve el promedio
+        let lejos = TexCoord {
+            uv: (0.25, 0.5),
+            width: 1.0,
+            resources: &recursos,
+        };
+        assert_eq!(textura(1.0).evaluate(lejos), 0.5);
+    }
+}